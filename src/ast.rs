@@ -31,6 +31,16 @@ pub enum Statement {
     Ret(RetStatement),
     Exp(ExpStatement),
     Blk(BlkStatement),
+    Func(FuncStatement),
+    Struct(StructStatement),
+    Enum(EnumStatement),
+    Impl(ImplStatement),
+    Defer(DeferStatement),
+    Const(ConstStatement),
+    For(ForStatement),
+    Yield(YieldStatement),
+    Import(ImportStatement),
+    Export(ExportStatement),
 }
 
 impl Node for Statement {
@@ -39,26 +49,110 @@ impl Node for Statement {
             Statement::Let(stmt) => stmt.string(),
             Statement::Ret(stmt) => stmt.string(),
             Statement::Exp(stmt) => stmt.string(),
-            Statement::Blk(stmt) => stmt.string(),
+            Statement::Blk(stmt) => format!("{{ {} }}", stmt.string()),
+            Statement::Func(stmt) => stmt.string(),
+            Statement::Struct(stmt) => stmt.string(),
+            Statement::Enum(stmt) => stmt.string(),
+            Statement::Impl(stmt) => stmt.string(),
+            Statement::Defer(stmt) => stmt.string(),
+            Statement::Const(stmt) => stmt.string(),
+            Statement::For(stmt) => stmt.string(),
+            Statement::Yield(stmt) => stmt.string(),
+            Statement::Import(stmt) => stmt.string(),
+            Statement::Export(stmt) => stmt.string(),
         }
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct LetStatement {
-    pub ident:   Identifier,
+    pub pattern: Pattern,
     pub rhs_exp: Expression, 
+    pub mutable: bool,       // 'let mut pattern = expression;'
 }
 
 impl Node for LetStatement {
     fn string(&self) -> String {
-        format!("let {} = {};", self.ident.string(), self.rhs_exp.string())
+        if self.mutable {
+            format!("let mut {} = {};", self.pattern.string(), self.rhs_exp.string())
+        } else {
+            format!("let {} = {};", self.pattern.string(), self.rhs_exp.string())
+        }
     }
 }
 
 impl LetStatement {
-    pub fn new(ident: Identifier, rhs_exp: Expression) -> LetStatement {
-        LetStatement { ident, rhs_exp }
+    pub fn new(pattern: Pattern, rhs_exp: Expression, mutable: bool) -> LetStatement {
+        LetStatement { pattern, rhs_exp, mutable }
+    }
+}
+
+// 'import "path" as name;' binds the exports of the module in the file to the name
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ImportStatement {
+    pub path: String,
+    pub name: Identifier,
+}
+
+impl Node for ImportStatement {
+    fn string(&self) -> String {
+        format!("import \"{}\" as {};", self.path, self.name.string())
+    }
+}
+
+impl ImportStatement {
+    pub fn new(path: String, name: Identifier) -> ImportStatement {
+        ImportStatement { path, name }
+    }
+}
+
+// 'export' followed by a top-level 'let', 'const', 'fn', 'struct' or 'enum', which makes the
+// names it defines visible to modules importing this one
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExportStatement {
+    pub stmt: Box<Statement>,
+}
+
+impl Node for ExportStatement {
+    fn string(&self) -> String {
+        format!("export {}", self.stmt.string())
+    }
+}
+
+impl ExportStatement {
+    pub fn new(stmt: Statement) -> ExportStatement {
+        ExportStatement { stmt: Box::new(stmt) }
+    }
+
+    // Names defined by the exported statement
+    pub fn names(&self) -> Vec<&Identifier> {
+        match *self.stmt {
+            Statement::Let(ref stmt) => stmt.pattern.bindings(),
+            Statement::Const(ref stmt) => vec![&stmt.name],
+            Statement::Func(ref stmt) => vec![&stmt.name],
+            Statement::Struct(ref stmt) => vec![&stmt.name],
+            Statement::Enum(ref stmt) => vec![&stmt.name],
+            _ => Vec::new(),
+        }
+    }
+}
+
+// 'const NAME = expression;' where the expression consists of literals and other constants
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ConstStatement {
+    pub name:  Identifier,
+    pub value: Expression,
+}
+
+impl Node for ConstStatement {
+    fn string(&self) -> String {
+        format!("const {} = {};", self.name.string(), self.value.string())
+    }
+}
+
+impl ConstStatement {
+    pub fn new(name: Identifier, value: Expression) -> ConstStatement {
+        ConstStatement { name, value }
     }
 }
 
@@ -79,6 +173,62 @@ impl RetStatement {
     }
 }
 
+// 'defer expression;' which is evaluated when the enclosing block exits
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DeferStatement {
+    pub exp: Expression,
+}
+
+impl Node for DeferStatement {
+    fn string(&self) -> String {
+        format!("defer {};", self.exp.string())
+    }
+}
+
+impl DeferStatement {
+    pub fn new(exp: Expression) -> DeferStatement {
+        DeferStatement { exp }
+    }
+}
+
+// 'for (pattern in expression) { statements }' which runs the block for each item
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ForStatement {
+    pub pattern:  Pattern,
+    pub iterable: Expression,
+    pub body:     BlkStatement,
+}
+
+impl Node for ForStatement {
+    fn string(&self) -> String {
+        format!("for ({} in {}) {{ {} }}", self.pattern.string(), self.iterable.string(), self.body.string())
+    }
+}
+
+impl ForStatement {
+    pub fn new(pattern: Pattern, iterable: Expression, body: BlkStatement) -> ForStatement {
+        ForStatement { pattern, iterable, body }
+    }
+}
+
+// 'yield expression;' which suspends the enclosing generator function
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct YieldStatement {
+    pub exp: Expression,
+}
+
+impl Node for YieldStatement {
+    fn string(&self) -> String {
+        format!("yield {};", self.exp.string())
+    }
+}
+
+impl YieldStatement {
+    pub fn new(exp: Expression) -> YieldStatement {
+        YieldStatement { exp }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct ExpStatement {
     pub exp: Expression,
@@ -119,22 +269,148 @@ impl BlkStatement {
     }
 }
 
+// 'fn name(params) { body }' which is hoisted within its block
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FuncStatement {
+    pub name: Identifier,
+    pub func: FunctionExpression,
+}
+
+impl Node for FuncStatement {
+    fn string(&self) -> String {
+        format!(
+            "fn {}({}) {{ {} }}",
+            self.name.string(), params_string(&self.func.params, &self.func.rest), self.func.body.string()
+        )
+    }
+}
+
+impl FuncStatement {
+    pub fn new(name: Identifier, func: FunctionExpression) -> FuncStatement {
+        FuncStatement { name, func }
+    }
+}
+
+// 'struct Name { field, ... }'
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StructStatement {
+    pub name:   Identifier,
+    pub fields: Vec<Identifier>,
+}
+
+impl Node for StructStatement {
+    fn string(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|field| field.string()).collect();
+        format!("struct {} {{ {} }}", self.name.string(), fields.join(", "))
+    }
+}
+
+impl StructStatement {
+    pub fn new(name: Identifier, fields: Vec<Identifier>) -> StructStatement {
+        StructStatement { name, fields }
+    }
+}
+
+// 'enum Name { Variant, Variant(field, ...), ... }'
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EnumStatement {
+    pub name:     Identifier,
+    pub variants: Vec<EnumVariant>,
+}
+
+impl Node for EnumStatement {
+    fn string(&self) -> String {
+        let variants: Vec<String> = self.variants.iter().map(|variant| variant.string()).collect();
+        format!("enum {} {{ {} }}", self.name.string(), variants.join(", "))
+    }
+}
+
+impl EnumStatement {
+    pub fn new(name: Identifier, variants: Vec<EnumVariant>) -> EnumStatement {
+        EnumStatement { name, variants }
+    }
+}
+
+// 'impl Type { fn name(self, ...) { ... } ... }'. Functions taking 'self' first are methods,
+// and the others are associated functions called by 'Type::name(...)'.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ImplStatement {
+    pub ty:    Identifier,
+    pub funcs: Vec<FuncStatement>,
+}
+
+impl Node for ImplStatement {
+    fn string(&self) -> String {
+        let funcs: Vec<String> = self.funcs.iter().map(|func| func.string()).collect();
+        format!("impl {} {{ {} }}", self.ty.string(), funcs.join(" "))
+    }
+}
+
+impl ImplStatement {
+    pub fn new(ty: Identifier, funcs: Vec<FuncStatement>) -> ImplStatement {
+        ImplStatement { ty, funcs }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EnumVariant {
+    pub name:   Identifier,
+    pub fields: Option<Vec<Identifier>>, // None for unit variant
+}
+
+impl Node for EnumVariant {
+    fn string(&self) -> String {
+        match self.fields {
+            Some(ref fields) => {
+                let fields: Vec<String> = fields.iter().map(|field| field.string()).collect();
+                format!("{}({})", self.name.string(), fields.join(", "))
+            }
+            None => self.name.string(),
+        }
+    }
+}
+
+impl EnumVariant {
+    pub fn new(name: Identifier, fields: Option<Vec<Identifier>>) -> EnumVariant {
+        EnumVariant { name, fields }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Expression {
     Ident(Identifier),
     Int(Integer),
     Bool(Boolean),
     Str(StringLiteral),
+    Interp(InterpolatedString),
+    Null(NullLiteral),
+    Array(ArrayLiteral),
+    Tuple(TupleLiteral),
+    Set(SetLiteral),
+    Hash(HashLiteral),
+    Placeholder(Placeholder),
+    Struct(StructLiteral),
+    Path(PathExpression),
 
     // Without boxing two expression, compiler can't detect the size of Expression.
     Prefix(Box<PrefixExpression>),
     Infix(Box<InfixExpression>),
     Postfix(Box<PostfixExpression>),
+    Assign(Box<AssignExpression>),
 
     // Complex (not C) expression
     If(IfExpression),
+    Blk(BlkStatement),
+    Throw(ThrowExpression),
+    Try(TryExpression),
     Func(FunctionExpression),
+    Macro(MacroLiteral),
     Call(CallExpression),
+    MethodCall(MethodCallExpression),
+    Field(FieldExpression),
+    Index(IndexExpression),
+    Slice(SliceExpression),
+    Match(MatchExpression),
 }
 
 impl Node for Expression {
@@ -144,12 +420,31 @@ impl Node for Expression {
             Expression::Int(integer)   => integer.string(),
             Expression::Bool(boolean)  => boolean.string(),
             Expression::Str(string)    => string.string(),
+            Expression::Interp(interp) => interp.string(),
+            Expression::Null(null)     => null.string(),
+            Expression::Array(array)   => array.string(),
+            Expression::Tuple(tuple)   => tuple.string(),
+            Expression::Set(set)       => set.string(),
+            Expression::Hash(hash)     => hash.string(),
+            Expression::Placeholder(p) => p.string(),
+            Expression::Struct(lit)    => lit.string(),
+            Expression::Path(path)     => path.string(),
+            Expression::Assign(assign) => assign.string(),
+            Expression::Field(field)   => field.string(),
             Expression::Prefix(prefix) => prefix.string(),
             Expression::Infix(infix)   => infix.string(),
             Expression::If(if_exp)     => if_exp.string(),
+            Expression::Blk(blk)       => format!("{{ {} }}", blk.string()),
+            Expression::Throw(throw)   => throw.string(),
+            Expression::Try(try_exp)   => try_exp.string(),
             Expression::Func(func)     => func.string(),
+            Expression::Macro(mac)     => mac.string(),
             Expression::Call(call)     => call.string(),
+            Expression::MethodCall(mc) => mc.string(),
             Expression::Postfix(post)  => post.string(),
+            Expression::Index(index)   => index.string(),
+            Expression::Slice(slice)   => slice.string(),
+            Expression::Match(mat)     => mat.string(),
         }
     }
 }
@@ -225,6 +520,177 @@ impl StringLiteral {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StringPart {
+    Text(String),
+    Exp(Expression),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InterpolatedString {
+    pub parts: Vec<StringPart>,
+}
+
+impl Node for InterpolatedString {
+    fn string(&self) -> String {
+        let mut ret = String::new();
+        ret.push('"');
+        for part in self.parts.iter() {
+            match part {
+                StringPart::Text(text) => ret.push_str(text),
+                StringPart::Exp(exp)   => ret.push_str(format!("${{{}}}", exp.string()).as_str()),
+            }
+        }
+        ret.push('"');
+        ret
+    }
+}
+
+impl InterpolatedString {
+    pub fn new(parts: Vec<StringPart>) -> InterpolatedString {
+        InterpolatedString { parts }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NullLiteral;
+
+impl Node for NullLiteral {
+    fn string(&self) -> String {
+        "null".to_string()
+    }
+}
+
+impl NullLiteral {
+    pub fn new() -> NullLiteral {
+        NullLiteral {}
+    }
+}
+
+// 'Name { field: expression, ... }'
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct StructLiteral {
+    pub name:   Identifier,
+    pub fields: Vec<(Identifier, Expression)>,
+}
+
+impl Node for StructLiteral {
+    fn string(&self) -> String {
+        let fields: Vec<String> = self.fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name.string(), value.string()))
+            .collect();
+        format!("{} {{ {} }}", self.name.string(), fields.join(", "))
+    }
+}
+
+impl StructLiteral {
+    pub fn new(name: Identifier, fields: Vec<(Identifier, Expression)>) -> StructLiteral {
+        StructLiteral { name, fields }
+    }
+}
+
+// '_' in arguments of the right hand side of '|>', which is replaced with the piped value
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Placeholder;
+
+impl Node for Placeholder {
+    fn string(&self) -> String {
+        "_".to_string()
+    }
+}
+
+impl Placeholder {
+    pub fn new() -> Placeholder {
+        Placeholder {}
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ArrayLiteral {
+    pub elements: Vec<Expression>,
+}
+
+impl Node for ArrayLiteral {
+    fn string(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|elem| elem.string()).collect();
+        format!("[{}]", elements.join(", "))
+    }
+}
+
+impl ArrayLiteral {
+    pub fn new(elements: Vec<Expression>) -> ArrayLiteral {
+        ArrayLiteral { elements }
+    }
+}
+
+// '(a, b)', '(a,)' or '()', which isn't a grouped expression
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TupleLiteral {
+    pub elements: Vec<Expression>,
+}
+
+impl Node for TupleLiteral {
+    fn string(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|elem| elem.string()).collect();
+        tuple_string(elements)
+    }
+}
+
+impl TupleLiteral {
+    pub fn new(elements: Vec<Expression>) -> TupleLiteral {
+        TupleLiteral { elements }
+    }
+}
+
+// Elements in parentheses, with a trailing comma for a single element
+pub fn tuple_string(elements: Vec<String>) -> String {
+    match elements.len() {
+        1 => format!("({},)", elements[0]),
+        _ => format!("({})", elements.join(", ")),
+    }
+}
+
+// '#{a, b, ...}'
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SetLiteral {
+    pub elements: Vec<Expression>,
+}
+
+impl Node for SetLiteral {
+    fn string(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|elem| elem.string()).collect();
+        format!("#{{{}}}", elements.join(", "))
+    }
+}
+
+impl SetLiteral {
+    pub fn new(elements: Vec<Expression>) -> SetLiteral {
+        SetLiteral { elements }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HashLiteral {
+    pub pairs: Vec<(Expression, Expression)>,
+}
+
+impl Node for HashLiteral {
+    fn string(&self) -> String {
+        let pairs: Vec<String> = self.pairs
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.string(), value.string()))
+            .collect();
+        format!("{{{}}}", pairs.join(", "))
+    }
+}
+
+impl HashLiteral {
+    pub fn new(pairs: Vec<(Expression, Expression)>) -> HashLiteral {
+        HashLiteral { pairs }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct PrefixExpression {
     pub operator: TokenKind,
@@ -264,6 +730,25 @@ impl Node for InfixExpression {
     }
 }
 
+// 'target = expression' where target is an identifier, a field or an index expression
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AssignExpression {
+    pub target:  Expression,
+    pub rhs_exp: Expression,
+}
+
+impl Node for AssignExpression {
+    fn string(&self) -> String {
+        format!("({} = {})", self.target.string(), self.rhs_exp.string())
+    }
+}
+
+impl AssignExpression {
+    pub fn new(target: Expression, rhs_exp: Expression) -> AssignExpression {
+        AssignExpression { target, rhs_exp }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct PostfixExpression {
     pub operator: TokenKind,
@@ -283,6 +768,53 @@ impl PostfixExpression {
     }
 }
 
+// 'throw expression'
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ThrowExpression {
+    pub value: Box<Expression>,
+}
+
+impl Node for ThrowExpression {
+    fn string(&self) -> String {
+        format!("throw {}", self.value.string())
+    }
+}
+
+impl ThrowExpression {
+    pub fn new(value: Expression) -> ThrowExpression {
+        ThrowExpression { value: Box::new(value) }
+    }
+}
+
+// 'try { ... } catch (e) { ... } finally { ... }' where either catch or finally can be omitted
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TryExpression {
+    pub body:    BlkStatement,
+    pub catch:   Option<(Identifier, BlkStatement)>,
+    pub finally: Option<BlkStatement>,
+}
+
+impl Node for TryExpression {
+    fn string(&self) -> String {
+        let mut ret = format!("try {{ {} }}", self.body.string());
+        if let Some((ref ident, ref blk)) = self.catch {
+            ret.push_str(&format!(" catch ( {} ) {{ {} }}", ident.string(), blk.string()));
+        }
+        if let Some(ref blk) = self.finally {
+            ret.push_str(&format!(" finally {{ {} }}", blk.string()));
+        }
+        ret
+    }
+}
+
+impl TryExpression {
+    pub fn new(
+        body: BlkStatement, catch: Option<(Identifier, BlkStatement)>, finally: Option<BlkStatement>
+    ) -> TryExpression {
+        TryExpression { body, catch, finally }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IfExpression {
     pub condition:   Box<Expression>,
@@ -322,66 +854,449 @@ impl IfExpression {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Parameter {
+    pub pattern: Pattern,
+    pub default: Option<Expression>, // 'pattern = expression'
+}
+
+impl Node for Parameter {
+    fn string(&self) -> String {
+        match self.default {
+            Some(ref default) => format!("{} = {}", self.pattern.string(), default.string()),
+            None => self.pattern.string(),
+        }
+    }
+}
+
+impl Parameter {
+    pub fn new(pattern: Pattern, default: Option<Expression>) -> Parameter {
+        Parameter { pattern, default }
+    }
+
+    // Name of the parameter which can be specified by keyword argument
+    pub fn name(&self) -> Option<&str> {
+        match self.pattern {
+            Pattern::Binding(ref ident) => Some(ident.name.as_str()),
+            _ => None,
+        }
+    }
+}
+
+// String of parameters such as 'x, y = 10, ...rest'
+pub fn params_string(params: &[Parameter], rest: &Option<Identifier>) -> String {
+    let mut params: Vec<String> = params.iter().map(|param| param.string()).collect();
+    if let Some(rest) = rest {
+        params.push(format!("...{}", rest.string()));
+    }
+    params.join(", ")
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct FunctionExpression {
-    pub params: Vec<Identifier>,
+    pub params: Vec<Parameter>,
+    pub rest:   Option<Identifier>, // '...rest' collects the remaining arguments
     pub body:   BlkStatement,
+    pub generator: bool, // Set if the body contains 'yield'
 }
 
 impl Node for FunctionExpression {
     fn string(&self) -> String {
-        let mut ret = String::new();
-        ret.push_str("fn(");
+        format!("fn({}) {{ {} }}", params_string(&self.params, &self.rest), self.body.string())
+    }
+}
 
-        for param in self.params.iter() {
-            ret.push_str(param.string().as_str());
-            ret.push_str(", ");
-        }
+impl FunctionExpression {
+    pub fn new(
+        params: Vec<Parameter>,
+        rest: Option<Identifier>,
+        body: BlkStatement,
+        generator: bool,
+    ) -> FunctionExpression {
+        FunctionExpression { params, rest, body, generator }
+    }
+}
 
-        // Remove ", "
-        ret.pop();
-        ret.pop();
+// 'macro(identifier, ...) { statements }' which is expanded before evaluation
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MacroLiteral {
+    pub params: Vec<Identifier>,
+    pub body:   BlkStatement,
+}
 
-        ret.push_str(") { ");
-        ret.push_str(self.body.string().as_str());
-        ret.push_str(" }");
-        ret
+impl Node for MacroLiteral {
+    fn string(&self) -> String {
+        let params: Vec<String> = self.params.iter().map(|param| param.string()).collect();
+        format!("macro({}) {{ {} }}", params.join(", "), self.body.string())
     }
 }
 
-impl FunctionExpression {
-    pub fn new(params: Vec<Identifier>, body: BlkStatement) -> FunctionExpression {
-        FunctionExpression { params, body }
+impl MacroLiteral {
+    pub fn new(params: Vec<Identifier>, body: BlkStatement) -> MacroLiteral {
+        MacroLiteral { params, body }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Argument {
+    Positional(Expression),
+    Keyword(Identifier, Expression), // 'name: expression'
+    Spread(Expression),              // '...expression'
+}
+
+impl Node for Argument {
+    fn string(&self) -> String {
+        match self {
+            Argument::Positional(exp)   => exp.string(),
+            Argument::Keyword(name, exp) => format!("{}: {}", name.string(), exp.string()),
+            Argument::Spread(exp)       => format!("...{}", exp.string()),
+        }
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct CallExpression {
-    pub ident: Box<Expression>, // Identifier or FunctionExpression
-    pub args:  Vec<Expression>,
+    pub ident: Box<Expression>, // Any expression which evaluates to a function
+    pub args:  Vec<Argument>,
+    pub optional: bool,         // 'f?.(...)' evaluates to null if 'f' is null
 }
 
 impl Node for CallExpression {
     fn string(&self) -> String {
-        let mut ret = String::new();
-        ret.push_str(format!("{}(", self.ident.string()).as_str());
+        let args: Vec<String> = self.args.iter().map(|arg| arg.string()).collect();
+        if self.optional {
+            format!("{}?.({})", self.ident.string(), args.join(", "))
+        } else {
+            format!("{}({})", self.ident.string(), args.join(", "))
+        }
+    }
+}
 
-        for arg in self.args.iter() {
-            ret.push_str(arg.string().as_str());
-            ret.push_str(", ");
+impl CallExpression {
+    pub fn new(ident: Expression, args: Vec<Argument>, optional: bool) -> CallExpression {
+        CallExpression { ident: Box::new(ident), args, optional }
+    }
+}
+
+// 'receiver.name(args)' which calls 'name' with the receiver as the first argument
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MethodCallExpression {
+    pub receiver: Box<Expression>,
+    pub name:     Identifier,
+    pub args:     Vec<Argument>,
+    pub optional: bool,         // 'a?.name(...)' evaluates to null if 'a' is null
+}
+
+impl Node for MethodCallExpression {
+    fn string(&self) -> String {
+        let args: Vec<String> = self.args.iter().map(|arg| arg.string()).collect();
+        if self.optional {
+            format!("{}?.{}({})", self.receiver.string(), self.name.string(), args.join(", "))
+        } else {
+            format!("{}.{}({})", self.receiver.string(), self.name.string(), args.join(", "))
         }
+    }
+}
+
+impl MethodCallExpression {
+    pub fn new(receiver: Expression, name: Identifier, args: Vec<Argument>, optional: bool) -> MethodCallExpression {
+        MethodCallExpression { receiver: Box::new(receiver), name, args, optional }
+    }
+}
 
-        // Remove ", "
-        ret.pop();
-        ret.pop();
+// 'Type::name'
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PathExpression {
+    pub ty:   Identifier,
+    pub name: Identifier,
+}
 
-        ret.push(')');
-        ret
+impl Node for PathExpression {
+    fn string(&self) -> String {
+        format!("{}::{}", self.ty.string(), self.name.string())
     }
 }
 
-impl CallExpression {
-    pub fn new(ident: Expression, args: Vec<Expression>) -> CallExpression {
-        CallExpression { ident: Box::new(ident), args }
+impl PathExpression {
+    pub fn new(ty: Identifier, name: Identifier) -> PathExpression {
+        PathExpression { ty, name }
+    }
+}
+
+// 'expression.name'
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FieldExpression {
+    pub lhs_exp:  Box<Expression>,
+    pub name:     Identifier,
+    pub optional: bool,         // 'a?.name' evaluates to null if 'a' is null
+}
+
+impl Node for FieldExpression {
+    fn string(&self) -> String {
+        if self.optional {
+            format!("{}?.{}", self.lhs_exp.string(), self.name.string())
+        } else {
+            format!("{}.{}", self.lhs_exp.string(), self.name.string())
+        }
+    }
+}
+
+impl FieldExpression {
+    pub fn new(lhs_exp: Expression, name: Identifier, optional: bool) -> FieldExpression {
+        FieldExpression { lhs_exp: Box::new(lhs_exp), name, optional }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IndexExpression {
+    pub lhs_exp: Box<Expression>,
+    pub index:   Box<Expression>,
+    pub optional: bool,         // 'a?.[...]' evaluates to null if 'a' is null
+}
+
+impl Node for IndexExpression {
+    fn string(&self) -> String {
+        if self.optional {
+            format!("({}?.[{}])", self.lhs_exp.string(), self.index.string())
+        } else {
+            format!("({}[{}])", self.lhs_exp.string(), self.index.string())
+        }
+    }
+}
+
+impl IndexExpression {
+    pub fn new(lhs_exp: Expression, index: Expression, optional: bool) -> IndexExpression {
+        IndexExpression { lhs_exp: Box::new(lhs_exp), index: Box::new(index), optional }
+    }
+}
+
+// 'a[start:end]' or 'a[start:end:step]', where each bound can be omitted
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SliceExpression {
+    pub lhs_exp: Box<Expression>,
+    pub start:   Option<Box<Expression>>,
+    pub end:     Option<Box<Expression>>,
+    pub step:    Option<Box<Expression>>,
+    pub optional: bool,         // 'a?.[...]' evaluates to null if 'a' is null
+}
+
+impl Node for SliceExpression {
+    fn string(&self) -> String {
+        let bound = |exp: &Option<Box<Expression>>| exp.as_ref().map(|exp| exp.string()).unwrap_or_default();
+        let mut bounds = format!("{}:{}", bound(&self.start), bound(&self.end));
+        if self.step.is_some() {
+            bounds = format!("{}:{}", bounds, bound(&self.step));
+        }
+        if self.optional {
+            format!("({}?.[{}])", self.lhs_exp.string(), bounds)
+        } else {
+            format!("({}[{}])", self.lhs_exp.string(), bounds)
+        }
+    }
+}
+
+impl SliceExpression {
+    pub fn new(
+        lhs_exp: Expression,
+        start: Option<Expression>,
+        end: Option<Expression>,
+        step: Option<Expression>,
+        optional: bool,
+    ) -> SliceExpression {
+        SliceExpression {
+            lhs_exp: Box::new(lhs_exp),
+            start: start.map(Box::new),
+            end: end.map(Box::new),
+            step: step.map(Box::new),
+            optional,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Pattern {
+    Wildcard,                      // '_'
+    Literal(Expression),           // Integer, string, boolean or null
+    Binding(Identifier),           // Bind the value to the identifier
+    Range(Integer, Integer, bool), // 'start..end' or 'start..=end' if the flag is set
+    Or(Vec<Pattern>),              // 'pattern | pattern | ...'
+
+    // '[pattern, ...]' with optional rest pattern '...rest'
+    Array(Vec<Pattern>, Option<Box<Pattern>>),
+    // '(pattern, ...)' matching a tuple of the same length
+    Tuple(Vec<Pattern>),
+    // '{key: pattern, ...}' with optional rest pattern '...rest'. '{key}' means '{key: key}'.
+    Hash(Vec<(String, Pattern)>, Option<Box<Pattern>>),
+    // 'Enum::Variant' or 'Enum::Variant(pattern, ...)'
+    Variant(PathExpression, Option<Vec<Pattern>>),
+}
+
+impl Node for Pattern {
+    fn string(&self) -> String {
+        match self {
+            Pattern::Wildcard         => "_".to_string(),
+            Pattern::Literal(exp)     => exp.string(),
+            Pattern::Binding(ident)   => ident.string(),
+            Pattern::Range(start, end, inclusive) => {
+                match inclusive {
+                    true  => format!("{}..={}", start.string(), end.string()),
+                    false => format!("{}..{}", start.string(), end.string()),
+                }
+            }
+            Pattern::Or(patterns) => {
+                let patterns: Vec<String> = patterns.iter().map(|pat| pat.string()).collect();
+                patterns.join(" | ")
+            }
+            Pattern::Array(patterns, rest) => {
+                let mut patterns: Vec<String> = patterns.iter().map(|pat| pat.string()).collect();
+                if let Some(rest) = rest {
+                    patterns.push(format!("...{}", rest.string()));
+                }
+                format!("[{}]", patterns.join(", "))
+            }
+            Pattern::Tuple(patterns) => tuple_string(patterns.iter().map(|pat| pat.string()).collect()),
+            Pattern::Hash(pairs, rest) => {
+                let mut pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, pat)| match pat {
+                        Pattern::Binding(ident) if ident.name == *key => key.clone(),
+                        pat => format!("{}: {}", key, pat.string()),
+                    })
+                    .collect();
+                if let Some(rest) = rest {
+                    pairs.push(format!("...{}", rest.string()));
+                }
+                format!("{{{}}}", pairs.join(", "))
+            }
+            Pattern::Variant(path, Some(patterns)) => {
+                let patterns: Vec<String> = patterns.iter().map(|pat| pat.string()).collect();
+                format!("{}({})", path.string(), patterns.join(", "))
+            }
+            Pattern::Variant(path, None) => path.string(),
+        }
+    }
+}
+
+impl Pattern {
+    // Identifiers bound by the pattern
+    pub fn bindings(&self) -> Vec<&Identifier> {
+        match self {
+            Pattern::Binding(ident) => vec![ident],
+            Pattern::Or(patterns) | Pattern::Tuple(patterns) => {
+                patterns.iter().flat_map(|pat| pat.bindings()).collect()
+            }
+            Pattern::Array(patterns, rest) => {
+                patterns.iter().chain(rest.as_deref()).flat_map(|pat| pat.bindings()).collect()
+            }
+            Pattern::Hash(pairs, rest) => {
+                pairs.iter().map(|(_, pat)| pat).chain(rest.as_deref()).flat_map(|pat| pat.bindings()).collect()
+            }
+            Pattern::Variant(_, Some(patterns)) => patterns.iter().flat_map(|pat| pat.bindings()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Whether the pattern matches any value
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Binding(_) => true,
+            Pattern::Or(patterns) => patterns.iter().any(|pat| pat.is_irrefutable()),
+            _ => false,
+        }
+    }
+
+    // Whether the pattern only takes the value apart without comparing it with literals or
+    // ranges. Such patterns can bind in 'let', parameters and 'for', where they fail only on
+    // the shape of the value.
+    pub fn is_destructuring(&self) -> bool {
+        match self {
+            Pattern::Wildcard | Pattern::Binding(_) => true,
+            Pattern::Literal(_) | Pattern::Range(..) => false,
+            Pattern::Or(patterns) | Pattern::Tuple(patterns) => patterns.iter().all(|pat| pat.is_destructuring()),
+            Pattern::Array(patterns, rest) => {
+                patterns.iter().chain(rest.as_deref()).all(|pat| pat.is_destructuring())
+            }
+            Pattern::Hash(pairs, rest) => {
+                pairs.iter().map(|(_, pat)| pat).chain(rest.as_deref()).all(|pat| pat.is_destructuring())
+            }
+            Pattern::Variant(_, patterns) => patterns.iter().flatten().all(|pat| pat.is_destructuring()),
+        }
+    }
+
+    // Whether every value matched by 'other' is also matched by this pattern
+    pub fn covers(&self, other: &Pattern) -> bool {
+        if self.is_irrefutable() {
+            return true;
+        }
+        match (self, other) {
+            (_, Pattern::Or(others)) => others.iter().all(|other| self.covers(other)),
+            (Pattern::Or(patterns), other) => patterns.iter().any(|pat| pat.covers(other)),
+            (Pattern::Literal(lhs), Pattern::Literal(rhs)) => lhs == rhs,
+            (Pattern::Range(start, end, inclusive), Pattern::Literal(Expression::Int(int))) => {
+                start.value <= int.value && (int.value < end.value || *inclusive && int.value == end.value)
+            }
+            (Pattern::Range(start, end, inclusive), Pattern::Range(other_start, other_end, other_inclusive)) => {
+                let last       = if *inclusive { Some(end.value) } else { end.value.checked_sub(1) };
+                let other_last = if *other_inclusive { Some(other_end.value) } else { other_end.value.checked_sub(1) };
+                match (last, other_last) {
+                    (Some(last), Some(other_last)) => start.value <= other_start.value && other_last <= last,
+                    _ => false,
+                }
+            }
+            (Pattern::Tuple(patterns), Pattern::Tuple(others)) => {
+                patterns.len() == others.len()
+                    && patterns.iter().zip(others.iter()).all(|(pat, other)| pat.covers(other))
+            }
+            (Pattern::Variant(path, None), Pattern::Variant(other_path, None)) => path == other_path,
+            (Pattern::Variant(path, Some(patterns)), Pattern::Variant(other_path, Some(others))) => {
+                path == other_path
+                    && patterns.len() == others.len()
+                    && patterns.iter().zip(others.iter()).all(|(pat, other)| pat.covers(other))
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard:   Option<Expression>,
+    pub body:    BlkStatement,
+}
+
+impl Node for MatchArm {
+    fn string(&self) -> String {
+        match self.guard {
+            Some(ref guard) => {
+                format!("{} if {} => {{ {} }}", self.pattern.string(), guard.string(), self.body.string())
+            }
+            None => format!("{} => {{ {} }}", self.pattern.string(), self.body.string()),
+        }
+    }
+}
+
+impl MatchArm {
+    pub fn new(pattern: Pattern, guard: Option<Expression>, body: BlkStatement) -> MatchArm {
+        MatchArm { pattern, guard, body }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MatchExpression {
+    pub subject: Box<Expression>,
+    pub arms:    Vec<MatchArm>,
+}
+
+impl Node for MatchExpression {
+    fn string(&self) -> String {
+        let arms: Vec<String> = self.arms.iter().map(|arm| arm.string()).collect();
+        format!("match ( {} ) {{ {} }}", self.subject.string(), arms.join(", "))
+    }
+}
+
+impl MatchExpression {
+    pub fn new(subject: Expression, arms: Vec<MatchArm>) -> MatchExpression {
+        MatchExpression { subject: Box::new(subject), arms }
     }
 }
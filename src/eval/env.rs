@@ -1,9 +1,15 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use super::object::Object;
 
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub obj: Object,
+    pub mutable: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Env {
-    pub map: HashMap<String, Object>,
+    pub map: HashMap<String, Binding>,
     pub outer: Option<Rc<RefCell<Env>>>,
 }
 
@@ -18,7 +24,7 @@ impl Env {
 
     pub fn get(&self, name: &String) -> Option<Object> {
         match self.map.get(name) {
-            Some(obj) => Some(obj.clone()),
+            Some(binding) => Some(binding.obj.clone()),
             None => match self.outer {
                 Some(ref env) => env.borrow().get(name),
                 None => None,
@@ -26,7 +32,32 @@ impl Env {
         }
     }
 
+    // Define a mutable binding, replacing the existing one
     pub fn set(&mut self, name: String, obj: Object) {
-        self.map.insert(name, obj);
+        self.map.insert(name, Binding { obj, mutable: true });
+    }
+
+    // Define a binding by 'let' or 'const', which can't replace an immutable one in this scope
+    pub fn define(&mut self, name: String, obj: Object, mutable: bool) -> Result<(), String> {
+        if let Some(Binding { mutable: false, .. }) = self.map.get(&name) {
+            return Err(format!("Cannot redefine immutable binding {}", name));
+        }
+        self.map.insert(name, Binding { obj, mutable });
+        Ok(())
+    }
+
+    // Update the nearest existing variable
+    pub fn assign(&mut self, name: &String, obj: Object) -> Result<(), String> {
+        match self.map.get_mut(name) {
+            Some(Binding { mutable: false, .. }) => Err(format!("Cannot assign to immutable binding {}", name)),
+            Some(binding) => {
+                binding.obj = obj;
+                Ok(())
+            }
+            None => match self.outer {
+                Some(ref env) => env.borrow_mut().assign(name, obj),
+                None => Err(format!("Identifier not found: {}", name)),
+            }
+        }
     }
 }
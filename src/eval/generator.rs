@@ -0,0 +1,367 @@
+use crate::ast::{BlkStatement, ExpStatement, Expression, Pattern, Statement};
+use super::{
+    Eval,
+    env::Env,
+    object::{Object, ObjectTrait, ErrorObj, Function, StringObj, Native, Null, HashObj, HashKey, Boolean},
+    task::{Resumption, Started, Wait},
+};
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+// A generator runs the body of a generator function on its own frames instead of the Rust stack,
+// so that it can be suspended at 'yield' and resumed later. Blocks, 'if' and 'for' statements are
+// run on frames, and 'yield' nested in any other expression is an error. 'return' finishes the
+// generator, and its value is discarded. Generators are shared by reference, so every copy of one
+// is advanced together.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    name: Option<String>,
+    signature: String,
+    state: Rc<RefCell<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    frames: Vec<Frame>, // Empty once the body has finished
+    running: bool,
+}
+
+// A block being run, with the statements left
+#[derive(Debug)]
+pub struct Frame {
+    stmts: VecDeque<Statement>,
+    pub env: Rc<RefCell<Env>>,
+    defers: Vec<(Expression, Rc<RefCell<Env>>)>,
+    // Set for the body of 'for', which is restarted for each item
+    looping: Option<Loop>,
+}
+
+#[derive(Debug)]
+struct Loop {
+    pattern: Pattern,
+    items: Items,
+    body: BlkStatement,
+    env: Rc<RefCell<Env>>, // The env 'for' is run in
+}
+
+// Where running frames stopped
+pub enum Step {
+    Yield(Object),
+    Wait(Wait, Resumption), // Only for tasks
+    Done(Option<Object>),   // With the error if failed
+}
+
+// Items iterated by 'for'
+#[derive(Debug)]
+pub enum Items {
+    Values(std::vec::IntoIter<Object>),
+    Generator(Generator),
+}
+
+impl Generator {
+    pub fn is(&self, other: &Generator) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+
+    // The frames left, which finishes the generator
+    pub fn take_frames(&self) -> Vec<Frame> {
+        std::mem::take(&mut self.state.borrow_mut().frames)
+    }
+}
+
+impl ObjectTrait for Generator {
+    fn inspect(&self) -> String {
+        format!("<generator {}>", self.signature)
+    }
+}
+
+impl Eval {
+    // A generator suspended before the first statement of the body. The current env must be the
+    // one where the arguments are bound.
+    pub(super) fn generator(&mut self, func: &Function) -> Object {
+        let frame = match self.frame(func.body.statements.clone()) {
+            Ok(frame) => frame,
+            Err(err) => return err,
+        };
+        let state = State { frames: vec![frame], running: false };
+        Object::Generator(
+            Generator { name: func.name.clone(), signature: func.signature(), state: Rc::new(RefCell::new(state)) }
+        )
+    }
+
+    // Run the generator until the next 'yield' and give the yielded value. None is returned once
+    // the body has finished. An error finishes the generator as well.
+    pub(super) fn resume(&mut self, gen: &Generator) -> Option<Object> {
+        let mut frames = {
+            let mut state = gen.state.borrow_mut();
+            if state.running {
+                return Some(
+                    Object::Err(ErrorObj::new(format!("Generator {} is already running", gen.signature)))
+                );
+            }
+            state.running = true;
+            std::mem::take(&mut state.frames)
+        };
+
+        let curr_env = Rc::clone(&self.env);
+        let step = self.run_frames(&mut frames, false);
+        self.env = curr_env;
+
+        let mut state = gen.state.borrow_mut();
+        state.running = false;
+        state.frames = frames;
+        let ret = match step {
+            Step::Yield(value) => Some(value),
+            Step::Done(err) => err,
+            Step::Wait(..) => None, // Generators don't wait
+        };
+        match ret {
+            Some(Object::Err(mut err)) => {
+                err.trace.push(gen.name.clone().unwrap_or_else(|| "<anonymous>".to_string()));
+                Some(Object::Err(err))
+            }
+            ret => ret,
+        }
+    }
+
+    // 'generator.next()' gives {"value": value, "done": false} for a yielded value, and
+    // {"value": null, "done": true} once the body has finished, so that the end can be told apart
+    // from 'yield null'. An error is given as it is.
+    pub(super) fn next_result(&mut self, gen: &Generator) -> Object {
+        let (value, done) = match self.resume(gen) {
+            Some(Object::Err(err)) => return Object::Err(err),
+            Some(value) => (value, false),
+            None => (Object::Null(Null::new()), true),
+        };
+        let mut ret = HashObj::new();
+        for (key, value) in [("value", value), ("done", Object::Bool(Boolean::new(done)))] {
+            ret.set(HashKey::Str(key.to_string()), Object::Str(StringObj::new(key.to_string())), value);
+        }
+        Object::Hash(ret)
+    }
+
+    // Run the frames of a generator, or a task if 'task' is set
+    pub(super) fn run_frames(&mut self, frames: &mut Vec<Frame>, task: bool) -> Step {
+        loop {
+            let frame = match frames.last_mut() {
+                Some(frame) => frame,
+                None => return Step::Done(None),
+            };
+            let stmt = match frame.stmts.pop_front() {
+                Some(stmt) => stmt,
+                None => {
+                    // Deferred expressions run at the end of each iteration, like blocks
+                    let defers = std::mem::take(&mut frame.defers);
+                    if let Some(err) = self.run_defers(defers) {
+                        return Step::Done(self.unwind(frames, err));
+                    }
+                    let Some(frame) = frames.last_mut() else {
+                        return Step::Done(None);
+                    };
+                    match self.next_iteration(frame) {
+                        Ok(true) => {}
+                        Ok(false) => {
+                            frames.pop();
+                        }
+                        Err(err) => return Step::Done(self.unwind(frames, err)),
+                    }
+                    continue;
+                }
+            };
+
+            self.env = Rc::clone(&frame.env);
+            match stmt {
+                Statement::Yield(stmt) => {
+                    let value = self.expr(stmt.exp);
+                    if self.is_abrupt(&value) {
+                        return Step::Done(self.unwind(frames, value));
+                    }
+                    return Step::Yield(value);
+                }
+                Statement::Defer(stmt) => {
+                    frame.defers.push((stmt.exp, Rc::clone(&self.env)));
+                }
+                Statement::Blk(blk) => {
+                    match self.frame(blk.statements) {
+                        Ok(frame) => frames.push(frame),
+                        Err(err) => return Step::Done(self.unwind(frames, err)),
+                    }
+                }
+                Statement::Exp(ExpStatement { exp: Expression::If(if_expr) }) => {
+                    let cond = self.expr(*if_expr.condition);
+                    if self.is_abrupt(&cond) {
+                        return Step::Done(self.unwind(frames, cond));
+                    }
+                    let blk = match (self.is_truthy(&cond), if_expr.alternative) {
+                        (true, _) => if_expr.consequence,
+                        (false, Some(blk)) => blk,
+                        (false, None) => {
+                            let err = Object::Err(ErrorObj::new("No else found".to_string()));
+                            return Step::Done(self.unwind(frames, err));
+                        }
+                    };
+                    match self.frame(blk.statements) {
+                        Ok(frame) => frames.push(frame),
+                        Err(err) => return Step::Done(self.unwind(frames, err)),
+                    }
+                }
+                Statement::For(stmt) => {
+                    let iterable = self.expr(stmt.iterable);
+                    if self.is_abrupt(&iterable) {
+                        return Step::Done(self.unwind(frames, iterable));
+                    }
+                    let items = match self.iterate(iterable) {
+                        Ok(items) => items,
+                        Err(err) => return Step::Done(self.unwind(frames, err)),
+                    };
+                    let looping = Loop { pattern: stmt.pattern, items, body: stmt.body, env: Rc::clone(&self.env) };
+                    frames.push(
+                        Frame {
+                            stmts: VecDeque::new(),
+                            env: Rc::clone(&self.env),
+                            defers: Vec::new(),
+                            looping: Some(looping),
+                        }
+                    );
+                }
+                stmt => {
+                    let ret = match Eval::waiting_statement(stmt, task) {
+                        Ok((exp, then)) => match self.start_call(exp) {
+                            Started::Done(value) => self.resume_with(then, value),
+                            Started::Wait(wait) => return Step::Wait(wait, then),
+                        },
+                        Err(stmt) => self.stmt(*stmt),
+                    };
+                    if self.is_abrupt(&ret) {
+                        return Step::Done(self.unwind(frames, ret));
+                    }
+                }
+            }
+        }
+    }
+
+    // Split a statement where a task can wait for a channel into the call and the use of its
+    // result. Other statements, and all statements of generators, are given back.
+    //
+    // A task is suspended only at a statement of its body which is a call, 'let pattern = call;'
+    // or 'target = call;'. A send, a recv or a select elsewhere, such as in 'ch.recv() + 1' or in
+    // a function called by the task, runs on the Rust stack and can't be suspended, so it's an
+    // error if it has to wait, and it completes as usual if it's ready.
+    fn waiting_statement(stmt: Statement, task: bool) -> Result<(Expression, Resumption), Box<Statement>> {
+        if !task {
+            return Err(Box::new(stmt));
+        }
+        let is_call = |exp: &Expression| matches!(exp, Expression::Call(_) | Expression::MethodCall(_));
+        match stmt {
+            Statement::Exp(stmt) if is_call(&stmt.exp) => Ok((stmt.exp, Resumption::Discard)),
+            Statement::Exp(ExpStatement { exp: Expression::Assign(assign) }) if is_call(&assign.rhs_exp) => {
+                Ok((assign.rhs_exp, Resumption::Assign(assign.target)))
+            }
+            Statement::Let(stmt) if is_call(&stmt.rhs_exp) => {
+                Ok((stmt.rhs_exp, Resumption::Let(stmt.pattern, stmt.mutable)))
+            }
+            stmt => Err(Box::new(stmt)),
+        }
+    }
+
+    // Evaluate a call, which may start a channel operation to wait for
+    fn start_call(&mut self, exp: Expression) -> Started {
+        let call = match self.prepare_call(exp) {
+            Some(Ok(call)) => call,
+            Some(Err(obj)) => return Started::Done(obj),
+            None => return Started::Done(Object::Null(Null::new())),
+        };
+        match call.func {
+            Object::Native(op @ (Native::Send | Native::Recv | Native::Select)) if call.keywords.is_empty() => {
+                self.start_op(op, call.args).unwrap_or_else(Started::Done)
+            }
+            func => Started::Done(self.apply(func, call.args, call.keywords)),
+        }
+    }
+
+    // A frame running the statements in a new scope of the current env
+    fn frame(&mut self, stmts: Vec<Statement>) -> Result<Frame, Object> {
+        let curr_env = Rc::clone(&self.env);
+        self.env = Rc::new(RefCell::new(Env::new_with_outer(Rc::clone(&curr_env))));
+        let hoisted = self.hoist(&stmts);
+        let env = std::mem::replace(&mut self.env, curr_env);
+        match hoisted {
+            Some(err) => Err(err),
+            None => Ok(Frame { stmts: stmts.into(), env, defers: Vec::new(), looping: None }),
+        }
+    }
+
+    // Restart the body of 'for' with the next item. False is returned if the frame isn't a loop
+    // or there are no more items.
+    fn next_iteration(&mut self, frame: &mut Frame) -> Result<bool, Object> {
+        let looping = match frame.looping {
+            Some(ref mut looping) => looping,
+            None => return Ok(false),
+        };
+        let item = match self.next_item(&mut looping.items) {
+            Some(item) if self.is_abrupt(&item) => return Err(item),
+            Some(item) => item,
+            None => return Ok(false),
+        };
+
+        self.env = Rc::clone(&looping.env);
+        self.env = self.bind_item(&looping.pattern, item)?;
+        let next = self.frame(looping.body.statements.clone())?;
+        frame.stmts = next.stmts;
+        frame.env = next.env;
+        Ok(true)
+    }
+
+    // Leave all frames because of an error or a return, running their deferred expressions. The
+    // generator finishes, and only an error is given to the caller.
+    pub(super) fn unwind(&mut self, frames: &mut Vec<Frame>, mut ret: Object) -> Option<Object> {
+        while let Some(frame) = frames.pop() {
+            if let Some(err) = self.run_defers(frame.defers) {
+                if !matches!(ret, Object::Err(_)) {
+                    ret = err;
+                }
+            }
+        }
+        match ret {
+            Object::Err(_) => Some(ret),
+            _ => None,
+        }
+    }
+
+    // Items of the object iterated by 'for'. Strings are iterated by characters, and hashes by keys.
+    pub(super) fn iterate(&self, obj: Object) -> Result<Items, Object> {
+        let values = match obj {
+            Object::Array(array) => array.elements,
+            Object::Tuple(tuple) => tuple.elements,
+            Object::Set(set) => set.elements,
+            Object::Str(string) => {
+                string.str.chars().map(|c| Object::Str(StringObj::new(c.to_string()))).collect()
+            }
+            Object::Hash(hash) => hash.pairs.into_iter().map(|(key, _)| key).collect(),
+            Object::Generator(gen) => return Ok(Items::Generator(gen)),
+            obj => return Err(Object::Err(ErrorObj::new(format!("{} is not iterable", obj.inspect())))),
+        };
+        Ok(Items::Values(values.into_iter()))
+    }
+
+    // The next item, which is an error object if a generator failed
+    pub(super) fn next_item(&mut self, items: &mut Items) -> Option<Object> {
+        match items {
+            Items::Values(values) => values.next(),
+            Items::Generator(gen) => self.resume(gen),
+        }
+    }
+
+    // A new scope of the current env with the bindings of the pattern matched with the item
+    pub(super) fn bind_item(&mut self, pattern: &Pattern, item: Object) -> Result<Rc<RefCell<Env>>, Object> {
+        let mut bindings = Vec::new();
+        if let Err(msg) = self.match_pattern(pattern, &item, &mut bindings) {
+            return Err(Object::Err(ErrorObj::new(msg)));
+        }
+
+        let mut env = Env::new_with_outer(Rc::clone(&self.env));
+        for (name, obj) in bindings.into_iter() {
+            env.set(name, obj);
+        }
+        Ok(Rc::new(RefCell::new(env)))
+    }
+}
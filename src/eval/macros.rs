@@ -0,0 +1,483 @@
+use crate::ast::{
+    self, Program, Statement, Expression, LetStatement, Pattern, Identifier, BlkStatement, Argument,
+    FunctionExpression, CallExpression, StringPart, MacroLiteral,
+};
+use super::{
+    Eval,
+    env::Env,
+    object::{Object, ObjectTrait, ErrorObj, Quote},
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+impl Eval {
+    // Define macros by top-level 'let name = macro(...) { ... };', which are removed from the
+    // program, then replace calls to them with the expressions they return. Macros stay defined
+    // for later programs given to this evaluator. The program isn't walked while no macro is
+    // defined.
+    pub fn expand_macros(&mut self, prog: Program) -> Result<Program, ErrorObj> {
+        let mut ret = Program::new();
+        for stmt in prog.statements.into_iter() {
+            match stmt {
+                Statement::Let(LetStatement { pattern: Pattern::Binding(ident), rhs_exp: Expression::Macro(mac), .. }) => {
+                    self.macros.insert(ident.name, mac);
+                }
+                stmt => ret.statements.push(stmt),
+            }
+        }
+        if self.macros.is_empty() {
+            return Ok(ret);
+        }
+
+        let nesting = Nesting::new(self.max_depth);
+        let mut expander = Expander { eval: self, depth: 0, nesting };
+        for stmt in ret.statements.iter_mut() {
+            walk_stmt(&mut expander, stmt)?;
+        }
+        Ok(ret)
+    }
+
+    // 'quote(expression)' gives the expression itself after splicing the values of 'unquote(...)'
+    // in it. Within a macro, identifiers bound by the quoted expression are renamed.
+    pub(super) fn quote(&mut self, args: Vec<Argument>) -> Object {
+        let mut node = match <[Argument; 1]>::try_from(args) {
+            Ok([Argument::Positional(exp)]) => exp,
+            _ => return Object::Err(ErrorObj::new("quote expects one expression".to_string())),
+        };
+
+        match self.splice(&mut node) {
+            Ok(()) => Object::Quote(Quote::new(node)),
+            Err(err) => Object::Err(err),
+        }
+    }
+
+    // Rename the identifiers bound by the quoted expression when a macro is being expanded, then
+    // replace 'unquote(...)' with the values
+    fn splice(&mut self, node: &mut Expression) -> Result<(), ErrorObj> {
+        if self.expanding > 0 {
+            let nesting = Nesting::new(self.max_depth);
+            let mut renamer = Renamer { names: HashMap::new(), collect: true, gensym: &mut self.gensym, nesting };
+            walk_exp(&mut renamer, node)?;
+            renamer.collect = false;
+            walk_exp(&mut renamer, node)?;
+        }
+        let nesting = Nesting::new(self.max_depth);
+        walk_exp(&mut Unquoter { eval: self, nesting }, node)
+    }
+
+    // Evaluate the body of the macro with the arguments quoted, which must give a quote
+    fn expand(&mut self, name: &str, mac: MacroLiteral, args: Vec<Argument>) -> Result<Expression, ErrorObj> {
+        let mut quotes = Vec::new();
+        for arg in args.into_iter() {
+            match arg {
+                Argument::Positional(exp) => quotes.push(Object::Quote(Quote::new(exp))),
+                _ => return Err(ErrorObj::new(format!("Macro {} takes only positional arguments", name))),
+            }
+        }
+        if quotes.len() != mac.params.len() {
+            return Err(
+                ErrorObj::new(
+                    format!("Wrong number of arguments to macro {}: expect {}, got {}", name, mac.params.len(), quotes.len())
+                )
+            );
+        }
+
+        let mut env = Env::new_with_outer(Rc::clone(&self.env));
+        for (param, quote) in mac.params.into_iter().zip(quotes) {
+            env.set(param.name, quote);
+        }
+        let curr_env = std::mem::replace(&mut self.env, Rc::new(RefCell::new(env)));
+        self.expanding += 1;
+        let ret = self.stmt(Statement::Blk(mac.body));
+        self.expanding -= 1;
+        self.env = curr_env;
+
+        let ret = match ret {
+            Object::Ret(ret) => *ret.value,
+            ret => ret,
+        };
+        match ret {
+            Object::Quote(quote) => Ok(quote.node),
+            Object::Err(err) => Err(err),
+            obj => Err(ErrorObj::new(format!("Macro {} must return a quoted expression, got {}", name, obj.inspect()))),
+        }
+    }
+}
+
+// The name of the function if it's called by an identifier, such as 'quote' of 'quote(x)'
+pub fn callee_name(call: &CallExpression) -> Option<&str> {
+    match *call.ident {
+        Expression::Ident(ref ident) => Some(ident.name.as_str()),
+        _ => None,
+    }
+}
+
+fn is_unquote(exp: &Expression) -> bool {
+    matches!(exp, Expression::Call(call) if callee_name(call) == Some("unquote"))
+}
+
+// Convert the value of 'unquote(...)' into the expression spliced into the quote
+fn unquoted(obj: Object) -> Result<Expression, ErrorObj> {
+    match obj {
+        Object::Int(int) => Ok(Expression::Int(ast::Integer::new(int.value))),
+        Object::Bool(bool) => Ok(Expression::Bool(ast::Boolean::new(bool.value))),
+        Object::Str(string) => Ok(Expression::Str(ast::StringLiteral::new(string.str))),
+        Object::Null(_) => Ok(Expression::Null(ast::NullLiteral::new())),
+        Object::Quote(quote) => Ok(quote.node),
+        Object::Array(array) => {
+            let elements = array.elements.into_iter().map(unquoted).collect::<Result<Vec<_>, _>>()?;
+            Ok(Expression::Array(ast::ArrayLiteral::new(elements)))
+        }
+        Object::Tuple(tuple) => {
+            let elements = tuple.elements.into_iter().map(unquoted).collect::<Result<Vec<_>, _>>()?;
+            Ok(Expression::Tuple(ast::TupleLiteral::new(elements)))
+        }
+        Object::Set(set) => {
+            let elements = set.elements.into_iter().map(unquoted).collect::<Result<Vec<_>, _>>()?;
+            Ok(Expression::Set(ast::SetLiteral::new(elements)))
+        }
+        Object::Err(err) => Err(err),
+        obj => Err(ErrorObj::new(format!("Cannot unquote {}", obj.inspect()))),
+    }
+}
+
+// Replaces calls to macros with their expansions, which are expanded again
+struct Expander<'a> {
+    eval: &'a mut Eval,
+    depth: usize,
+    nesting: Nesting,
+}
+
+impl Rewriter for Expander<'_> {
+    fn expression(&mut self, exp: &mut Expression) -> Result<bool, ErrorObj> {
+        let (name, mac, args) = match exp {
+            Expression::Call(call) => match callee_name(call).and_then(|name| self.eval.macros.get(name)) {
+                Some(mac) => (callee_name(call).unwrap_or_default().to_string(), mac.clone(), call.args.clone()),
+                None => return Ok(false),
+            },
+            _ => return Ok(false),
+        };
+        if self.depth >= self.eval.max_depth {
+            return Err(ErrorObj::new(format!("Macro expansion is nested deeper than {}", self.eval.max_depth)));
+        }
+
+        *exp = self.eval.expand(&name, mac, args)?;
+        // The expansion takes the place of the call, so it isn't nested any deeper in the walk
+        self.depth += 1;
+        let ret = walk_children(self, exp);
+        self.depth -= 1;
+        ret.map(|_| true)
+    }
+
+    fn nesting(&mut self) -> &mut Nesting {
+        &mut self.nesting
+    }
+}
+
+// Splices the values of 'unquote(...)' evaluated in the current env
+struct Unquoter<'a> {
+    eval: &'a mut Eval,
+    nesting: Nesting,
+}
+
+impl Rewriter for Unquoter<'_> {
+    fn expression(&mut self, exp: &mut Expression) -> Result<bool, ErrorObj> {
+        let arg = match exp {
+            Expression::Call(call) if callee_name(call) == Some("unquote") => match call.args.as_slice() {
+                [Argument::Positional(arg)] => arg.clone(),
+                _ => return Err(ErrorObj::new("unquote expects one expression".to_string())),
+            },
+            _ => return Ok(false),
+        };
+        *exp = unquoted(self.eval.expr(arg))?;
+        Ok(true)
+    }
+
+    fn nesting(&mut self) -> &mut Nesting {
+        &mut self.nesting
+    }
+}
+
+// Renames identifiers bound in a quoted expression outside of 'unquote(...)', so that they can't
+// capture variables of the code which a macro is expanded into. The bindings are collected first,
+// then they and the references to them are renamed. Fresh names contain '#', which can't be
+// written in code.
+struct Renamer<'a> {
+    names: HashMap<String, String>,
+    collect: bool,
+    gensym: &'a mut usize,
+    nesting: Nesting,
+}
+
+impl Rewriter for Renamer<'_> {
+    fn expression(&mut self, exp: &mut Expression) -> Result<bool, ErrorObj> {
+        if is_unquote(exp) {
+            return Ok(true);
+        }
+        if let (false, Expression::Ident(ident)) = (self.collect, exp) {
+            if let Some(name) = self.names.get(&ident.name) {
+                ident.name = name.clone();
+            }
+        }
+        Ok(false)
+    }
+
+    fn binding(&mut self, ident: &mut Identifier) {
+        if !self.collect {
+            if let Some(name) = self.names.get(&ident.name) {
+                ident.name = name.clone();
+            }
+        } else if !self.names.contains_key(&ident.name) {
+            *self.gensym += 1;
+            self.names.insert(ident.name.clone(), format!("{}#{}", ident.name, self.gensym));
+        }
+    }
+
+    fn nesting(&mut self) -> &mut Nesting {
+        &mut self.nesting
+    }
+}
+
+// Visitor which can replace expressions while walking the tree
+trait Rewriter {
+    // Called for each expression before its children. True is returned if the children are
+    // already handled and must be skipped.
+    fn expression(&mut self, exp: &mut Expression) -> Result<bool, ErrorObj>;
+
+    // Called for each identifier bound by a pattern, a parameter or a declaration
+    fn binding(&mut self, _ident: &mut Identifier) {}
+
+    // The nesting of the tree being walked
+    fn nesting(&mut self) -> &mut Nesting;
+}
+
+// Expressions and blocks entered by a walk, which is bounded like the evaluation of the tree so
+// that a deep tree can't overflow the stack
+struct Nesting {
+    depth: usize,
+    max_depth: usize,
+}
+
+impl Nesting {
+    fn new(max_depth: usize) -> Nesting {
+        Nesting { depth: 0, max_depth }
+    }
+
+    fn enter(&mut self) -> Result<(), ErrorObj> {
+        if self.depth >= self.max_depth {
+            return Err(ErrorObj::new(format!("Expression is nested deeper than {}", self.max_depth)));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+fn walk_stmt<R: Rewriter>(r: &mut R, stmt: &mut Statement) -> Result<(), ErrorObj> {
+    match stmt {
+        Statement::Let(stmt) => {
+            walk_exp(r, &mut stmt.rhs_exp)?;
+            walk_pattern(r, &mut stmt.pattern);
+        }
+        Statement::Const(stmt) => {
+            walk_exp(r, &mut stmt.value)?;
+            r.binding(&mut stmt.name);
+        }
+        Statement::Ret(stmt) => walk_exp(r, &mut stmt.exp)?,
+        Statement::Exp(stmt) => walk_exp(r, &mut stmt.exp)?,
+        Statement::Defer(stmt) => walk_exp(r, &mut stmt.exp)?,
+        Statement::Yield(stmt) => walk_exp(r, &mut stmt.exp)?,
+        Statement::Blk(blk) => walk_blk(r, blk)?,
+        Statement::Func(stmt) => {
+            r.binding(&mut stmt.name);
+            walk_func(r, &mut stmt.func)?;
+        }
+        Statement::Impl(stmt) => {
+            for func in stmt.funcs.iter_mut() {
+                walk_func(r, &mut func.func)?;
+            }
+        }
+        Statement::For(stmt) => {
+            walk_exp(r, &mut stmt.iterable)?;
+            walk_pattern(r, &mut stmt.pattern);
+            walk_blk(r, &mut stmt.body)?;
+        }
+        Statement::Import(stmt) => r.binding(&mut stmt.name),
+        Statement::Export(stmt) => walk_stmt(r, &mut stmt.stmt)?,
+        Statement::Struct(_) | Statement::Enum(_) => {}
+    }
+    Ok(())
+}
+
+fn walk_blk<R: Rewriter>(r: &mut R, blk: &mut BlkStatement) -> Result<(), ErrorObj> {
+    r.nesting().enter()?;
+    let ret = blk.statements.iter_mut().try_for_each(|stmt| walk_stmt(r, stmt));
+    r.nesting().leave();
+    ret
+}
+
+fn walk_func<R: Rewriter>(r: &mut R, func: &mut FunctionExpression) -> Result<(), ErrorObj> {
+    for param in func.params.iter_mut() {
+        walk_pattern(r, &mut param.pattern);
+        if let Some(ref mut default) = param.default {
+            walk_exp(r, default)?;
+        }
+    }
+    if let Some(ref mut rest) = func.rest {
+        r.binding(rest);
+    }
+    walk_blk(r, &mut func.body)
+}
+
+fn walk_args<R: Rewriter>(r: &mut R, args: &mut [Argument]) -> Result<(), ErrorObj> {
+    for arg in args.iter_mut() {
+        match arg {
+            Argument::Positional(exp) | Argument::Keyword(_, exp) | Argument::Spread(exp) => walk_exp(r, exp)?,
+        }
+    }
+    Ok(())
+}
+
+fn walk_pattern<R: Rewriter>(r: &mut R, pattern: &mut Pattern) {
+    match pattern {
+        Pattern::Binding(ident) => r.binding(ident),
+        Pattern::Or(patterns) | Pattern::Tuple(patterns) | Pattern::Variant(_, Some(patterns)) => {
+            for pattern in patterns.iter_mut() {
+                walk_pattern(r, pattern);
+            }
+        }
+        Pattern::Array(patterns, rest) => {
+            for pattern in patterns.iter_mut() {
+                walk_pattern(r, pattern);
+            }
+            if let Some(rest) = rest {
+                walk_pattern(r, rest);
+            }
+        }
+        Pattern::Hash(pairs, rest) => {
+            for (_, pattern) in pairs.iter_mut() {
+                walk_pattern(r, pattern);
+            }
+            if let Some(rest) = rest {
+                walk_pattern(r, rest);
+            }
+        }
+        Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range(..) | Pattern::Variant(_, None) => {}
+    }
+}
+
+fn walk_exp<R: Rewriter>(r: &mut R, exp: &mut Expression) -> Result<(), ErrorObj> {
+    r.nesting().enter()?;
+    let ret = walk_children(r, exp);
+    r.nesting().leave();
+    ret
+}
+
+fn walk_children<R: Rewriter>(r: &mut R, exp: &mut Expression) -> Result<(), ErrorObj> {
+    if r.expression(exp)? {
+        return Ok(());
+    }
+
+    match exp {
+        Expression::Ident(_)
+        | Expression::Int(_)
+        | Expression::Bool(_)
+        | Expression::Str(_)
+        | Expression::Null(_)
+        | Expression::Placeholder(_)
+        | Expression::Path(_) => {}
+        Expression::Interp(interp) => {
+            for part in interp.parts.iter_mut() {
+                if let StringPart::Exp(exp) = part {
+                    walk_exp(r, exp)?;
+                }
+            }
+        }
+        Expression::Array(ast::ArrayLiteral { elements })
+        | Expression::Tuple(ast::TupleLiteral { elements })
+        | Expression::Set(ast::SetLiteral { elements }) => {
+            for elem in elements.iter_mut() {
+                walk_exp(r, elem)?;
+            }
+        }
+        Expression::Hash(hash) => {
+            for (key, value) in hash.pairs.iter_mut() {
+                walk_exp(r, key)?;
+                walk_exp(r, value)?;
+            }
+        }
+        Expression::Struct(literal) => {
+            for (_, value) in literal.fields.iter_mut() {
+                walk_exp(r, value)?;
+            }
+        }
+        Expression::Prefix(prefix) => walk_exp(r, &mut prefix.rhs_exp)?,
+        Expression::Infix(infix) => {
+            walk_exp(r, &mut infix.lhs_exp)?;
+            walk_exp(r, &mut infix.rhs_exp)?;
+        }
+        Expression::Postfix(postfix) => walk_exp(r, &mut postfix.lhs_exp)?,
+        Expression::Assign(assign) => {
+            walk_exp(r, &mut assign.target)?;
+            walk_exp(r, &mut assign.rhs_exp)?;
+        }
+        Expression::If(if_exp) => {
+            walk_exp(r, &mut if_exp.condition)?;
+            walk_blk(r, &mut if_exp.consequence)?;
+            if let Some(ref mut alternative) = if_exp.alternative {
+                walk_blk(r, alternative)?;
+            }
+        }
+        Expression::Blk(blk) => walk_blk(r, blk)?,
+        Expression::Throw(throw) => walk_exp(r, &mut throw.value)?,
+        Expression::Try(try_exp) => {
+            walk_blk(r, &mut try_exp.body)?;
+            if let Some((ref mut ident, ref mut blk)) = try_exp.catch {
+                r.binding(ident);
+                walk_blk(r, blk)?;
+            }
+            if let Some(ref mut finally) = try_exp.finally {
+                walk_blk(r, finally)?;
+            }
+        }
+        Expression::Func(func) => walk_func(r, func)?,
+        Expression::Macro(mac) => {
+            for param in mac.params.iter_mut() {
+                r.binding(param);
+            }
+            walk_blk(r, &mut mac.body)?;
+        }
+        Expression::Call(call) => {
+            walk_exp(r, &mut call.ident)?;
+            walk_args(r, &mut call.args)?;
+        }
+        Expression::MethodCall(call) => {
+            walk_exp(r, &mut call.receiver)?;
+            walk_args(r, &mut call.args)?;
+        }
+        Expression::Field(field) => walk_exp(r, &mut field.lhs_exp)?,
+        Expression::Index(index) => {
+            walk_exp(r, &mut index.lhs_exp)?;
+            walk_exp(r, &mut index.index)?;
+        }
+        Expression::Slice(slice) => {
+            walk_exp(r, &mut slice.lhs_exp)?;
+            for bound in [&mut slice.start, &mut slice.end, &mut slice.step].into_iter().flatten() {
+                walk_exp(r, bound)?;
+            }
+        }
+        Expression::Match(match_exp) => {
+            walk_exp(r, &mut match_exp.subject)?;
+            for arm in match_exp.arms.iter_mut() {
+                walk_pattern(r, &mut arm.pattern);
+                if let Some(ref mut guard) = arm.guard {
+                    walk_exp(r, guard)?;
+                }
+                walk_blk(r, &mut arm.body)?;
+            }
+        }
+    }
+    Ok(())
+}
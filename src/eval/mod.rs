@@ -1,134 +1,1184 @@
+mod test;
 pub mod env;
+pub mod generator;
+pub mod macros;
+pub mod module;
 pub mod object;
+pub mod task;
 
 use crate::{
-    ast::{Program, Statement, Expression, IfExpression, CallExpression},
-    token::TokenKind
+    ast::{
+        Program, Statement, Expression, IfExpression, IndexExpression, StringPart, MatchExpression,
+        Pattern, Node, Argument, MethodCallExpression, FieldExpression, StructLiteral,
+        PathExpression, TryExpression, Identifier, EnumVariant, ForStatement, MacroLiteral,
+        SliceExpression, LetStatement, ConstStatement, RetStatement, ImplStatement, StructStatement,
+        EnumStatement, DeferStatement, BlkStatement, InterpolatedString, ArrayLiteral, TupleLiteral,
+        SetLiteral, HashLiteral, PrefixExpression, InfixExpression, PostfixExpression,
+        AssignExpression, ThrowExpression, FunctionExpression
+    },
+    token::{Token, TokenKind}
 };
 use self::{
     env::Env,
-    object::{Object, Integer, Null, Boolean, ReturnValue, ErrorObj, ObjectTrait, Function, StringObj, Buildin},
+    object::{
+        Object, Integer, Null, Boolean, ReturnValue, ErrorObj, ObjectTrait, Function, StringObj,
+        Buildin, ArrayObj, TupleObj, SetObj, HashObj, HashKey, Composition, StructDef, StructObj,
+        EnumDef, VariantObj, VariantCtor, Native
+    },
+    macros::callee_name,
+    module::{Loading, Module},
+    task::Task,
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::{HashMap, VecDeque}, path::PathBuf, rc::Rc};
+
+// Evaluation is recursive, so both nested expressions and function calls consume Rust stack.
+// The depth counts the expressions, blocks and patterns being evaluated, including the ones
+// waiting in callers, and the calls count the functions running. The interpreter should run on
+// a thread with STACK_SIZE bytes of stack, which holds either limit with a margin. Debug builds
+// are optimized in Cargo.toml as well, since unoptimized frames are a few times larger.
+pub const DEFAULT_MAX_DEPTH: usize = 16384;
+pub const DEFAULT_MAX_CALLS: usize = 3072;
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+// Types other than structs and enums which 'impl' can add methods to
+const BUILTIN_TYPES: [&str; 12] = [
+    "Int", "Bool", "String", "Null", "Array", "Tuple", "Set", "Hash", "Function", "Error", "Generator", "Channel"
+];
 
 pub struct Eval {
     env: Rc<RefCell<Env>>,
+    depth: usize,
+    max_depth: usize,
+    calls: usize,
+    max_calls: usize,
+    // Methods for each type name, which take precedence over functions in env on method calls
+    methods: HashMap<String, HashMap<String, Object>>,
+    // Expressions deferred in each running block, with the env to evaluate them in
+    defers: Vec<Vec<(Expression, Rc<RefCell<Env>>)>>,
+    // Spawned tasks in the order to run, and the one running now
+    tasks: VecDeque<Task>,
+    current_task: Option<usize>,
+    task_ids: usize,
+    channel_ids: usize,
+    // Macros defined so far, the number of them being expanded, and the last fresh name given
+    macros: HashMap<String, MacroLiteral>,
+    expanding: usize,
+    gensym: usize,
+    // Modules evaluated so far by their files, and the ones being evaluated by nested imports
+    modules: HashMap<PathBuf, Module>,
+    loading: Vec<Loading>,
+}
+
+// A call whose function and arguments are evaluated
+struct PreparedCall {
+    func: Object,
+    args: Vec<Object>,
+    keywords: Vec<(String, Object)>,
+}
+
+// A field or an evaluated index on the way from a variable to the target of an assignment
+enum Step {
+    Field(String),
+    Index(Object),
 }
 
 impl Eval {
     pub fn new(env: Rc<RefCell<Env>>) -> Eval {
-        env.borrow_mut().set(
-            "puts".to_string(),
-            Object::Buildin(Buildin::new(Buildin::print))
-        );
-        env.borrow_mut().set(
-            "exit".to_string(),
-            Object::Buildin(Buildin::new(Buildin::exit))
-        );
-        Eval { env }
+        Eval::new_with_limit(env, DEFAULT_MAX_DEPTH)
     }
 
-    pub fn eval(&mut self, prog: Program) -> Object {
-        let mut ret: Object = Object::Null(Null::new());
-        for stmt in prog.statements.into_iter() {
-            ret = self.stmt(stmt);
-            match ret {
-                Object::Ret(ret_val) => return *ret_val.value,
-                Object::Err(_)       => return ret,
-                _ => (),
-            }
+    pub fn new_with_limit(env: Rc<RefCell<Env>>, max_depth: usize) -> Eval {
+        Eval::define_globals(&env);
+        Eval {
+            env,
+            depth: 0,
+            max_depth,
+            calls: 0,
+            max_calls: DEFAULT_MAX_CALLS,
+            methods: Eval::builtin_methods(),
+            defers: Vec::new(),
+            tasks: VecDeque::new(),
+            current_task: None,
+            task_ids: 0,
+            channel_ids: 0,
+            macros: HashMap::new(),
+            expanding: 0,
+            gensym: 0,
+            modules: HashMap::new(),
+            loading: Vec::new(),
+        }
+    }
+
+    // Methods of builtin types, which are found before variables of the same names
+    fn builtin_methods() -> HashMap<String, HashMap<String, Object>> {
+        let natives = [
+            ("Generator", "next", Native::Next),
+            ("Channel", "send", Native::Send),
+            ("Channel", "recv", Native::Recv),
+        ];
+        let mut ret: HashMap<String, HashMap<String, Object>> = HashMap::new();
+        for (ty, name, native) in natives.into_iter() {
+            ret.entry(ty.to_string()).or_default().insert(name.to_string(), Object::Native(native));
         }
         ret
     }
+
+    // Builtin functions and types, which every module starts with. They are immutable like the
+    // declarations of a program.
+    fn define_globals(env: &Rc<RefCell<Env>>) {
+        let mut globals = vec![
+            ("puts", Object::Buildin(Buildin::new_with_display(Buildin::print))),
+            ("exit", Object::Buildin(Buildin::new(Buildin::exit))),
+            ("len", Object::Buildin(Buildin::new(Buildin::len))),
+            ("push", Object::Buildin(Buildin::new(Buildin::push))),
+            ("read_file", Object::Buildin(Buildin::new(Buildin::read_file))),
+            ("set", Object::Buildin(Buildin::new(Buildin::set))),
+            ("union", Object::Buildin(Buildin::new(Buildin::union))),
+            ("intersection", Object::Buildin(Buildin::new(Buildin::intersection))),
+            ("difference", Object::Buildin(Buildin::new(Buildin::difference))),
+            ("is_subset", Object::Buildin(Buildin::new(Buildin::is_subset))),
+            ("spawn", Object::Native(Native::Spawn)),
+            ("channel", Object::Native(Native::Channel)),
+            ("select", Object::Native(Native::Select)),
+        ];
+
+        // 'enum Result { Ok(value), Err(error) }' with 'Ok' and 'Err' as constructors
+        let variants = [("Ok", "value"), ("Err", "error")];
+        let result = variants.iter().map(|(name, field)| {
+            let field = Identifier::new(field.to_string());
+            EnumVariant::new(Identifier::new(name.to_string()), Some(vec![field]))
+        });
+        globals.push(("Result", Object::EnumDef(EnumDef::new("Result".to_string(), result.collect(), true))));
+        for (name, field) in variants.iter() {
+            let ctor = VariantCtor::new("Result".to_string(), name.to_string(), vec![field.to_string()], true);
+            globals.push((name, Object::Ctor(ctor)));
+        }
+
+        let mut env = env.borrow_mut();
+        for (name, obj) in globals.into_iter() {
+            // The env is a new one, so there is no immutable binding to conflict with
+            let _ = env.define(name.to_string(), obj, false);
+        }
+    }
+
+    // Run the program, then the spawned tasks until all of them finish
+    pub fn eval(&mut self, prog: Program) -> Object {
+        let ret = match self.blk(prog.statements) {
+            Object::Ret(ret_val) => *ret_val.value,
+            ret => ret,
+        };
+        // Tasks spawned by a failed program are dropped, so that a REPL doesn't run them later
+        if matches!(ret, Object::Err(_)) {
+            self.tasks.clear();
+            return ret;
+        }
+        match self.run_tasks() {
+            Object::Err(err) => Object::Err(err),
+            _ => ret,
+        }
+    }
 }
 
 impl Eval {
     fn stmt(&mut self, stmt: Statement) -> Object {
         match stmt {
             Statement::Exp(stmt) => self.expr(stmt.exp),
-            Statement::Let(stmt) => {
-                let name    = stmt.ident.name.clone();
-                let rhs_exp = self.expr(stmt.rhs_exp);
-                self.env.borrow_mut().set(name, rhs_exp.clone());
-                rhs_exp
-            }
-            Statement::Ret(stmt) => Object::Ret(ReturnValue::new(self.expr(stmt.exp))),
-            Statement::Blk(stmt) => {
-                let mut ret: Object = Object::Null(Null::new());
-                for stmt in stmt.statements.into_iter() {
-                    ret = self.stmt(stmt);
-                    match ret {
-                        Object::Ret(_) => return ret,
-                        Object::Err(_) => return ret,
-                        _ => (),
+            Statement::Let(stmt) => self.let_stmt(stmt),
+            Statement::Const(stmt) => self.const_stmt(stmt),
+            Statement::Ret(stmt) => self.ret_stmt(stmt),
+            Statement::Func(_) => Object::Null(Null::new()),
+            Statement::Struct(stmt) => self.struct_stmt(stmt),
+            Statement::Enum(stmt) => self.enum_stmt(stmt),
+            Statement::Impl(stmt) => self.impl_stmt(stmt),
+            Statement::Blk(stmt) => self.blk_stmt(stmt),
+            Statement::For(stmt) => self.for_stmt(stmt),
+            Statement::Import(stmt) => self.import(stmt),
+            Statement::Export(stmt) => self.stmt(*stmt.stmt),
+            Statement::Yield(_) => {
+                Object::Err(
+                    ErrorObj::new("yield can only be used in blocks, if and for statements of a generator".to_string())
+                )
+            }
+            Statement::Defer(stmt) => self.defer_stmt(stmt),
+        }
+    }
+
+    fn ret_stmt(&mut self, stmt: RetStatement) -> Object {
+        let value = self.expr(stmt.exp);
+        if self.is_abrupt(&value) {
+            return value;
+        }
+        Object::Ret(ReturnValue::new(value))
+    }
+
+    fn let_stmt(&mut self, stmt: LetStatement) -> Object {
+        let rhs_exp = self.expr(stmt.rhs_exp);
+        if self.is_abrupt(&rhs_exp) {
+            return rhs_exp;
+        }
+        self.define_pattern(&stmt.pattern, rhs_exp, stmt.mutable)
+    }
+
+    fn const_stmt(&mut self, stmt: ConstStatement) -> Object {
+        let value = self.expr(stmt.value);
+        if self.is_abrupt(&value) {
+            return value;
+        }
+        if let Err(msg) = self.env.borrow_mut().define(stmt.name.name, value.clone(), false) {
+            return Object::Err(ErrorObj::new(msg));
+        }
+        value
+    }
+
+    fn struct_stmt(&mut self, stmt: StructStatement) -> Object {
+        let fields = stmt.fields.into_iter().map(|field| field.name).collect();
+        let def = StructDef::new(stmt.name.name.clone(), fields);
+        self.declare(stmt.name.name, Object::StructDef(def))
+    }
+
+    fn enum_stmt(&mut self, stmt: EnumStatement) -> Object {
+        let def = EnumDef::new(stmt.name.name.clone(), stmt.variants, false);
+        self.declare(stmt.name.name, Object::EnumDef(def))
+    }
+
+    // Define the name of a declaration, which is immutable like 'const'
+    fn declare(&mut self, name: String, obj: Object) -> Object {
+        match self.env.borrow_mut().define(name, obj, false) {
+            Ok(()) => Object::Null(Null::new()),
+            Err(msg) => Object::Err(ErrorObj::new(msg)),
+        }
+    }
+
+    fn defer_stmt(&mut self, stmt: DeferStatement) -> Object {
+        match self.defers.last_mut() {
+            Some(defers) => defers.push((stmt.exp, Rc::clone(&self.env))),
+            None => return Object::Err(ErrorObj::new("defer used outside of block".to_string())),
+        }
+        Object::Null(Null::new())
+    }
+
+    fn impl_stmt(&mut self, stmt: ImplStatement) -> Object {
+        let ty = stmt.ty.name;
+        let defined = matches!(self.env.borrow().get(&ty), Some(Object::StructDef(_) | Object::EnumDef(_)));
+        if !defined && !BUILTIN_TYPES.contains(&ty.as_str()) {
+            return Object::Err(ErrorObj::new(format!("Unknown type for impl: {}", ty)));
+        }
+
+        for stmt in stmt.funcs.into_iter() {
+            let func = Function::new(
+                Some(format!("{}::{}", ty, stmt.name.name)),
+                stmt.func.params,
+                stmt.func.rest,
+                stmt.func.body,
+                Rc::clone(&self.env),
+                stmt.func.generator,
+            );
+            self.methods.entry(ty.clone()).or_default().insert(stmt.name.name, Object::Func(func));
+        }
+        Object::Null(Null::new())
+    }
+
+    // Run the block in a new scope of the current env
+    // Blocks nest through statements rather than expressions, so they are counted in the depth
+    // as well
+    fn blk_stmt(&mut self, stmt: BlkStatement) -> Object {
+        if self.depth >= self.max_depth {
+            return Object::Err(ErrorObj::new(self.too_deep()));
+        }
+
+        self.depth += 1;
+        let curr_env = Rc::clone(&self.env);
+        self.env = Rc::new(RefCell::new(Env::new_with_outer(Rc::clone(&curr_env))));
+        let ret = self.blk(stmt.statements);
+        self.env = curr_env;
+        self.depth -= 1;
+        ret
+    }
+
+    // Define the bindings of 'let', and give the value or an error
+    fn define_pattern(&mut self, pattern: &Pattern, value: Object, mutable: bool) -> Object {
+        let mut bindings = Vec::new();
+        if let Err(msg) = self.match_pattern(pattern, &value, &mut bindings) {
+            return Object::Err(ErrorObj::new(msg));
+        }
+        for (name, obj) in bindings.into_iter() {
+            if let Err(msg) = self.env.borrow_mut().define(name, obj, mutable) {
+                return Object::Err(ErrorObj::new(msg));
+            }
+        }
+        value
+    }
+
+    // Run the statements in the current env, then the expressions deferred by them in reverse order. Deferred
+    // expressions run even if the block exits by return or error. An error from them replaces
+    // the result unless the block already failed.
+    fn blk(&mut self, stmts: Vec<Statement>) -> Object {
+        let mut ret: Object = Object::Null(Null::new());
+        if let Some(err) = self.hoist(&stmts) {
+            return err;
+        }
+        self.defers.push(Vec::new());
+        for stmt in stmts.into_iter() {
+            ret = self.stmt(stmt);
+            if matches!(ret, Object::Ret(_) | Object::Err(_)) {
+                break;
+            }
+        }
+
+        let defers = self.defers.pop().unwrap_or_default();
+        if let Some(err) = self.run_defers(defers) {
+            if !matches!(ret, Object::Err(_)) {
+                ret = err;
+            }
+        }
+        ret
+    }
+
+    // Run deferred expressions in reverse order, and give the first error among them
+    fn run_defers(&mut self, defers: Vec<(Expression, Rc<RefCell<Env>>)>) -> Option<Object> {
+        let mut ret = None;
+        for (exp, env) in defers.into_iter().rev() {
+            let curr_env = std::mem::replace(&mut self.env, env);
+            let obj = self.expr(exp);
+            self.env = curr_env;
+            if matches!(obj, Object::Err(_)) && ret.is_none() {
+                ret = Some(obj);
+            }
+        }
+        ret
+    }
+
+    // Define functions declared by 'fn name(...)' before running the block, so that they can
+    // call each other regardless of the order.
+    fn hoist(&mut self, stmts: &[Statement]) -> Option<Object> {
+        for stmt in stmts.iter() {
+            let stmt = match stmt {
+                Statement::Export(stmt) => &*stmt.stmt,
+                stmt => stmt,
+            };
+            if let Statement::Func(stmt) = stmt {
+                let func = Function::new(
+                    Some(stmt.name.name.clone()),
+                    stmt.func.params.clone(),
+                    stmt.func.rest.clone(),
+                    stmt.func.body.clone(),
+                    Rc::clone(&self.env),
+                    stmt.func.generator,
+                );
+                let ret = self.declare(stmt.name.name.clone(), Object::Func(func));
+                if self.is_abrupt(&ret) {
+                    return Some(ret);
+                }
+            }
+        }
+        None
+    }
+
+    fn expr(&mut self, expr: Expression) -> Object {
+        if self.depth >= self.max_depth {
+            return Object::Err(ErrorObj::new(self.too_deep()));
+        }
+
+        self.depth += 1;
+        let ret = self.nested_expr(expr);
+        self.depth -= 1;
+        ret
+    }
+
+    fn nested_expr(&mut self, expr: Expression) -> Object {
+        match expr {
+            Expression::Ident(ident)   => self.variable(ident),
+            Expression::Int(int)       => Object::Int(Integer::new(int.value)),
+            Expression::Bool(bool)     => Object::Bool(Boolean::new(bool.value)),
+            Expression::Str(string)    => Object::Str(StringObj::new(string.str.clone())),
+            Expression::Interp(interp) => self.interp(interp),
+            Expression::Null(_)        => Object::Null(Null::new()),
+            Expression::Placeholder(_) => {
+                Object::Err(ErrorObj::new("Placeholder _ can only be used as an argument after |>".to_string()))
+            }
+            Expression::Array(array)   => self.array_literal(array),
+            Expression::Tuple(tuple)   => self.tuple_literal(tuple),
+            Expression::Set(set)       => self.set_literal(set),
+            Expression::Hash(hash)     => self.hash_literal(hash),
+
+            Expression::Struct(literal) => self.struct_literal(literal),
+            Expression::Path(path) => self.path(path),
+
+            Expression::Prefix(prefix) => self.prefix_expr(*prefix),
+            Expression::Infix(infix) => self.infix_expr(*infix),
+            Expression::Assign(assign) => self.assign_expr(*assign),
+            Expression::Postfix(postfix) => self.postfix_expr(*postfix),
+
+            Expression::If(if_expr) => {
+                self.if_expr(if_expr)
+            }
+            Expression::Blk(blk) => self.blk_stmt(blk),
+            Expression::Throw(throw) => self.throw_expr(throw),
+            Expression::Try(try_expr) => {
+                self.try_expr(try_expr)
+            }
+            Expression::Match(match_expr) => {
+                self.match_expr(match_expr)
+            }
+            Expression::Func(func) => self.func_literal(func),
+            Expression::Macro(_) => {
+                Object::Err(ErrorObj::new("macro can only be defined by a top-level let".to_string()))
+            }
+            Expression::Call(call) if callee_name(&call) == Some("quote") => {
+                self.quote(call.args)
+            }
+            Expression::Call(call) if callee_name(&call) == Some("unquote") => {
+                Object::Err(ErrorObj::new("unquote used outside of quote".to_string()))
+            }
+            expr @ (
+                Expression::Call(_)
+                | Expression::MethodCall(_)
+                | Expression::Index(_)
+                | Expression::Slice(_)
+                | Expression::Field(_)
+            ) => self.chain(expr).unwrap_or(Object::Null(Null::new())),
+        }
+    }
+
+    fn array_literal(&mut self, array: ArrayLiteral) -> Object {
+        match self.elements(array.elements) {
+            Ok(elements) => Object::Array(ArrayObj::new(elements)),
+            Err(err) => err,
+        }
+    }
+
+    fn tuple_literal(&mut self, tuple: TupleLiteral) -> Object {
+        match self.elements(tuple.elements) {
+            Ok(elements) => Object::Tuple(TupleObj::new(elements)),
+            Err(err) => err,
+        }
+    }
+
+    fn assign_expr(&mut self, assign: AssignExpression) -> Object {
+        let value = self.expr(assign.rhs_exp);
+        if self.is_abrupt(&value) {
+            return value;
+        }
+        self.assign(assign.target, value)
+    }
+
+    fn func_literal(&self, func: FunctionExpression) -> Object {
+        let params = func.params;
+        let rest   = func.rest;
+        let body   = func.body;
+        Object::Func(Function::new(None, params, rest, body, Rc::clone(&self.env), func.generator))
+    }
+
+    fn variable(&self, ident: Identifier) -> Object {
+        match self.env.borrow().get(&ident.name) {
+            Some(obj) => obj,
+            _ => Object::Err(ErrorObj::new(format!("Identifier not found: {}", ident.name)))
+        }
+    }
+
+    fn interp(&mut self, interp: InterpolatedString) -> Object {
+        let mut ret = String::new();
+        for part in interp.parts.into_iter() {
+            match part {
+                StringPart::Text(text) => ret.push_str(&text),
+                StringPart::Exp(exp) => {
+                    let obj = self.expr(exp);
+                    if self.is_abrupt(&obj) {
+                        return obj;
+                    }
+                    let string = self.display(obj);
+                    if self.is_abrupt(&string) {
+                        return string;
                     }
+                    ret.push_str(&string.inspect());
+                }
+            }
+        }
+        Object::Str(StringObj::new(ret))
+    }
+
+    fn set_literal(&mut self, set: SetLiteral) -> Object {
+        let elements = match self.elements(set.elements) {
+            Ok(elements) => elements,
+            Err(err) => return err,
+        };
+        match SetObj::from_elements(elements) {
+            Ok(set) => Object::Set(set),
+            Err(elem) => Object::Err(ErrorObj::new(format!("Unusable as set element: {}", elem.inspect()))),
+        }
+    }
+
+    fn hash_literal(&mut self, hash: HashLiteral) -> Object {
+        let mut ret = HashObj::new();
+        for (key, value) in hash.pairs.into_iter() {
+            let key = self.expr(key);
+            if self.is_abrupt(&key) {
+                return key;
+            }
+            let value = self.expr(value);
+            if self.is_abrupt(&value) {
+                return value;
+            }
+            match HashKey::from_object(&key) {
+                Some(hash_key) => ret.set(hash_key, key, value),
+                None => {
+                    return Object::Err(
+                        ErrorObj::new(format!("Unusable as hash key: {}", key.inspect()))
+                    );
+                }
+            }
+        }
+        Object::Hash(ret)
+    }
+
+    fn prefix_expr(&mut self, prefix: PrefixExpression) -> Object {
+        let right = self.expr(prefix.rhs_exp);
+        if !self.is_abrupt(&right) {
+            self.prefix(prefix.operator, right)
+        } else {
+            right
+        }
+    }
+
+    fn infix_expr(&mut self, infix: InfixExpression) -> Object {
+        match infix.operator {
+            TokenKind::Coalesce => {
+                let left = self.expr(infix.lhs_exp);
+                return match left {
+                    Object::Null(_) => self.expr(infix.rhs_exp),
+                    _ => left,
+                };
+            }
+            TokenKind::Pipeline => {
+                let left = self.expr(infix.lhs_exp);
+                if self.is_abrupt(&left) {
+                    return left;
+                }
+                return self.pipeline(left, infix.rhs_exp);
+            }
+            _ => {}
+        }
+
+        let right = self.expr(infix.rhs_exp);
+        if self.is_abrupt(&right) {
+            return right;
+        }
+
+        let left  = self.expr(infix.lhs_exp);
+        if self.is_abrupt(&left) {
+            return left;
+        }
+
+        let hook = match infix.operator {
+            TokenKind::Plus                  => Some("op_add"),
+            TokenKind::Minus                 => Some("op_sub"),
+            TokenKind::Asterisk              => Some("op_mul"),
+            TokenKind::Eq | TokenKind::NotEq => Some("op_eq"),
+            TokenKind::LT | TokenKind::GT
+            | TokenKind::LtEq | TokenKind::GtEq => Some("op_lt"),
+            _ => None,
+        };
+        if let Some(name) = hook {
+            if let Some(func) = self.hook(&left, name) {
+                return self.infix_hook(infix.operator, func, left, right);
+            }
+            if self.hook(&right, name).is_some() {
+                let op = Token::new(infix.operator, "");
+                return Object::Err(
+                    ErrorObj::new(format!(
+                        "{}::{} is only used for the left operand: {} {} {}",
+                        right.type_name(), name, left.inspect(), op.literal(), right.inspect()
+                    ))
+                );
+            }
+        }
+        self.infix(infix.operator, left, right)
+    }
+
+    fn postfix_expr(&mut self, postfix: PostfixExpression) -> Object {
+        let right = self.expr(postfix.lhs_exp);
+        if !self.is_abrupt(&right) {
+            self.postfix(postfix.operator, right)
+        } else {
+            right
+        }
+    }
+
+    fn throw_expr(&mut self, throw: ThrowExpression) -> Object {
+        let value = self.expr(*throw.value);
+        match value {
+            Object::Err(_) => value,
+            Object::Exception(err) => Object::Err(err),
+            Object::Str(ref string) => {
+                Object::Err(ErrorObj::new_thrown("Error".to_string(), string.str.clone(), value))
+            }
+            value => match self.display(value.clone()) {
+                Object::Str(msg) => Object::Err(ErrorObj::new_thrown(value.type_name(), msg.str, value)),
+                err => err,
+            }
+        }
+    }
+
+    // 'Enum::Variant' is the value itself for unit variants, or its constructor otherwise. Other
+    // paths refer to associated functions defined by 'impl'.
+    fn path(&self, path: PathExpression) -> Object {
+        let PathExpression { ty, name } = path;
+        let def = self.env.borrow().get(&ty.name);
+        if let Some(Object::EnumDef(ref def)) = def {
+            if let Some(variant) = def.variants.iter().find(|variant| variant.name.name == name.name) {
+                return match variant.fields {
+                    Some(ref fields) => {
+                        let fields = fields.iter().map(|field| field.name.clone()).collect();
+                        Object::Ctor(VariantCtor::new(def.name.clone(), name.name, fields, def.builtin))
+                    }
+                    None => Object::Variant(VariantObj::new(def.name.clone(), name.name, None, def.builtin)),
+                };
+            }
+        }
+        if let Some(func) = self.methods.get(&ty.name).and_then(|methods| methods.get(&name.name)) {
+            return func.clone();
+        }
+
+        let msg = match def {
+            Some(Object::EnumDef(def)) => {
+                format!("Enum {} has no variant or associated function {}", def.name, name.name)
+            }
+            Some(Object::StructDef(def)) => format!("Struct {} has no associated function {}", def.name, name.name),
+            Some(obj) => format!("{} is not a type: {}", ty.name, obj.inspect()),
+            None if BUILTIN_TYPES.contains(&ty.name.as_str()) => {
+                format!("{} has no associated function {}", ty.name, name.name)
+            }
+            None => format!("Identifier not found: {}", ty.name),
+        };
+        Object::Err(ErrorObj::new(msg))
+    }
+
+    fn struct_literal(&mut self, literal: StructLiteral) -> Object {
+        let name = literal.name.name;
+        let def = match self.env.borrow().get(&name) {
+            Some(Object::StructDef(def)) => def,
+            Some(obj) => return Object::Err(ErrorObj::new(format!("{} is not a struct: {}", name, obj.inspect()))),
+            None => return Object::Err(ErrorObj::new(format!("Identifier not found: {}", name))),
+        };
+
+        let mut given: Vec<(String, Object)> = Vec::new();
+        for (field, exp) in literal.fields.into_iter() {
+            if !def.fields.contains(&field.name) {
+                return Object::Err(ErrorObj::new(format!("Struct {} has no field {}", name, field.name)));
+            }
+            if given.iter().any(|(given, _)| *given == field.name) {
+                return Object::Err(ErrorObj::new(format!("Field {} of struct {} is given twice", field.name, name)));
+            }
+            let value = self.expr(exp);
+            if self.is_abrupt(&value) {
+                return value;
+            }
+            given.push((field.name, value));
+        }
+
+        let mut fields = Vec::new();
+        for field in def.fields.into_iter() {
+            match given.iter().position(|(given, _)| *given == field) {
+                Some(i) => fields.push(given.swap_remove(i)),
+                None => return Object::Err(ErrorObj::new(format!("Missing field {} of struct {}", field, name))),
+            }
+        }
+        Object::Struct(StructObj::new(name, fields))
+    }
+
+    // Evaluate the elements of an array or a tuple in order
+    fn elements(&mut self, exps: Vec<Expression>) -> Result<Vec<Object>, Object> {
+        let mut elements = Vec::new();
+        for elem in exps.into_iter() {
+            let elem = self.expr(elem);
+            if self.is_abrupt(&elem) {
+                return Err(elem);
+            }
+            elements.push(elem);
+        }
+        Ok(elements)
+    }
+
+    // Store the value to the target, then write the updated container back to its own place so
+    // that 'p.x = 1' or 'a[0].y = 2' updates the variable holding it.
+    fn assign(&mut self, target: Expression, value: Object) -> Object {
+        match target {
+            // 'a, b = b, a' assigns each element after evaluating all of them
+            Expression::Tuple(tuple) => {
+                let elements = match value {
+                    Object::Tuple(ref values) if values.elements.len() == tuple.elements.len() => {
+                        values.elements.clone()
+                    }
+                    _ => {
+                        return Object::Err(
+                            ErrorObj::new(
+                                format!(
+                                    "Expected a tuple of {} values to assign to {}, got {}",
+                                    tuple.elements.len(), tuple.string(), value.inspect()
+                                )
+                            )
+                        );
+                    }
+                };
+                for (target, elem) in tuple.elements.into_iter().zip(elements) {
+                    let ret = self.assign(target, elem);
+                    if self.is_abrupt(&ret) {
+                        return ret;
+                    }
+                }
+                value
+            }
+            target => {
+                let mut steps = Vec::new();
+                let ident = match self.place(target, &mut steps) {
+                    Ok(ident) => ident,
+                    Err(obj) => return obj,
+                };
+                let updated = if steps.is_empty() {
+                    value.clone()
+                } else {
+                    let container = match self.env.borrow().get(&ident.name) {
+                        Some(obj) => obj,
+                        None => return Object::Err(ErrorObj::new(format!("Identifier not found: {}", ident.name))),
+                    };
+                    match self.store(container, &steps, value.clone()) {
+                        Ok(obj) => obj,
+                        Err(obj) => return obj,
+                    }
+                };
+                match self.env.borrow_mut().assign(&ident.name, updated) {
+                    Ok(()) => value,
+                    Err(msg) => Object::Err(ErrorObj::new(msg)),
+                }
+            }
+        }
+    }
+
+    // The variable holding the target, with the fields and indexes leading from it to the target.
+    // Each index is evaluated once, from the variable outwards.
+    fn place(&mut self, target: Expression, steps: &mut Vec<Step>) -> Result<Identifier, Object> {
+        match target {
+            Expression::Ident(ident) => Ok(ident),
+            Expression::Field(field) if !field.optional => {
+                let ident = self.place(*field.lhs_exp, steps)?;
+                steps.push(Step::Field(field.name.name));
+                Ok(ident)
+            }
+            Expression::Index(index) if !index.optional => {
+                let ident = self.place(*index.lhs_exp, steps)?;
+                let index = self.expr(*index.index);
+                if self.is_abrupt(&index) {
+                    return Err(index);
+                }
+                steps.push(Step::Index(index));
+                Ok(ident)
+            }
+            target => Err(Object::Err(ErrorObj::new(format!("Can't assign to {}", target.string())))),
+        }
+    }
+
+    // Store the value at the end of the steps in the container, and give the updated container
+    fn store(&mut self, mut container: Object, steps: &[Step], value: Object) -> Result<Object, Object> {
+        let Some((step, rest)) = steps.split_first() else {
+            return Ok(value);
+        };
+        match (&mut container, step) {
+            (Object::Struct(obj), Step::Field(name)) => match obj.get_mut(name) {
+                Some(field) => *field = self.store(field.clone(), rest, value)?,
+                None => {
+                    return Err(Object::Err(ErrorObj::new(format!("Struct {} has no field {}", obj.name, name))));
+                }
+            }
+            (Object::Hash(hash), Step::Field(name)) => {
+                let key = HashKey::Str(name.clone());
+                let field = hash.get(&key).cloned().unwrap_or(Object::Null(Null::new()));
+                let field = self.store(field, rest, value)?;
+                hash.set(key, Object::Str(StringObj::new(name.clone())), field);
+            }
+            (_, Step::Field(name)) => {
+                return Err(
+                    Object::Err(
+                        ErrorObj::new(format!("Field access not supported: {}.{}", container.inspect(), name))
+                    )
+                );
+            }
+            (Object::Array(array), Step::Index(Object::Int(int))) => {
+                let len = array.elements.len();
+                match Eval::position(int.value, len).and_then(|i| array.elements.get_mut(i)) {
+                    Some(elem) => *elem = self.store(elem.clone(), rest, value)?,
+                    None => {
+                        return Err(
+                            Object::Err(
+                                ErrorObj::new(format!("Index out of range: {} for length {}", int.value, len))
+                            )
+                        );
+                    }
+                }
+            }
+            (Object::Hash(hash), Step::Index(key)) => match HashKey::from_object(key) {
+                Some(hash_key) => {
+                    let elem = hash.get(&hash_key).cloned().unwrap_or(Object::Null(Null::new()));
+                    let elem = self.store(elem, rest, value)?;
+                    hash.set(hash_key, key.clone(), elem);
+                }
+                None => {
+                    return Err(Object::Err(ErrorObj::new(format!("Unusable as hash key: {}", key.inspect()))));
+                }
+            }
+            (_, Step::Index(index)) => {
+                return Err(
+                    Object::Err(
+                        ErrorObj::new(
+                            format!("Index operator not supported: {}[{}]", container.inspect(), index.inspect())
+                        )
+                    )
+                );
+            }
+        }
+        Ok(container)
+    }
+
+    // Evaluate a chain of calls and indexes. None is returned if the chain is short-circuited by
+    // '?.' applied to null, in which case the whole chain evaluates to null. Each link recurses
+    // on its left hand side, so it's counted in the depth like an expression.
+    fn chain(&mut self, expr: Expression) -> Option<Object> {
+        if self.depth >= self.max_depth {
+            return Some(Object::Err(ErrorObj::new(self.too_deep())));
+        }
+
+        self.depth += 1;
+        let ret = self.nested_chain(expr);
+        self.depth -= 1;
+        ret
+    }
+
+    fn nested_chain(&mut self, expr: Expression) -> Option<Object> {
+        match expr {
+            Expression::Call(_) | Expression::MethodCall(_) => {
+                match self.prepare_call(expr)? {
+                    Ok(call) => Some(self.apply(call.func, call.args, call.keywords)),
+                    Err(obj) => Some(obj),
+                }
+            }
+            Expression::Index(index) => self.index_link(index),
+            Expression::Slice(slice) => self.slice_link(slice),
+            Expression::Field(field) => self.field_link(field),
+            expr => Some(self.expr(expr)),
+        }
+    }
+
+    fn index_link(&mut self, index: IndexExpression) -> Option<Object> {
+        let IndexExpression { lhs_exp, index, optional } = index;
+        let left = self.chain(*lhs_exp)?;
+        if optional && matches!(left, Object::Null(_)) {
+            return None;
+        }
+        if self.is_abrupt(&left) {
+            return Some(left);
+        }
+        let index = self.expr(*index);
+        if self.is_abrupt(&index) {
+            return Some(index);
+        }
+        match self.hook(&left, "op_index") {
+            Some(func) => Some(self.apply(func, vec![left, index], Vec::new())),
+            None => Some(self.index(left, index)),
+        }
+    }
+
+    fn slice_link(&mut self, slice: SliceExpression) -> Option<Object> {
+        let SliceExpression { lhs_exp, start, end, step, optional } = slice;
+        let left = self.chain(*lhs_exp)?;
+        if optional && matches!(left, Object::Null(_)) {
+            return None;
+        }
+        if self.is_abrupt(&left) {
+            return Some(left);
+        }
+        let mut bounds = Vec::new();
+        for bound in [start, end, step] {
+            match bound.map(|bound| self.expr(*bound)) {
+                None | Some(Object::Null(_)) => bounds.push(None),
+                Some(Object::Int(int)) => bounds.push(Some(int.value)),
+                Some(obj) if self.is_abrupt(&obj) => return Some(obj),
+                Some(obj) => {
+                    return Some(Object::Err(
+                        ErrorObj::new(format!("Slice bounds must be integers, got {}", obj.inspect()))
+                    ));
+                }
+            }
+        }
+        Some(Eval::slice(left, bounds[0], bounds[1], bounds[2]))
+    }
+
+    fn field_link(&mut self, field: FieldExpression) -> Option<Object> {
+        let FieldExpression { lhs_exp, name, optional } = field;
+        let left = self.chain(*lhs_exp)?;
+        if optional && matches!(left, Object::Null(_)) {
+            return None;
+        }
+        if self.is_abrupt(&left) {
+            return Some(left);
+        }
+        Some(self.field(left, &name.name))
+    }
+
+    // Evaluate the function and the arguments of a call without calling it. None is returned if
+    // the call is short-circuited by '?.', and an error if evaluating them failed.
+    fn prepare_call(&mut self, expr: Expression) -> Option<Result<PreparedCall, Object>> {
+        match expr {
+            Expression::Call(call) => {
+                let func = self.chain(*call.ident)?;
+                if call.optional && matches!(func, Object::Null(_)) {
+                    return None;
+                }
+                Some(self.arguments(func, call.args, None))
+            }
+            Expression::MethodCall(call) => {
+                let MethodCallExpression { receiver, name, args, optional } = call;
+                let receiver = self.chain(*receiver)?;
+                if optional && matches!(receiver, Object::Null(_)) {
+                    return None;
+                }
+                if self.is_abrupt(&receiver) {
+                    return Some(Err(receiver));
+                }
+                // 'module.f(args)' calls the exported function
+                if let Object::Module(module) = receiver {
+                    let func = module.get(&name.name);
+                    return Some(self.arguments(func, args, None));
+                }
+                let func = self.method(&receiver, &name.name);
+                // 'receiver.f(args)' behaves as 'receiver |> f(args)'
+                Some(self.arguments(func, args, Some(receiver)))
+            }
+            expr => Some(Err(self.expr(expr))),
+        }
+    }
+
+    // Look up a method in the table of the receiver's type, then a function in env.
+    fn method(&self, receiver: &Object, name: &String) -> Object {
+        let ty = receiver.type_name();
+        let method = self.methods
+            .get(&ty)
+            .and_then(|methods| methods.get(name))
+            .cloned();
+        if let Some(Object::Func(ref func)) = method {
+            if func.params.first().and_then(|param| param.name()) != Some("self") {
+                return Object::Err(
+                    ErrorObj::new(format!("{}::{} is an associated function, not a method", ty, name))
+                );
+            }
+        }
+        match method.or_else(|| self.env.borrow().get(name)) {
+            Some(func) => func,
+            None => {
+                Object::Err(
+                    ErrorObj::new(format!("Method {} not found for {}", name, receiver.inspect()))
+                )
+            }
+        }
+    }
+
+    // Hooks are methods defined by 'impl' for structs and enums, which are used by operators and
+    // conversion to strings. They have reserved names starting with 'op_', such as 'op_add' for
+    // '+' and 'op_to_string' for 'puts', so that a method named 'add' stays an ordinary method.
+    // Only the left operand is looked up, so '3 * v' is an error if 'v' has 'op_mul' and must be
+    // written 'v * 3'.
+    fn hook(&self, obj: &Object, name: &str) -> Option<Object> {
+        match obj {
+            Object::Struct(_) | Object::Variant(_) => {
+                self.methods.get(&obj.type_name()).and_then(|methods| methods.get(name)).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    // 'a > b' calls 'op_lt(b, a)', 'a <= b' negates 'op_lt(b, a)', 'a >= b' negates 'op_lt(a, b)'
+    // and 'a != b' negates 'op_eq(a, b)'
+    fn infix_hook(&mut self, op: TokenKind, func: Object, left: Object, right: Object) -> Object {
+        let ty = left.type_name();
+        let ret = match op {
+            TokenKind::GT | TokenKind::LtEq => self.apply(func, vec![right, left], Vec::new()),
+            _                               => self.apply(func, vec![left, right], Vec::new()),
+        };
+        match (op, ret) {
+            (TokenKind::Plus | TokenKind::Minus | TokenKind::Asterisk, ret) => ret,
+            (TokenKind::NotEq | TokenKind::LtEq | TokenKind::GtEq, Object::Bool(bool)) => {
+                Object::Bool(Boolean::new(!bool.value))
+            }
+            (_, Object::Bool(bool)) => Object::Bool(bool),
+            (_, Object::Err(err)) => Object::Err(err),
+            (_, ret) => {
+                let name = match op {
+                    TokenKind::LT | TokenKind::GT | TokenKind::LtEq | TokenKind::GtEq => "op_lt",
+                    _ => "op_eq",
+                };
+                Object::Err(
+                    ErrorObj::new(format!("{}::{} must return a boolean, got {}", ty, name, ret.inspect()))
+                )
+            }
+        }
+    }
+
+    // Convert the object to a string object, using 'op_to_string' hook if defined
+    fn display(&mut self, obj: Object) -> Object {
+        let func = match self.hook(&obj, "op_to_string") {
+            Some(func) => func,
+            None => return Object::Str(StringObj::new(obj.inspect())),
+        };
+
+        let ty = obj.type_name();
+        match self.apply(func, vec![obj], Vec::new()) {
+            Object::Str(string) => Object::Str(string),
+            Object::Err(err) => Object::Err(err),
+            ret => {
+                Object::Err(
+                    ErrorObj::new(format!("{}::op_to_string must return a string, got {}", ty, ret.inspect()))
+                )
+            }
+        }
+    }
+
+    fn field(&self, left: Object, name: &String) -> Object {
+        match left {
+            Object::Struct(obj) => match obj.get(name) {
+                Some(value) => value.clone(),
+                None => Object::Err(ErrorObj::new(format!("Struct {} has no field {}", obj.name, name))),
+            }
+            Object::Hash(hash) => {
+                hash.get(&HashKey::Str(name.clone())).cloned().unwrap_or(Object::Null(Null::new()))
+            }
+            Object::Module(module) => module.get(name),
+            Object::Tuple(tuple) => {
+                match name.parse::<usize>().ok().and_then(|i| tuple.elements.get(i)) {
+                    Some(elem) => elem.clone(),
+                    None => Object::Err(ErrorObj::new(format!("Tuple {} has no field {}", tuple.inspect(), name))),
+                }
+            }
+            Object::Exception(err) => match name.as_str() {
+                "message" => Object::Str(StringObj::new(err.msg)),
+                "kind"    => Object::Str(StringObj::new(err.kind)),
+                "trace"   => {
+                    let trace = err.trace.into_iter().map(|name| Object::Str(StringObj::new(name))).collect();
+                    Object::Array(ArrayObj::new(trace))
                 }
-                ret
+                "value"   => err.value.map(|value| *value).unwrap_or(Object::Null(Null::new())),
+                _ => Object::Err(ErrorObj::new(format!("Error has no field {}", name))),
             }
+            left => Object::Err(ErrorObj::new(format!("Field access not supported: {}.{}", left.inspect(), name))),
         }
     }
 
-    fn expr(&mut self, expr: Expression) -> Object {
-        match expr {
-            Expression::Ident(ident)   => {
-                match self.env.borrow().get(&ident.name) {
-                    Some(obj) => obj.clone(),
-                    _ => Object::Err(ErrorObj::new(format!("Identifier not found: {}", ident.name)))
+    fn index(&self, left: Object, index: Object) -> Object {
+        match (&left, &index) {
+            (Object::Array(ArrayObj { elements }), Object::Int(int))
+            | (Object::Tuple(TupleObj { elements }), Object::Int(int)) => {
+                match Eval::position(int.value, elements.len()).and_then(|i| elements.get(i)) {
+                    Some(elem) => elem.clone(),
+                    None => Object::Null(Null::new()),
                 }
             }
-            Expression::Int(int)       => Object::Int(Integer::new(int.value)),
-            Expression::Bool(bool)     => Object::Bool(Boolean::new(bool.value)),
-            Expression::Str(string)    => Object::Str(StringObj::new(string.str.clone())),
-
-            Expression::Prefix(prefix) => {
-                let right = self.expr(prefix.rhs_exp);
-                if !self.is_error(&right) {
-                    self.prefix(prefix.operator, right)
-                } else {
-                    right
+            // Strings are indexed by Unicode scalar values
+            (Object::Str(string), Object::Int(int)) => {
+                let len = string.str.chars().count();
+                match Eval::position(int.value, len).and_then(|i| string.str.chars().nth(i)) {
+                    Some(c) => Object::Str(StringObj::new(c.to_string())),
+                    None => Object::Null(Null::new()),
                 }
             }
-            Expression::Infix(infix) => {
-                let right = self.expr(infix.rhs_exp);
-                if self.is_error(&right) {
-                    return right;
+            (Object::Hash(hash), key) => {
+                match HashKey::from_object(key) {
+                    Some(key) => hash.get(&key).cloned().unwrap_or(Object::Null(Null::new())),
+                    None => Object::Err(ErrorObj::new(format!("Unusable as hash key: {}", key.inspect()))),
                 }
-
-                let left  = self.expr(infix.lhs_exp);
-                if self.is_error(&left) {
-                    return left;
-                }
-
-                self.infix(infix.operator, left, right)
             }
-            Expression::Postfix(postfix) => {
-                let right = self.expr(postfix.lhs_exp);
-                if !self.is_error(&right) {
-                    self.postfix(postfix.operator, right)
-                } else {
-                    right
-                }
+            _ => {
+                Object::Err(
+                    ErrorObj::new(
+                        format!("Index operator not supported: {}[{}]", left.inspect(), index.inspect())
+                    )
+                )
             }
+        }
+    }
 
-            Expression::If(if_expr) => {
-                self.if_expr(if_expr)
+    // The position of an index in a sequence of the length, where a negative index counts from
+    // the end, such as -1 for the last element. The position may be past the end.
+    fn position(index: i64, len: usize) -> Option<usize> {
+        if index < 0 {
+            len.checked_sub(usize::try_from(index.unsigned_abs()).ok()?)
+        } else {
+            usize::try_from(index).ok()
+        }
+    }
+
+    // 'seq[start:end:step]' of an array, a tuple or a string, with the bounds of Python: they
+    // count from the end if negative and are clamped to the sequence, and omitted ones cover the
+    // whole sequence in the direction of the step. Unlike a view, the slice is a copy of the
+    // elements: sequences are values here, so writing to it leaves the sequence unchanged.
+    fn slice(left: Object, start: Option<i64>, end: Option<i64>, step: Option<i64>) -> Object {
+        let step = step.unwrap_or(1);
+        if step == 0 {
+            return Object::Err(ErrorObj::new("Slice step cannot be zero".to_string()));
+        }
+        let positions = |len: usize| -> Vec<usize> {
+            let len = len as i64;
+            let clamp = |bound: i64, low: i64, high: i64| {
+                let bound = if bound < 0 { bound.saturating_add(len) } else { bound };
+                bound.clamp(low, high)
+            };
+            if step > 0 {
+                let start = start.map_or(0, |start| clamp(start, 0, len));
+                let end = end.map_or(len, |end| clamp(end, 0, len));
+                (start..end).step_by(step as usize).map(|i| i as usize).collect()
+            } else {
+                let start = start.map_or(len - 1, |start| clamp(start, -1, len - 1));
+                let end = end.map_or(-1, |end| clamp(end, -1, len - 1));
+                let step = step.unsigned_abs() as usize;
+                ((end + 1)..=start).rev().step_by(step).map(|i| i as usize).collect()
+            }
+        };
+        match left {
+            Object::Array(array) => {
+                let elements = positions(array.elements.len()).into_iter().map(|i| array.elements[i].clone());
+                Object::Array(ArrayObj::new(elements.collect()))
             }
-            Expression::Func(func) => {
-                let params = func.params;
-                let body   = func.body;
-                Object::Func(Function::new(params, body, Rc::clone(&self.env)))
+            Object::Tuple(tuple) => {
+                let elements = positions(tuple.elements.len()).into_iter().map(|i| tuple.elements[i].clone());
+                Object::Tuple(TupleObj::new(elements.collect()))
             }
-            Expression::Call(call) => {
-                self.call_func(call)
+            Object::Str(string) => {
+                let chars: Vec<char> = string.str.chars().collect();
+                let str = positions(chars.len()).into_iter().map(|i| chars[i]).collect();
+                Object::Str(StringObj::new(str))
             }
+            obj => Object::Err(ErrorObj::new(format!("Slicing not supported: {}", obj.inspect()))),
         }
     }
 
     fn postfix(&self, op: TokenKind, right: Object) -> Object {
         match op {
+            // 'Ok(v)?' is 'v', and 'Err(e)?' returns 'Err(e)' from the enclosing function. Only
+            // the builtin 'Result' is accepted, not an enum which happens to have the same name.
+            TokenKind::Question => {
+                let invalid = |obj: &Object| {
+                    Object::Err(
+                        ErrorObj::new(format!("Invalid uses of postfix operator: ? can't applied to {}", obj.inspect()))
+                    )
+                };
+                match right {
+                    Object::Variant(ref v) if v.builtin && v.name == "Err" => Object::Ret(ReturnValue::new(right)),
+                    Object::Variant(ref v) if v.builtin && v.name == "Ok" => match v.payload.as_deref() {
+                        Some([value]) => value.clone(),
+                        _ => invalid(&right),
+                    },
+                    obj => invalid(&obj),
+                }
+            }
             TokenKind::Inc => {
                 match right {
-                    Object::Int(int) => Object::Int(Integer::new(int.value + 1)),
+                    Object::Int(int) => Eval::checked_int(int.value.checked_add(1), op, int.value),
                     obj => {
                         Object::Err(
                             ErrorObj::new(
@@ -143,7 +1193,7 @@ impl Eval {
             }
             TokenKind::Dec => {
                 match right {
-                    Object::Int(int) => Object::Int(Integer::new(int.value - 1)),
+                    Object::Int(int) => Eval::checked_int(int.value.checked_sub(1), op, int.value),
                     obj => {
                         Object::Err(
                             ErrorObj::new(
@@ -165,13 +1215,13 @@ impl Eval {
             TokenKind::Bang => {
                 match right {
                     Object::Bool(bool) => Object::Bool(Boolean::new(!bool.value)),
-                    Object::Null(_)    => Object::Bool(Boolean::new(false)),
+                    Object::Null(_)    => Object::Bool(Boolean::new(true)),
                     _                  => Object::Bool(Boolean::new(false)),
                 }
             }
             TokenKind::Minus => {
                 match right {
-                    Object::Int(int) => Object::Int(Integer::new(-int.value)),
+                    Object::Int(int) => Eval::checked_int(int.value.checked_neg(), op, int.value),
                     obj => {
                         Object::Err(
                             ErrorObj::new(
@@ -186,7 +1236,7 @@ impl Eval {
             }
             TokenKind::Inc => {
                 match right {
-                    Object::Int(int) => Object::Int(Integer::new(int.value + 1)),
+                    Object::Int(int) => Eval::checked_int(int.value.checked_add(1), op, int.value),
                     obj => {
                         Object::Err(
                             ErrorObj::new(
@@ -201,7 +1251,7 @@ impl Eval {
             }
             TokenKind::Dec => {
                 match right {
-                    Object::Int(int) => Object::Int(Integer::new(int.value - 1)),
+                    Object::Int(int) => Eval::checked_int(int.value.checked_sub(1), op, int.value),
                     obj => {
                         Object::Err(
                             ErrorObj::new(
@@ -219,22 +1269,48 @@ impl Eval {
     }
 
     fn infix(&self, op: TokenKind, left: Object, right: Object) -> Object {
-        if let (Object::Int(left), Object::Int(right)) = (&left, &right) {
-            let left  = left.value;
-            let right = right.value;
+        match op {
+            TokenKind::ComposeRight | TokenKind::ComposeLeft => {
+                if !self.is_callable(&left) || !self.is_callable(&right) {
+                    return Object::Err(
+                        ErrorObj::new(
+                            format!(
+                                "Invalid uses of infix operator: {:?} can't applied to {} and {}",
+                                op, left.inspect(), right.inspect()
+                            )
+                        )
+                    );
+                }
+                return match op {
+                    TokenKind::ComposeRight => Object::Compose(Composition::new(left, right)),
+                    _                       => Object::Compose(Composition::new(right, left)),
+                };
+            }
+            _ => (),
+        }
+
+        match op {
+            TokenKind::Eq    => return Object::Bool(Boolean::new(left.equals(&right))),
+            TokenKind::NotEq => return Object::Bool(Boolean::new(!left.equals(&right))),
+            TokenKind::In    => return Eval::contains(&right, &left),
+            _ => (),
+        }
+
+        if let (Object::Set(left), Object::Set(right)) = (&left, &right) {
             return match op {
-                TokenKind::Plus     => Object::Int(Integer::new(left + right)),
-                TokenKind::Minus    => Object::Int(Integer::new(left - right)),
-                TokenKind::Asterisk => Object::Int(Integer::new(left * right)),
-                TokenKind::Slash    => Object::Int(Integer::new(left / right)),
-                TokenKind::LT       => Object::Bool(Boolean::new(left < right)),
-                TokenKind::GT       => Object::Bool(Boolean::new(left > right)),
+                TokenKind::Pipe      => Object::Set(left.union(right)),
+                TokenKind::Ampersand => Object::Set(left.intersection(right)),
+                TokenKind::Minus     => Object::Set(left.difference(right)),
+                TokenKind::LtEq      => Object::Bool(Boolean::new(left.is_subset(right))),
+                TokenKind::GtEq      => Object::Bool(Boolean::new(right.is_subset(left))),
+                TokenKind::LT        => Object::Bool(Boolean::new(left.len() < right.len() && left.is_subset(right))),
+                TokenKind::GT        => Object::Bool(Boolean::new(right.len() < left.len() && right.is_subset(left))),
                 _ => {
                     Object::Err(
                         ErrorObj::new(
                             format!(
                                 "Invalid uses of infix operator: {:?} can't applied to {} and {}",
-                                op, left, right
+                                op, left.inspect(), right.inspect()
                             )
                         )
                     )
@@ -242,12 +1318,21 @@ impl Eval {
             };
         }
 
-        if let (Object::Bool(left), Object::Bool(right)) = (&left, &right) {
+        if let (Object::Int(left), Object::Int(right)) = (&left, &right) {
             let left  = left.value;
             let right = right.value;
             return match op {
-                TokenKind::Eq       => Object::Bool(Boolean::new(left == right)),
-                TokenKind::NotEq    => Object::Bool(Boolean::new(left != right)),
+                TokenKind::Plus     => Eval::checked_infix(left.checked_add(right), op, left, right),
+                TokenKind::Minus    => Eval::checked_infix(left.checked_sub(right), op, left, right),
+                TokenKind::Asterisk => Eval::checked_infix(left.checked_mul(right), op, left, right),
+                TokenKind::Slash if right == 0 => {
+                    Object::Err(ErrorObj::new(format!("Division by zero: {} / {}", left, right)))
+                }
+                TokenKind::Slash    => Eval::checked_infix(left.checked_div(right), op, left, right),
+                TokenKind::LT       => Object::Bool(Boolean::new(left < right)),
+                TokenKind::GT       => Object::Bool(Boolean::new(left > right)),
+                TokenKind::LtEq     => Object::Bool(Boolean::new(left <= right)),
+                TokenKind::GtEq     => Object::Bool(Boolean::new(left >= right)),
                 _ => {
                     Object::Err(
                         ErrorObj::new(
@@ -261,13 +1346,22 @@ impl Eval {
             };
         }
 
+        if let (Object::Bool(left), Object::Bool(right)) = (&left, &right) {
+            return Object::Err(
+                ErrorObj::new(
+                    format!(
+                        "Invalid uses of infix operator: {:?} can't applied to {} and {}",
+                        op, left.value, right.value
+                    )
+                )
+            );
+        }
+
         if let (Object::Str(left), Object::Str(right)) = (&left, &right) {
             let left  = &left.str;
             let right = &right.str;
             return match op {
                 TokenKind::Plus  => Object::Str(StringObj::new(format!("{}{}", left, right))),
-                TokenKind::Eq    => Object::Bool(Boolean::new(left == right)),
-                TokenKind::NotEq => Object::Bool(Boolean::new(left != right)),
                 _ => {
                     Object::Err(
                         ErrorObj::new(
@@ -290,67 +1384,586 @@ impl Eval {
         )
     }
 
+    // 'item in collection' for elements of sets, arrays and tuples, keys of hashes and substrings
+    fn contains(collection: &Object, item: &Object) -> Object {
+        let ret = match collection {
+            Object::Set(set) => set.contains(item),
+            Object::Array(ArrayObj { elements }) | Object::Tuple(TupleObj { elements }) => {
+                elements.iter().any(|elem| elem.equals(item))
+            }
+            Object::Hash(hash) => HashKey::from_object(item).is_some_and(|key| hash.get(&key).is_some()),
+            Object::Str(string) => match item {
+                Object::Str(sub) => string.str.contains(&sub.str),
+                _ => return Object::Err(ErrorObj::new(format!("Can't look for {} in a string", item.inspect()))),
+            },
+            _ => {
+                return Object::Err(
+                    ErrorObj::new(format!("Can't look for {} in {}", item.inspect(), collection.inspect()))
+                );
+            }
+        };
+        Object::Bool(Boolean::new(ret))
+    }
+
     fn if_expr(&mut self, if_expr: IfExpression) -> Object {
         let cond = self.expr(*if_expr.condition);
-        if self.is_error(&cond) {
-            return cond;
+        if self.is_abrupt(&cond) {
+            cond
+        } else if self.is_truthy(&cond) {
+            self.blk_stmt(if_expr.consequence)
+        } else if let Some(stmt) = if_expr.alternative {
+            self.blk_stmt(stmt)
         } else {
-            if self.is_truthy(&cond) {
-                return self.stmt(Statement::Blk(if_expr.consequence));
-            }else if let Some(stmt) = if_expr.alternative {
-                return self.stmt(Statement::Blk(stmt));
+            Object::Err(ErrorObj::new("No else found".to_string()))
+        }
+    }
+
+    fn for_stmt(&mut self, stmt: ForStatement) -> Object {
+        let iterable = self.expr(stmt.iterable);
+        if self.is_abrupt(&iterable) {
+            return iterable;
+        }
+        let mut items = match self.iterate(iterable) {
+            Ok(items) => items,
+            Err(err) => return err,
+        };
+
+        while let Some(item) = self.next_item(&mut items) {
+            if self.is_abrupt(&item) {
+                return item;
+            }
+            let env = match self.bind_item(&stmt.pattern, item) {
+                Ok(env) => env,
+                Err(err) => return err,
+            };
+
+            let curr_env = std::mem::replace(&mut self.env, env);
+            let ret = self.stmt(Statement::Blk(stmt.body.clone()));
+            self.env = curr_env;
+            if self.is_abrupt(&ret) {
+                return ret;
+            }
+        }
+        Object::Null(Null::new())
+    }
+
+    fn try_expr(&mut self, try_expr: TryExpression) -> Object {
+        let TryExpression { body, catch, finally } = try_expr;
+        let mut ret = self.stmt(Statement::Blk(body));
+
+        if let (Object::Err(err), Some((ident, blk))) = (&ret, catch) {
+            let mut local_env = Env::new_with_outer(Rc::clone(&self.env));
+            local_env.set(ident.name, Object::Exception(err.clone()));
+
+            let curr_env = Rc::clone(&self.env);
+            self.env = Rc::new(RefCell::new(local_env));
+            ret = self.stmt(Statement::Blk(blk));
+            self.env = curr_env;
+        }
+
+        // Errors and returns in 'finally' take precedence over the result of the other blocks
+        if let Some(blk) = finally {
+            let finally = self.stmt(Statement::Blk(blk));
+            if matches!(finally, Object::Err(_) | Object::Ret(_)) {
+                return finally;
+            }
+        }
+        ret
+    }
+
+    fn match_expr(&mut self, match_expr: MatchExpression) -> Object {
+        let value = self.expr(*match_expr.subject);
+        if self.is_abrupt(&value) {
+            return value;
+        }
+
+        for arm in match_expr.arms.into_iter() {
+            let mut bindings = Vec::new();
+            if self.match_pattern(&arm.pattern, &value, &mut bindings).is_err() {
+                continue;
+            }
+
+            let mut local_env = Env::new_with_outer(Rc::clone(&self.env));
+            for (name, obj) in bindings.into_iter() {
+                local_env.set(name, obj);
+            }
+
+            let curr_env = Rc::clone(&self.env);
+            self.env = Rc::new(RefCell::new(local_env));
+            let guard = match arm.guard {
+                Some(guard) => self.expr(guard),
+                None => Object::Bool(Boolean::new(true)),
+            };
+            let ret = if self.is_abrupt(&guard) {
+                Some(guard)
+            } else if self.is_truthy(&guard) {
+                Some(self.stmt(Statement::Blk(arm.body)))
             } else {
-                Object::Err(ErrorObj::new(format!("No else found")))
+                None
+            };
+            self.env = curr_env;
+
+            if let Some(ret) = ret {
+                return ret;
             }
         }
+
+        Object::Err(ErrorObj::new(format!("No match arm matched: {}", value.inspect())))
     }
 
-    fn call_func(&mut self, call: CallExpression) -> Object {
-        let func = match *call.ident {
-            Expression::Ident(ident) => self.expr(Expression::Ident(ident)),
-            Expression::Func(func)   => self.expr(Expression::Func(func)),
-            Expression::Call(call)   => self.expr(Expression::Call(call)),
-            _ => return Object::Err(ErrorObj::new(format!("You can't call function from {:?}", call))),
-        };
+    // Test the value against the pattern, collecting the variables it binds. On mismatch, the
+    // reason is returned so that 'let' and function calls can report it.
+    fn match_pattern(
+        &mut self, pattern: &Pattern, value: &Object, bindings: &mut Vec<(String, Object)>
+    ) -> Result<(), String> {
+        if self.depth >= self.max_depth {
+            return Err(self.too_deep());
+        }
 
-        let func = match func {
-            Object::Func(func) => func,
-            Object::Buildin(b) => return (b.func)(call.args.iter().map(|arg| self.expr(arg.clone())).collect()),
-            _ => return Object::Err(ErrorObj::new(format!("{:?} is not a function", func))),
-        };
+        self.depth += 1;
+        let ret = self.nested_match_pattern(pattern, value, bindings);
+        self.depth -= 1;
+        ret
+    }
 
-        if call.args.len() != func.params.len() {
-            return Object::Err(
-                ErrorObj::new(
-                    format!(
-                        "Number of argument is less than or greater than: expect {}, got {}",
-                        func.params.len(), call.args.len()
+    fn nested_match_pattern(
+        &mut self, pattern: &Pattern, value: &Object, bindings: &mut Vec<(String, Object)>
+    ) -> Result<(), String> {
+        match pattern {
+            Pattern::Wildcard => Ok(()),
+            Pattern::Binding(ident) => {
+                bindings.push((ident.name.clone(), value.clone()));
+                Ok(())
+            }
+            Pattern::Literal(exp) => {
+                let matched = match (self.expr(exp.clone()), value) {
+                    (Object::Null(_), Object::Null(_)) => true,
+                    (lit, value) => {
+                        match (HashKey::from_object(&lit), HashKey::from_object(value)) {
+                            (Some(lit), Some(value)) => lit == value,
+                            _ => false,
+                        }
+                    }
+                };
+                match matched {
+                    true  => Ok(()),
+                    false => Err(format!("Expected {}, got {}", pattern.string(), value.inspect())),
+                }
+            }
+            Pattern::Range(start, end, inclusive) => {
+                let matched = match value {
+                    Object::Int(int) => {
+                        start.value <= int.value
+                            && (int.value < end.value || *inclusive && int.value == end.value)
+                    }
+                    _ => false,
+                };
+                match matched {
+                    true  => Ok(()),
+                    false => Err(format!("Expected a value in {}, got {}", pattern.string(), value.inspect())),
+                }
+            }
+            Pattern::Or(patterns) => {
+                for pat in patterns.iter() {
+                    let len = bindings.len();
+                    if self.match_pattern(pat, value, bindings).is_ok() {
+                        return Ok(());
+                    }
+                    bindings.truncate(len);
+                }
+                Err(format!("Expected {}, got {}", pattern.string(), value.inspect()))
+            }
+            Pattern::Array(patterns, rest) => {
+                let elements = match value {
+                    Object::Array(array) => &array.elements,
+                    _ => {
+                        return Err(
+                            format!(
+                                "Expected an array to destructure with {}, got {}",
+                                pattern.string(), value.inspect()
+                            )
+                        );
+                    }
+                };
+                let too_short = elements.len() < patterns.len();
+                let too_long  = rest.is_none() && elements.len() > patterns.len();
+                if too_short || too_long {
+                    return Err(
+                        format!(
+                            "Expected {} {} {} to destructure with {}, got {}",
+                            if rest.is_some() { "at least" } else { "exactly" },
+                            patterns.len(), if patterns.len() == 1 { "element" } else { "elements" },
+                            pattern.string(), value.inspect()
+                        )
+                    );
+                }
+
+                for (pat, elem) in patterns.iter().zip(elements.iter()) {
+                    self.match_pattern(pat, elem, bindings)?;
+                }
+                if let Some(rest) = rest {
+                    let remains = Object::Array(ArrayObj::new(elements[patterns.len()..].to_vec()));
+                    self.match_pattern(rest, &remains, bindings)?;
+                }
+                Ok(())
+            }
+            Pattern::Tuple(patterns) => {
+                let elements = match value {
+                    Object::Tuple(tuple) if tuple.elements.len() == patterns.len() => &tuple.elements,
+                    _ => {
+                        return Err(
+                            format!(
+                                "Expected a tuple of {} {} to destructure with {}, got {}",
+                                patterns.len(), if patterns.len() == 1 { "element" } else { "elements" },
+                                pattern.string(), value.inspect()
+                            )
+                        );
+                    }
+                };
+                for (pat, elem) in patterns.iter().zip(elements.iter()) {
+                    self.match_pattern(pat, elem, bindings)?;
+                }
+                Ok(())
+            }
+            Pattern::Hash(pairs, rest) => {
+                let hash = match value {
+                    Object::Hash(hash) => hash,
+                    _ => {
+                        return Err(
+                            format!(
+                                "Expected a hash to destructure with {}, got {}",
+                                pattern.string(), value.inspect()
+                            )
+                        );
+                    }
+                };
+
+                for (key, pat) in pairs.iter() {
+                    match hash.get(&HashKey::Str(key.clone())) {
+                        Some(value) => self.match_pattern(pat, value, bindings)?,
+                        None => {
+                            return Err(
+                                format!(
+                                    "Key {} not found to destructure with {}",
+                                    key, pattern.string()
+                                )
+                            );
+                        }
+                    }
+                }
+                if let Some(rest) = rest {
+                    let mut remains = HashObj::new();
+                    for (key, value) in hash.pairs.iter() {
+                        let used = match key {
+                            Object::Str(key) => pairs.iter().any(|(name, _)| *name == key.str),
+                            _ => false,
+                        };
+                        if let (false, Some(hash_key)) = (used, HashKey::from_object(key)) {
+                            remains.set(hash_key, key.clone(), value.clone());
+                        }
+                    }
+                    self.match_pattern(rest, &Object::Hash(remains), bindings)?;
+                }
+                Ok(())
+            }
+            Pattern::Variant(path, patterns) => {
+                let variant = match value {
+                    Object::Variant(variant)
+                        if variant.enum_name == path.ty.name && variant.name == path.name.name => variant,
+                    _ => return Err(format!("Expected {}, got {}", pattern.string(), value.inspect())),
+                };
+                match (patterns, &variant.payload) {
+                    (Some(patterns), Some(payload)) if patterns.len() == payload.len() => {
+                        for (pat, value) in patterns.iter().zip(payload.iter()) {
+                            self.match_pattern(pat, value, bindings)?;
+                        }
+                        Ok(())
+                    }
+                    (None, None) => Ok(()),
+                    _ => Err(format!("Expected {}, got {}", pattern.string(), value.inspect())),
+                }
+            }
+        }
+    }
+
+    // 'value |> f' calls 'f(value)'. If the right hand side is a call, the value replaces each '_'
+    // in the arguments, or becomes the first argument if there is no '_'.
+    fn pipeline(&mut self, value: Object, rhs: Expression) -> Object {
+        match rhs {
+            Expression::Call(call) if !call.optional => {
+                let func = self.expr(*call.ident);
+                self.call_func_with(func, call.args, Some(value))
+            }
+            rhs => {
+                let func = self.expr(rhs);
+                if self.is_abrupt(&func) {
+                    return func;
+                }
+                self.apply(func, vec![value], Vec::new())
+            }
+        }
+    }
+
+    fn call_func_with(&mut self, func: Object, args: Vec<Argument>, piped: Option<Object>) -> Object {
+        match self.arguments(func, args, piped) {
+            Ok(call) => self.apply(call.func, call.args, call.keywords),
+            Err(err) => err,
+        }
+    }
+
+    // Evaluate the arguments. The piped value replaces the placeholder, or is the first argument.
+    fn arguments(
+        &mut self, func: Object, args: Vec<Argument>, piped: Option<Object>
+    ) -> Result<PreparedCall, Object> {
+        if self.is_abrupt(&func) {
+            return Err(func);
+        }
+
+        let mut positional = Vec::new();
+        let mut keywords   = Vec::new();
+        let mut replaced   = false;
+        for arg in args.into_iter() {
+            match arg {
+                Argument::Positional(Expression::Placeholder(_)) if piped.is_some() => {
+                    positional.extend(piped.clone());
+                    replaced = true;
+                }
+                Argument::Positional(exp) => {
+                    let arg = self.expr(exp);
+                    if self.is_abrupt(&arg) {
+                        return Err(arg);
+                    }
+                    positional.push(arg);
+                }
+                Argument::Keyword(name, exp) => {
+                    let arg = self.expr(exp);
+                    if self.is_abrupt(&arg) {
+                        return Err(arg);
+                    }
+                    keywords.push((name.name, arg));
+                }
+                Argument::Spread(exp) => {
+                    match self.expr(exp) {
+                        Object::Array(array) => positional.extend(array.elements),
+                        Object::Hash(hash) => {
+                            for (key, value) in hash.pairs.into_iter() {
+                                match key {
+                                    Object::Str(key) => keywords.push((key.str, value)),
+                                    key => {
+                                        return Err(
+                                            Object::Err(
+                                                ErrorObj::new(
+                                                    format!("Keyword argument must be a string: {}", key.inspect())
+                                                )
+                                            )
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                        Object::Err(err) => return Err(Object::Err(err)),
+                        obj => {
+                            return Err(
+                                Object::Err(
+                                    ErrorObj::new(format!("You can't spread {} into arguments", obj.inspect()))
+                                )
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        if let (false, Some(piped)) = (replaced, piped) {
+            positional.insert(0, piped);
+        }
+
+        Ok(PreparedCall { func, args: positional, keywords })
+    }
+
+    fn apply(&mut self, func: Object, args: Vec<Object>, keywords: Vec<(String, Object)>) -> Object {
+        match func {
+            Object::Func(func) => self.call_func(func, args, keywords),
+            func => self.apply_buildin(func, args, keywords),
+        }
+    }
+
+    // Apply a function other than ones defined in code
+    fn apply_buildin(&mut self, func: Object, args: Vec<Object>, keywords: Vec<(String, Object)>) -> Object {
+        match func {
+            Object::Compose(comp) => {
+                let ret = self.apply(*comp.first, args, keywords);
+                if self.is_abrupt(&ret) {
+                    return ret;
+                }
+                self.apply(*comp.second, vec![ret], Vec::new())
+            }
+            Object::Buildin(b) if keywords.is_empty() && b.display => {
+                let mut strings = Vec::new();
+                for arg in args.into_iter() {
+                    let string = self.display(arg);
+                    if self.is_abrupt(&string) {
+                        return string;
+                    }
+                    strings.push(string);
+                }
+                (b.func)(strings)
+            }
+            Object::Buildin(b) if keywords.is_empty() => (b.func)(args),
+            Object::Native(native) if keywords.is_empty() => self.native(native, args),
+            Object::Ctor(ctor) if keywords.is_empty() && args.len() == ctor.fields.len() => {
+                Object::Variant(VariantObj::new(ctor.enum_name, ctor.name, Some(args), ctor.builtin))
+            }
+            Object::Ctor(ctor) if keywords.is_empty() => {
+                Object::Err(
+                    ErrorObj::new(
+                        format!(
+                            "Wrong number of arguments to {}: expect {}, got {}",
+                            ctor.inspect(), ctor.fields.len(), args.len()
+                        )
                     )
                 )
-            );
-        }
-        let mut args = Vec::new();
-        for (arg, ident) in call.args.iter().zip(func.params.iter()) {
-            let name = ident.name.clone();
-            let arg  = self.expr(arg.clone());
-            args.push((name, arg));
+            }
+            Object::Buildin(_) | Object::Ctor(_) | Object::Native(_) => {
+                Object::Err(ErrorObj::new(format!("{} doesn't take keyword arguments", func.inspect())))
+            }
+            _ => Object::Err(ErrorObj::new(format!("{} is not a function", func.inspect()))),
         }
+    }
 
-        let mut local_env = Env::new_with_outer(Rc::clone(&func.env));
-        for (name, arg) in args.iter() {
-            local_env.set(name.clone(), arg.clone());
+    fn call_func(&mut self, func: Function, args: Vec<Object>, keywords: Vec<(String, Object)>) -> Object {
+        if self.calls >= self.max_calls {
+            return Object::Err(ErrorObj::new(format!("Maximum call depth exceeded: {}", self.max_calls)));
         }
 
+        self.calls += 1;
+        let ret = self.nested_call_func(func, args, keywords);
+        self.calls -= 1;
+        ret
+    }
+
+    fn nested_call_func(&mut self, func: Function, args: Vec<Object>, keywords: Vec<(String, Object)>) -> Object {
+        let (slots, rest) = match Eval::slots(&func, args, keywords) {
+            Ok(slots) => slots,
+            Err(err) => return err,
+        };
+
+        // Default values are evaluated in the function's scope, so they can refer to the
+        // parameters before them.
         let curr_env = Rc::clone(&self.env);
-        self.env = Rc::new(RefCell::new(local_env));
-        let ret = self.stmt(Statement::Blk(func.body));
+        self.env = Rc::new(RefCell::new(Env::new_with_outer(Rc::clone(&func.env))));
+        let ret = self.bind_params(&func, slots, rest);
+        let ret = match ret {
+            Some(err) => err,
+            None if func.generator => self.generator(&func),
+            None => self.blk_stmt(func.body),
+        };
         self.env = curr_env;
 
         match ret {
             Object::Ret(ret) => *ret.value,
+            Object::Err(mut err) => {
+                err.trace.push(func.name.unwrap_or_else(|| "<anonymous>".to_string()));
+                Object::Err(err)
+            }
             _ => ret,
         }
     }
+
+    // Assign each argument to a parameter. Positional arguments come first, then keywords. The
+    // arguments left are given for the rest parameter.
+    fn slots(
+        func: &Function, args: Vec<Object>, keywords: Vec<(String, Object)>
+    ) -> Result<(Vec<Option<Object>>, Vec<Object>), Object> {
+        let required = func.params.iter().filter(|param| param.default.is_none()).count();
+        if args.len() > func.params.len() && func.rest.is_none() {
+            let expect = match required == func.params.len() {
+                true  => format!("{}", func.params.len()),
+                false => format!("{} to {}", required, func.params.len()),
+            };
+            return Err(
+                Object::Err(
+                    ErrorObj::new(
+                        format!(
+                            "Wrong number of arguments to {}: expect {}, got {}",
+                            func.signature(), expect, args.len()
+                        )
+                    )
+                )
+            );
+        }
+
+        let mut slots: Vec<Option<Object>> = vec![None; func.params.len()];
+        let mut args = args.into_iter();
+        for slot in slots.iter_mut() {
+            *slot = args.next();
+        }
+        let rest: Vec<Object> = args.collect();
+
+        for (name, arg) in keywords.into_iter() {
+            let pos = func.params.iter().position(|param| param.name() == Some(name.as_str()));
+            match pos {
+                Some(pos) if slots[pos].is_none() => slots[pos] = Some(arg),
+                Some(_) => {
+                    return Err(
+                        Object::Err(
+                            ErrorObj::new(format!("Multiple values for parameter {} of {}", name, func.signature()))
+                        )
+                    );
+                }
+                None => {
+                    return Err(
+                        Object::Err(
+                            ErrorObj::new(format!("Unknown keyword argument {} to {}", name, func.signature()))
+                        )
+                    );
+                }
+            }
+        }
+
+        Ok((slots, rest))
+    }
+
+    // Bind arguments to parameters in the current env. An error object is returned on failure.
+    fn bind_params(&mut self, func: &Function, slots: Vec<Option<Object>>, rest: Vec<Object>) -> Option<Object> {
+        for (param, slot) in func.params.iter().zip(slots) {
+            let arg = match (slot, &param.default) {
+                (Some(arg), _) => arg,
+                (None, Some(default)) => {
+                    let arg = self.expr(default.clone());
+                    if self.is_abrupt(&arg) {
+                        return Some(arg);
+                    }
+                    arg
+                }
+                (None, None) => {
+                    return Some(
+                        Object::Err(
+                            ErrorObj::new(
+                                format!(
+                                    "Missing argument for parameter {} of {}",
+                                    param.pattern.string(), func.signature()
+                                )
+                            )
+                        )
+                    );
+                }
+            };
+
+            let mut bindings = Vec::new();
+            if let Err(msg) = self.match_pattern(&param.pattern, &arg, &mut bindings) {
+                return Some(Object::Err(ErrorObj::new(msg)));
+            }
+            for (name, obj) in bindings.into_iter() {
+                self.env.borrow_mut().set(name, obj);
+            }
+        }
+
+        if let Some(ref ident) = func.rest {
+            self.env.borrow_mut().set(ident.name.clone(), Object::Array(ArrayObj::new(rest)));
+        }
+        None
+    }
 }
 
 impl Eval {
@@ -362,10 +1975,43 @@ impl Eval {
         }
     }
 
-    fn is_error(&self, obj: &Object) -> bool {
-        match obj {
-            Object::Err(_) => true,
-            _              => false,
+    fn is_callable(&self, obj: &Object) -> bool {
+        matches!(
+            obj,
+            Object::Func(_) | Object::Buildin(_) | Object::Native(_) | Object::Compose(_) | Object::Ctor(_)
+        )
+    }
+
+    fn too_deep(&self) -> String {
+        format!("Evaluation is nested deeper than {}", self.max_depth)
+    }
+
+    // Errors and early returns by '?' stop evaluating the enclosing expressions
+    fn is_abrupt(&self, obj: &Object) -> bool {
+        matches!(obj, Object::Err(_) | Object::Ret(_))
+    }
+
+    fn checked_int(value: Option<i64>, op: TokenKind, operand: i64) -> Object {
+        match value {
+            Some(value) => Object::Int(Integer::new(value)),
+            None => {
+                Object::Err(
+                    ErrorObj::new(format!("Integer overflow: {:?} can't applied to {}", op, operand))
+                )
+            }
+        }
+    }
+
+    fn checked_infix(value: Option<i64>, op: TokenKind, left: i64, right: i64) -> Object {
+        match value {
+            Some(value) => Object::Int(Integer::new(value)),
+            None => {
+                Object::Err(
+                    ErrorObj::new(
+                        format!("Integer overflow: {:?} can't applied to {} and {}", op, left, right)
+                    )
+                )
+            }
         }
     }
 }
@@ -0,0 +1,134 @@
+use crate::{
+    ast::{ImportStatement, Program, Statement},
+    lexer::Lexer,
+    parser::Parser,
+};
+use super::{
+    Eval,
+    env::Env,
+    object::{Object, ObjectTrait, ErrorObj, Null},
+};
+use std::{cell::RefCell, collections::HashMap, fs, path::{Path, PathBuf}, rc::Rc};
+
+// A module is a file evaluated once in its own env, which 'import "path" as name;' binds to the
+// name. Its exported bindings are shared by every import of the file, and they're used as fields
+// and methods of the module, such as 'math.pi' and 'math.add(1, 2)'.
+#[derive(Debug, Clone)]
+pub struct Module {
+    path: String, // As written by the first import
+    exports: Rc<HashMap<String, Object>>,
+}
+
+// A module being evaluated, with its file resolved and the path as written
+#[derive(Debug)]
+pub struct Loading {
+    file: PathBuf,
+    path: String,
+}
+
+impl Module {
+    pub fn is(&self, other: &Module) -> bool {
+        Rc::ptr_eq(&self.exports, &other.exports)
+    }
+
+    pub fn get(&self, name: &str) -> Object {
+        match self.exports.get(name) {
+            Some(obj) => obj.clone(),
+            None => Object::Err(ErrorObj::new(format!("Module {} has no export {}", self.path, name))),
+        }
+    }
+}
+
+impl ObjectTrait for Module {
+    fn inspect(&self) -> String {
+        format!("<module {}>", self.path)
+    }
+}
+
+impl Eval {
+    pub(super) fn import(&mut self, stmt: ImportStatement) -> Object {
+        let module = match self.module(&stmt.path) {
+            Ok(module) => module,
+            Err(err) => return err,
+        };
+        if let Err(msg) = self.env.borrow_mut().define(stmt.name.name, Object::Module(module), false) {
+            return Object::Err(ErrorObj::new(msg));
+        }
+        Object::Null(Null::new())
+    }
+
+    // The module in the file, which is evaluated on the first import. The path is relative to
+    // the file of the importing module, or to the working directory for the main program.
+    fn module(&mut self, path: &str) -> Result<Module, Object> {
+        let dir = self.loading.last().and_then(|module| module.file.parent()).unwrap_or(Path::new(""));
+        let file = fs::canonicalize(dir.join(path))
+            .map_err(|err| Object::Err(ErrorObj::new(format!("Can't import {}: {}", path, err))))?;
+        if let Some(module) = self.modules.get(&file) {
+            return Ok(module.clone());
+        }
+        if let Some(pos) = self.loading.iter().position(|module| module.file == file) {
+            let mut cycle: Vec<&str> = self.loading[pos..].iter().map(|module| module.path.as_str()).collect();
+            cycle.push(path);
+            return Err(Object::Err(ErrorObj::new(format!("Circular import: {}", cycle.join(" -> ")))));
+        }
+
+        let input = fs::read_to_string(&file)
+            .map_err(|err| Object::Err(ErrorObj::new(format!("Can't import {}: {}", path, err))))?;
+        let lexer  = Lexer::new(input.as_str());
+        let parser = Parser::new(lexer.tokenize());
+        let prog = parser.parse()
+            .map_err(|err| Object::Err(ErrorObj::new(format!("Can't import {}: {}", path, err))))?;
+
+        self.loading.push(Loading { file: file.clone(), path: path.to_string() });
+        let exports = self.run_module(prog);
+        self.loading.pop();
+        let exports = match exports {
+            Ok(exports) => exports,
+            Err(Object::Err(mut err)) => {
+                err.trace.push(path.to_string());
+                return Err(Object::Err(err));
+            }
+            Err(obj) => return Err(obj),
+        };
+
+        let module = Module { path: path.to_string(), exports: Rc::new(exports) };
+        self.modules.insert(file, module.clone());
+        Ok(module)
+    }
+
+    // Run the program in a new env with only the globals, and give the exported bindings. Macros
+    // defined by the module are local to it.
+    fn run_module(&mut self, prog: Program) -> Result<HashMap<String, Object>, Object> {
+        let env = Rc::new(RefCell::new(Env::new()));
+        Eval::define_globals(&env);
+        let curr_env = std::mem::replace(&mut self.env, Rc::clone(&env));
+        let curr_macros = std::mem::take(&mut self.macros);
+
+        let ret = match self.expand_macros(prog) {
+            Ok(prog) => {
+                let names: Vec<String> = prog.statements.iter()
+                    .filter_map(|stmt| match stmt {
+                        Statement::Export(stmt) => Some(stmt.names()),
+                        _ => None,
+                    })
+                    .flatten()
+                    .map(|ident| ident.name.clone())
+                    .collect();
+                match self.blk(prog.statements) {
+                    Object::Err(err) => Err(Object::Err(err)),
+                    _ => Ok(names),
+                }
+            }
+            Err(err) => Err(Object::Err(err)),
+        };
+        self.env = curr_env;
+        self.macros = curr_macros;
+
+        let env = env.borrow();
+        let exports = ret?.into_iter().filter_map(|name| {
+            let obj = env.get(&name)?;
+            Some((name, obj))
+        });
+        Ok(exports.collect())
+    }
+}
@@ -1,5 +1,5 @@
-use crate::{eval::env::Env, ast::{Identifier, BlkStatement, Node}};
-use std::{rc::Rc, cell::RefCell, process::exit};
+use crate::{eval::{env::Env, generator::Generator, module::Module, task::Channel}, ast::{Parameter, Identifier, BlkStatement, Node, EnumVariant, Expression, params_string, tuple_string}};
+use std::{rc::Rc, cell::RefCell, collections::HashMap, fs, process::exit};
 
 pub trait ObjectTrait {
     fn inspect(&self) -> String;
@@ -11,9 +11,25 @@ pub enum Object {
     Bool(Boolean),
     Str(StringObj),
     Null(Null),
+    Array(ArrayObj),
+    Tuple(TupleObj),
+    Set(SetObj),
+    Hash(HashObj),
+    StructDef(StructDef),
+    Struct(StructObj),
+    EnumDef(EnumDef),
+    Variant(VariantObj),
+    Ctor(VariantCtor),
+    Exception(ErrorObj),
     Ret(ReturnValue),
     Func(Function),
     Buildin(Buildin),
+    Native(Native),
+    Compose(Composition),
+    Generator(Generator),
+    Channel(Channel),
+    Module(Module),
+    Quote(Quote),
     Err(ErrorObj),
 }
 
@@ -24,14 +40,110 @@ impl ObjectTrait for Object {
             Self::Bool(boolean) => boolean.inspect(),
             Self::Str(string)   => string.inspect(),
             Self::Null(null)    => null.inspect(),
+            Self::Array(array)  => array.inspect(),
+            Self::Tuple(tuple)  => tuple.inspect(),
+            Self::Set(set)      => set.inspect(),
+            Self::Hash(hash)    => hash.inspect(),
+            Self::StructDef(d)  => d.inspect(),
+            Self::Struct(obj)   => obj.inspect(),
+            Self::EnumDef(d)    => d.inspect(),
+            Self::Variant(v)    => v.inspect(),
+            Self::Ctor(ctor)    => ctor.inspect(),
+            Self::Exception(e)  => format!("{}: {}", e.kind, e.msg),
             Self::Ret(ret)      => ret.inspect(),
             Self::Func(func)    => func.inspect(),
             Self::Buildin(b)    => b.inspect(),
+            Self::Native(_)     => "Buildin Function".to_string(),
+            Self::Compose(comp) => comp.inspect(),
+            Self::Generator(g)  => g.inspect(),
+            Self::Channel(ch)   => ch.inspect(),
+            Self::Module(module) => module.inspect(),
+            Self::Quote(quote)  => quote.inspect(),
             Self::Err(err)      => err.inspect(),
         }
     }
 }
 
+impl Object {
+    // Name of the type, which is used to look up methods defined for the type
+    pub fn type_name(&self) -> String {
+        match self {
+            Self::Int(_)     => "Int",
+            Self::Bool(_)    => "Bool",
+            Self::Str(_)     => "String",
+            Self::Null(_)    => "Null",
+            Self::Array(_)   => "Array",
+            Self::Tuple(_)   => "Tuple",
+            Self::Set(_)     => "Set",
+            Self::Hash(_)    => "Hash",
+            Self::StructDef(_) => "Struct",
+            Self::Struct(obj) => return obj.name.clone(),
+            Self::EnumDef(_) => "Enum",
+            Self::Variant(v) => return v.enum_name.clone(),
+            Self::Ret(ret)   => return ret.value.type_name(),
+            Self::Func(_)
+            | Self::Buildin(_)
+            | Self::Native(_)
+            | Self::Compose(_)
+            | Self::Ctor(_)  => "Function",
+            Self::Generator(_) => "Generator",
+            Self::Channel(_) => "Channel",
+            Self::Module(_)  => "Module",
+            Self::Quote(_)   => "Quote",
+            Self::Err(_)
+            | Self::Exception(_) => "Error",
+        }.to_string()
+    }
+
+    // Equality by value. Values of different types and functions are never equal, and generators
+    // and channels are equal only to themselves.
+    pub fn equals(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Self::Int(left), Self::Int(right))     => left.value == right.value,
+            (Self::Bool(left), Self::Bool(right))   => left.value == right.value,
+            (Self::Str(left), Self::Str(right))     => left.str == right.str,
+            (Self::Null(_), Self::Null(_))          => true,
+            (Self::Array(ArrayObj { elements: left }), Self::Array(ArrayObj { elements: right }))
+            | (Self::Tuple(TupleObj { elements: left }), Self::Tuple(TupleObj { elements: right })) => {
+                left.len() == right.len() && left.iter().zip(right.iter()).all(|(l, r)| l.equals(r))
+            }
+            (Self::Set(left), Self::Set(right)) => left.len() == right.len() && left.is_subset(right),
+            (Self::Hash(left), Self::Hash(right)) => {
+                left.pairs.len() == right.pairs.len()
+                    && left.pairs.iter().all(|(key, value)| {
+                        match HashKey::from_object(key).and_then(|key| right.get(&key)) {
+                            Some(other) => value.equals(other),
+                            None => false,
+                        }
+                    })
+            }
+            (Self::StructDef(left), Self::StructDef(right)) => left.name == right.name,
+            (Self::Struct(left), Self::Struct(right)) => {
+                left.name == right.name
+                    && left.fields.len() == right.fields.len()
+                    && left.fields.iter().zip(right.fields.iter())
+                        .all(|((l_name, l), (r_name, r))| l_name == r_name && l.equals(r))
+            }
+            (Self::EnumDef(left), Self::EnumDef(right)) => left.name == right.name && left.builtin == right.builtin,
+            (Self::Generator(left), Self::Generator(right)) => left.is(right),
+            (Self::Channel(left), Self::Channel(right)) => left.is(right),
+            (Self::Module(left), Self::Module(right)) => left.is(right),
+            (Self::Quote(left), Self::Quote(right)) => left.node == right.node,
+            (Self::Variant(left), Self::Variant(right)) => {
+                left.enum_name == right.enum_name
+                    && left.builtin == right.builtin
+                    && left.name == right.name
+                    && match (&left.payload, &right.payload) {
+                        (Some(l), Some(r)) => l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| l.equals(r)),
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Integer {
     pub value: i64,
@@ -79,7 +191,7 @@ impl StringObj {
 
 impl ObjectTrait for StringObj {
     fn inspect(&self) -> String {
-        format!("{}", self.str)
+        self.str.clone()
     }
 }
 
@@ -94,7 +206,307 @@ impl Null {
 
 impl ObjectTrait for Null {
     fn inspect(&self) -> String {
-        format!("null")
+        "null".to_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArrayObj {
+    pub elements: Vec<Object>,
+}
+
+impl ArrayObj {
+    pub fn new(elements: Vec<Object>) -> ArrayObj {
+        ArrayObj { elements }
+    }
+}
+
+impl ObjectTrait for ArrayObj {
+    fn inspect(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|elem| elem.inspect()).collect();
+        format!("[{}]", elements.join(", "))
+    }
+}
+
+// Fixed number of values, such as multiple results of a function
+#[derive(Debug, Clone)]
+pub struct TupleObj {
+    pub elements: Vec<Object>,
+}
+
+impl TupleObj {
+    pub fn new(elements: Vec<Object>) -> TupleObj {
+        TupleObj { elements }
+    }
+}
+
+impl ObjectTrait for TupleObj {
+    fn inspect(&self) -> String {
+        tuple_string(self.elements.iter().map(|elem| elem.inspect()).collect())
+    }
+}
+
+// Objects which can be used as a key of hash
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+}
+
+impl HashKey {
+    pub fn from_object(obj: &Object) -> Option<HashKey> {
+        match obj {
+            Object::Int(int)    => Some(HashKey::Int(int.value)),
+            Object::Bool(bool)  => Some(HashKey::Bool(bool.value)),
+            Object::Str(string) => Some(HashKey::Str(string.str.clone())),
+            _ => None,
+        }
+    }
+}
+
+// Pairs are kept in insertion order so that inspect is deterministic.
+#[derive(Debug, Clone)]
+pub struct HashObj {
+    pub pairs: Vec<(Object, Object)>,
+    index: HashMap<HashKey, usize>,
+}
+
+impl HashObj {
+    pub fn new() -> HashObj {
+        HashObj { pairs: Vec::new(), index: HashMap::new() }
+    }
+
+    pub fn get(&self, key: &HashKey) -> Option<&Object> {
+        self.index.get(key).map(|i| &self.pairs[*i].1)
+    }
+
+    pub fn set(&mut self, key: HashKey, key_obj: Object, value: Object) {
+        match self.index.get(&key) {
+            Some(i) => self.pairs[*i].1 = value,
+            None => {
+                self.index.insert(key, self.pairs.len());
+                self.pairs.push((key_obj, value));
+            }
+        }
+    }
+}
+
+impl ObjectTrait for HashObj {
+    fn inspect(&self) -> String {
+        let pairs: Vec<String> = self.pairs
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.inspect(), value.inspect()))
+            .collect();
+        format!("{{{}}}", pairs.join(", "))
+    }
+}
+
+// Elements are usable as hash keys, and kept in insertion order so that inspect is deterministic.
+#[derive(Debug, Clone)]
+pub struct SetObj {
+    pub elements: Vec<Object>,
+    index: HashMap<HashKey, usize>,
+}
+
+impl SetObj {
+    pub fn new() -> SetObj {
+        SetObj { elements: Vec::new(), index: HashMap::new() }
+    }
+
+    // A set of the elements, or the first one unusable as a hash key
+    pub fn from_elements(elements: Vec<Object>) -> Result<SetObj, Object> {
+        let mut ret = SetObj::new();
+        for elem in elements.into_iter() {
+            match HashKey::from_object(&elem) {
+                Some(key) => ret.insert(key, elem),
+                None => return Err(elem),
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn contains(&self, elem: &Object) -> bool {
+        HashKey::from_object(elem).is_some_and(|key| self.index.contains_key(&key))
+    }
+
+    pub fn insert(&mut self, key: HashKey, elem: Object) {
+        if !self.index.contains_key(&key) {
+            self.index.insert(key, self.elements.len());
+            self.elements.push(elem);
+        }
+    }
+
+    pub fn union(&self, other: &SetObj) -> SetObj {
+        let mut ret = self.clone();
+        for elem in other.elements.iter() {
+            if let Some(key) = HashKey::from_object(elem) {
+                ret.insert(key, elem.clone());
+            }
+        }
+        ret
+    }
+
+    pub fn intersection(&self, other: &SetObj) -> SetObj {
+        self.filter(|elem| other.contains(elem))
+    }
+
+    pub fn difference(&self, other: &SetObj) -> SetObj {
+        self.filter(|elem| !other.contains(elem))
+    }
+
+    pub fn is_subset(&self, other: &SetObj) -> bool {
+        self.elements.iter().all(|elem| other.contains(elem))
+    }
+
+    fn filter(&self, pred: impl Fn(&Object) -> bool) -> SetObj {
+        let mut ret = SetObj::new();
+        for elem in self.elements.iter().filter(|elem| pred(elem)) {
+            if let Some(key) = HashKey::from_object(elem) {
+                ret.insert(key, elem.clone());
+            }
+        }
+        ret
+    }
+}
+
+impl ObjectTrait for SetObj {
+    fn inspect(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|elem| elem.inspect()).collect();
+        format!("#{{{}}}", elements.join(", "))
+    }
+}
+
+// Declared by 'struct Name { field, ... }'
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl StructDef {
+    pub fn new(name: String, fields: Vec<String>) -> StructDef {
+        StructDef { name, fields }
+    }
+}
+
+impl ObjectTrait for StructDef {
+    fn inspect(&self) -> String {
+        format!("struct {} {{ {} }}", self.name, self.fields.join(", "))
+    }
+}
+
+// Fields are kept in the order of the declaration.
+#[derive(Debug, Clone)]
+pub struct StructObj {
+    pub name: String,
+    pub fields: Vec<(String, Object)>,
+}
+
+impl StructObj {
+    pub fn new(name: String, fields: Vec<(String, Object)>) -> StructObj {
+        StructObj { name, fields }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Object> {
+        self.fields.iter().find(|(name, _)| name == field).map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, field: &str) -> Option<&mut Object> {
+        self.fields.iter_mut().find(|(name, _)| name == field).map(|(_, value)| value)
+    }
+}
+
+impl ObjectTrait for StructObj {
+    fn inspect(&self) -> String {
+        let fields: Vec<String> = self.fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value.inspect()))
+            .collect();
+        format!("{} {{ {} }}", self.name, fields.join(", "))
+    }
+}
+
+// Declared by 'enum Name { Variant, Variant(field, ...), ... }'
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    pub name: String,
+    pub variants: Vec<EnumVariant>,
+    pub builtin: bool, // Set for the builtin 'Result', which an enum of the same name can't pass for
+}
+
+impl EnumDef {
+    pub fn new(name: String, variants: Vec<EnumVariant>, builtin: bool) -> EnumDef {
+        EnumDef { name, variants, builtin }
+    }
+}
+
+impl ObjectTrait for EnumDef {
+    fn inspect(&self) -> String {
+        let variants: Vec<String> = self.variants.iter().map(|variant| variant.string()).collect();
+        format!("enum {} {{ {} }}", self.name, variants.join(", "))
+    }
+}
+
+// A value of an enum. The payload is None for unit variants.
+#[derive(Debug, Clone)]
+pub struct VariantObj {
+    pub enum_name: String,
+    pub name: String,
+    pub payload: Option<Vec<Object>>,
+    pub builtin: bool, // Same as the one of its enum
+}
+
+impl VariantObj {
+    pub fn new(enum_name: String, name: String, payload: Option<Vec<Object>>, builtin: bool) -> VariantObj {
+        VariantObj { enum_name, name, payload, builtin }
+    }
+
+    // 'Ok(value)' of the builtin 'enum Result { Ok(value), Err(error) }'
+    pub fn ok(value: Object) -> VariantObj {
+        VariantObj::new("Result".to_string(), "Ok".to_string(), Some(vec![value]), true)
+    }
+
+    // 'Err(error)' of the builtin 'enum Result { Ok(value), Err(error) }'
+    pub fn err(error: Object) -> VariantObj {
+        VariantObj::new("Result".to_string(), "Err".to_string(), Some(vec![error]), true)
+    }
+}
+
+impl ObjectTrait for VariantObj {
+    fn inspect(&self) -> String {
+        match self.payload {
+            Some(ref payload) => {
+                let payload: Vec<String> = payload.iter().map(|value| value.inspect()).collect();
+                format!("{}::{}({})", self.enum_name, self.name, payload.join(", "))
+            }
+            None => format!("{}::{}", self.enum_name, self.name),
+        }
+    }
+}
+
+// 'Enum::Variant' of a variant with payload, which constructs the variant when called
+#[derive(Debug, Clone)]
+pub struct VariantCtor {
+    pub enum_name: String,
+    pub name: String,
+    pub fields: Vec<String>,
+    pub builtin: bool, // Same as the one of its enum
+}
+
+impl VariantCtor {
+    pub fn new(enum_name: String, name: String, fields: Vec<String>, builtin: bool) -> VariantCtor {
+        VariantCtor { enum_name, name, fields, builtin }
+    }
+}
+
+impl ObjectTrait for VariantCtor {
+    fn inspect(&self) -> String {
+        format!("fn {}::{}({})", self.enum_name, self.name, self.fields.join(", "))
     }
 }
 
@@ -115,14 +527,22 @@ impl ObjectTrait for ReturnValue {
     }
 }
 
+// Object::Err propagates until caught by 'catch', which binds it as Object::Exception.
 #[derive(Debug, Clone)]
 pub struct ErrorObj {
     pub msg: String,
+    pub kind: String,               // 'RuntimeError' for errors raised by the interpreter
+    pub trace: Vec<String>,         // Functions the error propagated out of, innermost first
+    pub value: Option<Box<Object>>, // Set if raised by 'throw'
 }
 
 impl ErrorObj {
     pub fn new(msg: String) -> ErrorObj {
-        ErrorObj { msg }
+        ErrorObj { msg, kind: "RuntimeError".to_string(), trace: Vec::new(), value: None }
+    }
+
+    pub fn new_thrown(kind: String, msg: String, value: Object) -> ErrorObj {
+        ErrorObj { msg, kind, trace: Vec::new(), value: Some(Box::new(value)) }
     }
 }
 
@@ -134,42 +554,103 @@ impl ObjectTrait for ErrorObj {
 
 #[derive(Debug, Clone)]
 pub struct Function {
-    pub params: Vec<Identifier>,
+    pub name: Option<String>, // Set if declared by 'fn name(...) { ... }'
+    pub params: Vec<Parameter>,
+    pub rest: Option<Identifier>,
     pub body: BlkStatement,
     pub env:  Rc<RefCell<Env>>,
+    pub generator: bool, // Set if calling it returns a generator
 }
 
 impl Function {
-    pub fn new(params: Vec<Identifier>, body: BlkStatement, env: Rc<RefCell<Env>>) -> Function {
-        Function { params, body, env }
+    pub fn new(
+        name: Option<String>,
+        params: Vec<Parameter>,
+        rest: Option<Identifier>,
+        body: BlkStatement,
+        env: Rc<RefCell<Env>>,
+        generator: bool,
+    ) -> Function {
+        Function { name, params, rest, body, env, generator }
+    }
+
+    // Such as 'fn(x, y = 10, ...rest)' or 'fn name(x)'
+    pub fn signature(&self) -> String {
+        match self.name {
+            Some(ref name) => format!("fn {}({})", name, params_string(&self.params, &self.rest)),
+            None => format!("fn({})", params_string(&self.params, &self.rest)),
+        }
     }
 }
 
 impl ObjectTrait for Function {
     fn inspect(&self) -> String {
-        let mut ret = String::new();
-        ret.push_str("fn(");
-        for ident in self.params.iter() {
-            ret.push_str(ident.string().as_str());
-            ret.push_str(", ")
-        }
-        ret.pop();
-        ret.pop();
-        ret.push_str("){\n");
-        ret.push_str(format!("    {}", self.body.string()).as_str());
-        ret.push_str("\n}");
-        ret
+        format!("{}{{\n    {}\n}}", self.signature(), self.body.string())
     }
 }
 
+// Function made by 'first >> second' or 'second << first', which passes the arguments to
+// 'first' and its result to 'second'.
+#[derive(Debug, Clone)]
+pub struct Composition {
+    pub first:  Box<Object>,
+    pub second: Box<Object>,
+}
+
+impl Composition {
+    pub fn new(first: Object, second: Object) -> Composition {
+        Composition { first: Box::new(first), second: Box::new(second) }
+    }
+}
+
+impl ObjectTrait for Composition {
+    fn inspect(&self) -> String {
+        format!("({} >> {})", self.first.inspect(), self.second.inspect())
+    }
+}
+
+// An unevaluated expression made by 'quote'
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub node: Expression,
+}
+
+impl Quote {
+    pub fn new(node: Expression) -> Quote {
+        Quote { node }
+    }
+}
+
+impl ObjectTrait for Quote {
+    fn inspect(&self) -> String {
+        format!("QUOTE({})", self.node.string())
+    }
+}
+
+// Builtin functions which need the evaluator, such as ones switching tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Native {
+    Next,
+    Spawn,
+    Channel,
+    Send,
+    Recv,
+    Select,
+}
+
 #[derive(Debug, Clone)]
 pub struct Buildin {
     pub func: fn(Vec<Object>) -> Object,
+    pub display: bool, // Set if arguments are converted to strings by 'to_string' hooks
 }
 
 impl Buildin {
     pub fn new(func: fn(Vec<Object>) -> Object) -> Buildin {
-        Buildin { func }
+        Buildin { func, display: false }
+    }
+
+    pub fn new_with_display(func: fn(Vec<Object>) -> Object) -> Buildin {
+        Buildin { func, display: true }
     }
 
     pub fn print(args: Vec<Object>) -> Object {
@@ -179,9 +660,106 @@ impl Buildin {
         Object::Null(Null::new())
     }
 
+    pub fn len(args: Vec<Object>) -> Object {
+        if args.len() != 1 {
+            return Object::Err(ErrorObj::new("Number of argument is not 1".to_string()))
+        }
+
+        let len = match args[0] {
+            Object::Str(ref string) => string.str.chars().count(),
+            Object::Array(ref array) => array.elements.len(),
+            Object::Tuple(ref tuple) => tuple.elements.len(),
+            Object::Set(ref set) => set.len(),
+            Object::Hash(ref hash) => hash.pairs.len(),
+            ref obj => return Object::Err(ErrorObj::new(format!("{} has no length", obj.inspect()))),
+        };
+        match i64::try_from(len) {
+            Ok(len)  => Object::Int(Integer::new(len)),
+            Err(err) => Object::Err(ErrorObj::new(err.to_string())),
+        }
+    }
+
+    pub fn push(args: Vec<Object>) -> Object {
+        if args.len() != 2 {
+            return Object::Err(ErrorObj::new("Number of argument is not 2".to_string()))
+        }
+
+        match args[0] {
+            Object::Array(ref array) => {
+                let mut elements = array.elements.clone();
+                elements.push(args[1].clone());
+                Object::Array(ArrayObj::new(elements))
+            }
+            Object::Set(ref set) => match HashKey::from_object(&args[1]) {
+                Some(key) => {
+                    let mut set = set.clone();
+                    set.insert(key, args[1].clone());
+                    Object::Set(set)
+                }
+                None => Object::Err(ErrorObj::new(format!("Unusable as set element: {}", args[1].inspect()))),
+            }
+            _ => Object::Err(ErrorObj::new("This object is not array".to_string())),
+        }
+    }
+
+    // 'set(elements)' makes a set of the elements of an array, a tuple or a set
+    pub fn set(args: Vec<Object>) -> Object {
+        let elements = match args.as_slice() {
+            [Object::Array(ArrayObj { elements }) | Object::Tuple(TupleObj { elements })] => elements.clone(),
+            [Object::Set(set)] => return Object::Set(set.clone()),
+            _ => return Object::Err(ErrorObj::new("set expects an array, a tuple or a set".to_string())),
+        };
+        match SetObj::from_elements(elements) {
+            Ok(set) => Object::Set(set),
+            Err(elem) => Object::Err(ErrorObj::new(format!("Unusable as set element: {}", elem.inspect()))),
+        }
+    }
+
+    // 'union(a, b)', 'intersection(a, b)' and 'difference(a, b)' of two sets
+    pub fn union(args: Vec<Object>) -> Object {
+        Buildin::set_operation("union", args, |left, right| Object::Set(left.union(right)))
+    }
+
+    pub fn intersection(args: Vec<Object>) -> Object {
+        Buildin::set_operation("intersection", args, |left, right| Object::Set(left.intersection(right)))
+    }
+
+    pub fn difference(args: Vec<Object>) -> Object {
+        Buildin::set_operation("difference", args, |left, right| Object::Set(left.difference(right)))
+    }
+
+    // 'is_subset(a, b)' checks every element of a is in b
+    pub fn is_subset(args: Vec<Object>) -> Object {
+        Buildin::set_operation("is_subset", args, |left, right| Object::Bool(Boolean::new(left.is_subset(right))))
+    }
+
+    fn set_operation(name: &str, args: Vec<Object>, op: impl Fn(&SetObj, &SetObj) -> Object) -> Object {
+        match args.as_slice() {
+            [Object::Set(left), Object::Set(right)] => op(left, right),
+            _ => Object::Err(ErrorObj::new(format!("{} expects two sets", name))),
+        }
+    }
+
+    pub fn read_file(args: Vec<Object>) -> Object {
+        if args.len() != 1 {
+            return Object::Err(ErrorObj::new("Number of argument is not 1".to_string()))
+        }
+
+        match args[0] {
+            Object::Str(ref path) => match fs::read_to_string(&path.str) {
+                Ok(contents) => Object::Variant(VariantObj::ok(Object::Str(StringObj::new(contents)))),
+                Err(err) => {
+                    let msg = format!("Failed to read {}: {}", path.str, err);
+                    Object::Variant(VariantObj::err(Object::Str(StringObj::new(msg))))
+                }
+            }
+            _ => Object::Err(ErrorObj::new("This object is not string".to_string())),
+        }
+    }
+
     pub fn exit(args: Vec<Object>) -> Object {
         if args.len() != 1 {
-            return Object::Err(ErrorObj::new(format!("Number of argument is not 1")))
+            return Object::Err(ErrorObj::new("Number of argument is not 1".to_string()))
         }
 
         match args[0] {
@@ -191,7 +769,7 @@ impl Buildin {
                     Err(err)  => Object::Err(ErrorObj::new(err.to_string())),
                 }
             }
-            _ => Object::Err(ErrorObj::new(format!("This object is not int"))),
+            _ => Object::Err(ErrorObj::new("This object is not int".to_string())),
         }
     }
 }
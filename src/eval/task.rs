@@ -0,0 +1,319 @@
+use crate::ast::{Expression, Pattern};
+use super::{
+    Eval,
+    generator::{Frame, Step},
+    object::{Object, ObjectTrait, ErrorObj, ArrayObj, Integer, Null, Native},
+};
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+// Tasks are spawned functions run by a cooperative scheduler on the same thread. A task runs on
+// frames like a generator, and it's switched when it waits for a channel or yields. Channel
+// operations can wait only as statements of the task's own body, that is 'ch.send(v);',
+// 'let x = ch.recv();' or 'x = ch.recv();' in blocks, 'if' and 'for'. The main program isn't a
+// task, so it runs the other tasks whenever it waits.
+#[derive(Debug)]
+pub struct Task {
+    id: usize,
+    signature: String,
+    frames: Vec<Frame>,
+    wait: Option<(Wait, Resumption)>,
+}
+
+// A channel passes values between tasks in order. Sending waits until the value is received,
+// or until it fits in the buffer if the channel has capacity.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    id: usize,
+    state: Rc<RefCell<ChannelState>>,
+}
+
+#[derive(Debug)]
+struct ChannelState {
+    queue: VecDeque<Object>,
+    capacity: usize,
+    sent: usize,  // Number of values ever sent
+    taken: usize, // Number of values ever received
+}
+
+// What a suspended channel operation waits for
+#[derive(Debug)]
+pub enum Wait {
+    Sent(Channel, usize), // Until the n-th value of the channel is received or buffered
+    Recv(Channel),
+    Select(Vec<Channel>),
+}
+
+// How the result of the operation is used when a task resumes
+#[derive(Debug)]
+pub enum Resumption {
+    Discard,
+    Let(Pattern, bool),
+    Assign(Expression),
+}
+
+// A channel operation which has finished or has to wait
+pub enum Started {
+    Done(Object),
+    Wait(Wait),
+}
+
+impl Channel {
+    pub fn is(&self, other: &Channel) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+
+    fn take(&self) -> Option<Object> {
+        let mut state = self.state.borrow_mut();
+        let value = state.queue.pop_front()?;
+        state.taken += 1;
+        Some(value)
+    }
+}
+
+impl ObjectTrait for Channel {
+    fn inspect(&self) -> String {
+        format!("<channel {}>", self.id)
+    }
+}
+
+impl Wait {
+    fn ready(&self) -> bool {
+        match self {
+            Wait::Sent(ch, n) => {
+                let state = ch.state.borrow();
+                state.taken + state.capacity >= *n
+            }
+            Wait::Recv(ch) => !ch.state.borrow().queue.is_empty(),
+            Wait::Select(chs) => chs.iter().any(|ch| !ch.state.borrow().queue.is_empty()),
+        }
+    }
+
+    // Result of the operation, which must be ready. Select gives the index of the channel and
+    // the received value.
+    fn complete(&self) -> Object {
+        match self {
+            Wait::Sent(..) => Object::Null(Null::new()),
+            Wait::Recv(ch) => ch.take().unwrap_or(Object::Null(Null::new())),
+            Wait::Select(chs) => {
+                for (i, ch) in chs.iter().enumerate() {
+                    if let Some(value) = ch.take() {
+                        return Object::Array(ArrayObj::new(vec![Object::Int(Integer::new(i as i64)), value]));
+                    }
+                }
+                Object::Null(Null::new())
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Wait::Sent(ch, _) => format!("send to channel {}", ch.id),
+            Wait::Recv(ch) => format!("recv from channel {}", ch.id),
+            Wait::Select(chs) => {
+                let ids: Vec<String> = chs.iter().map(|ch| ch.id.to_string()).collect();
+                format!("select on channels {}", ids.join(", "))
+            }
+        }
+    }
+}
+
+impl Eval {
+    pub(super) fn native(&mut self, native: Native, args: Vec<Object>) -> Object {
+        match native {
+            Native::Next => match args.as_slice() {
+                [Object::Generator(gen)] => self.next_result(gen),
+                [Object::Generator(_), _, ..] => {
+                    Object::Err(ErrorObj::new("Generator::next takes no arguments".to_string()))
+                }
+                _ => Object::Err(ErrorObj::new("next expects a generator".to_string())),
+            },
+            Native::Spawn => self.spawn(args),
+            Native::Channel => self.channel(args),
+            Native::Send | Native::Recv | Native::Select => match self.start_op(native, args) {
+                Ok(Started::Done(value)) => value,
+                Ok(Started::Wait(wait)) => self.wait(wait),
+                Err(err) => err,
+            },
+        }
+    }
+
+    // 'spawn(f, args...)' schedules 'f(args...)' as a new task and gives its id
+    fn spawn(&mut self, mut args: Vec<Object>) -> Object {
+        let mut func = match args.is_empty() {
+            false => match args.remove(0) {
+                Object::Func(func) => func,
+                obj => return Object::Err(ErrorObj::new(format!("spawn expects a function, got {}", obj.inspect()))),
+            },
+            true => return Object::Err(ErrorObj::new("spawn expects a function".to_string())),
+        };
+
+        // The body is run on frames by calling the function as a generator
+        let signature = func.signature();
+        func.generator = true;
+        let gen = match self.apply(Object::Func(func), args, Vec::new()) {
+            Object::Generator(gen) => gen,
+            err => return err,
+        };
+
+        self.task_ids += 1;
+        let frames = gen.take_frames();
+        self.tasks.push_back(Task { id: self.task_ids, signature, frames, wait: None });
+        Object::Int(Integer::new(self.task_ids as i64))
+    }
+
+    // 'channel()' is unbuffered, and 'channel(n)' buffers up to n values
+    fn channel(&mut self, args: Vec<Object>) -> Object {
+        let capacity = match args.as_slice() {
+            [] => 0,
+            [Object::Int(n)] if n.value >= 0 => n.value as usize,
+            _ => return Object::Err(ErrorObj::new("channel expects a non-negative capacity".to_string())),
+        };
+
+        self.channel_ids += 1;
+        let state = ChannelState { queue: VecDeque::new(), capacity, sent: 0, taken: 0 };
+        Object::Channel(Channel { id: self.channel_ids, state: Rc::new(RefCell::new(state)) })
+    }
+
+    // Start 'send(ch, value)', 'recv(ch)' or 'select(ch...)'
+    pub(super) fn start_op(&mut self, op: Native, args: Vec<Object>) -> Result<Started, Object> {
+        let wait = match (op, args.as_slice()) {
+            (Native::Send, [Object::Channel(ch), value]) => {
+                let mut state = ch.state.borrow_mut();
+                state.queue.push_back(value.clone());
+                state.sent += 1;
+                Wait::Sent(ch.clone(), state.sent)
+            }
+            (Native::Recv, [Object::Channel(ch)]) => Wait::Recv(ch.clone()),
+            (Native::Select, chs) if !chs.is_empty() => {
+                let mut ret = Vec::new();
+                for ch in chs.iter() {
+                    match ch {
+                        Object::Channel(ch) => ret.push(ch.clone()),
+                        obj => {
+                            return Err(Object::Err(ErrorObj::new(format!("{} is not a channel", obj.inspect()))));
+                        }
+                    }
+                }
+                Wait::Select(ret)
+            }
+            (Native::Send, _) => return Err(Object::Err(ErrorObj::new("send expects a channel and a value".to_string()))),
+            (Native::Recv, _) => return Err(Object::Err(ErrorObj::new("recv expects a channel".to_string()))),
+            _ => return Err(Object::Err(ErrorObj::new("select expects one or more channels".to_string()))),
+        };
+
+        match wait.ready() {
+            true  => Ok(Started::Done(wait.complete())),
+            false => Ok(Started::Wait(wait)),
+        }
+    }
+
+    // Wait for the operation outside of the statements of a task. The main program runs the
+    // other tasks until it's ready, but a task can't be suspended here.
+    fn wait(&mut self, wait: Wait) -> Object {
+        if let Some(id) = self.current_task {
+            return Object::Err(
+                ErrorObj::new(
+                    format!("Task {} can't wait to {} here, only as a statement of its body", id, wait.describe())
+                )
+            );
+        }
+
+        while !wait.ready() {
+            match self.run_next_task() {
+                Ok(true) => {}
+                Ok(false) => return self.deadlock(Some(&wait)),
+                Err(err) => return err,
+            }
+        }
+        wait.complete()
+    }
+
+    // Run the tasks until all of them have finished or are stuck
+    pub(super) fn run_tasks(&mut self) -> Object {
+        loop {
+            match self.run_next_task() {
+                Ok(true) => {}
+                Ok(false) if self.tasks.is_empty() => return Object::Null(Null::new()),
+                Ok(false) => return self.deadlock(None),
+                Err(err) => return err,
+            }
+        }
+    }
+
+    // Run the first task which can proceed until it waits, yields or finishes. False is returned
+    // if there is no such task, and the error is given if the task failed.
+    fn run_next_task(&mut self) -> Result<bool, Object> {
+        let pos = self.tasks.iter().position(|task| task.wait.as_ref().is_none_or(|(wait, _)| wait.ready()));
+        let mut task = match pos.and_then(|pos| self.tasks.remove(pos)) {
+            Some(task) => task,
+            None => return Ok(false),
+        };
+
+        let prev = self.current_task.replace(task.id);
+        let curr_env = Rc::clone(&self.env);
+        let step = self.run_task(&mut task);
+        self.env = curr_env;
+        self.current_task = prev;
+
+        match step {
+            Step::Yield(_) => self.tasks.push_back(task),
+            Step::Wait(wait, then) => {
+                task.wait = Some((wait, then));
+                self.tasks.push_back(task);
+            }
+            Step::Done(None) => {}
+            // The program fails with the task, so the other tasks are dropped as well
+            Step::Done(Some(Object::Err(mut err))) => {
+                self.tasks.clear();
+                err.trace.push(format!("task {}", task.id));
+                return Err(Object::Err(err));
+            }
+            Step::Done(Some(obj)) => {
+                self.tasks.clear();
+                return Err(obj);
+            }
+        }
+        Ok(true)
+    }
+
+    fn run_task(&mut self, task: &mut Task) -> Step {
+        if let Some((wait, then)) = task.wait.take() {
+            if let Some(frame) = task.frames.last() {
+                self.env = Rc::clone(&frame.env);
+            }
+            let ret = self.resume_with(then, wait.complete());
+            if self.is_abrupt(&ret) {
+                return Step::Done(self.unwind(&mut task.frames, ret));
+            }
+        }
+        self.run_frames(&mut task.frames, true)
+    }
+
+    // Use the result of a channel operation or a call as the statement it was started by
+    pub(super) fn resume_with(&mut self, then: Resumption, value: Object) -> Object {
+        if self.is_abrupt(&value) {
+            return value;
+        }
+        match then {
+            Resumption::Discard => value,
+            Resumption::Let(pattern, mutable) => self.define_pattern(&pattern, value, mutable),
+            Resumption::Assign(target) => self.assign(target, value),
+        }
+    }
+
+    // Report the waiting tasks, which are dropped since they can never proceed
+    fn deadlock(&mut self, main: Option<&Wait>) -> Object {
+        let mut stuck = Vec::new();
+        if let Some(wait) = main {
+            stuck.push(format!("main waits to {}", wait.describe()));
+        }
+        for task in self.tasks.drain(..) {
+            let wait = match task.wait {
+                Some((ref wait, _)) => wait.describe(),
+                None => "run".to_string(),
+            };
+            stuck.push(format!("task {} {} waits to {}", task.id, task.signature, wait));
+        }
+        Object::Err(ErrorObj::new(format!("Deadlock: {}", stuck.join(", "))))
+    }
+}
@@ -0,0 +1,1258 @@
+#![cfg(test)]
+
+use crate::{
+    lexer::Lexer,
+    parser::Parser,
+    eval::{Eval, STACK_SIZE, env::Env, object::{Object, ObjectTrait}},
+};
+use std::{cell::RefCell, fs, path::PathBuf, rc::Rc, thread, panic};
+
+#[test]
+fn test_integer_arithmetic() {
+    let tests = vec![
+        ("5 + 5 * 2;", "15"),
+        ("(5 + 5) * 2;", "20"),
+        ("10 / 3;", "3"),
+        ("-7;", "-7"),
+        ("let a = 4; a++;", "5"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+}
+
+#[test]
+fn test_arithmetic_errors() {
+    let tests = vec![
+        "1 / 0;",
+        "let min = -9223372036854775807 - 1; min / -1;",
+        "let min = -9223372036854775807 - 1; -min;",
+        "9223372036854775807 + 1;",
+        "9223372036854775807 * 2;",
+        "9223372036854775807++;",
+    ];
+    for input in tests {
+        test_error(eval_input(input));
+    }
+}
+
+#[test]
+fn test_recursion_limit() {
+    run_with_stack(|| {
+        let input = "
+            let f = fn(n) { if (n < 1) { 0 } else { f(n - 1) } };
+            f(100000);";
+        test_object(eval_input(input), "An error happen: Maximum call depth exceeded: 3072");
+
+        // Calls are limited apart from expressions, so plain recursion goes a few thousand deep
+        let input = "
+            let f = fn(n) { if (n < 1) { 0 } else { f(n - 1) } };
+            f(3000);";
+        test_object(eval_input(input), "0");
+
+        let input = format!(
+            "let sum = fn(xs, i) {{ if (i == len(xs)) {{ 0 }} else {{ xs[i] + sum(xs, i + 1) }} }}; sum([{}1], 0);",
+            "1, ".repeat(1999)
+        );
+        test_object(eval_input(&input), "2000");
+
+        let input = "let m = macro() { quote(m()) }; m();";
+        test_object(eval_input(input), "An error happen: Macro expansion is nested deeper than 16384");
+
+        // Expanding macros walks the whole program, which is bounded as well
+        let input = format!("let m = macro() {{ quote(1) }}; 1{};", " + 1".repeat(200000));
+        test_object(eval_input(&input), "An error happen: Expression is nested deeper than 16384");
+
+        // The expression quoted by a macro is renamed and spliced by walks bounded as well
+        let input = format!("let m = macro() {{ quote(1{}) }}; m();", " + 1".repeat(100));
+        let lexer   = Lexer::new(&input);
+        let parser  = Parser::new_with_limit(lexer.tokenize(), usize::MAX);
+        let program = parser.parse().unwrap_or_else(|err| panic!("{}", err));
+        let mut eval = Eval::new_with_limit(Rc::new(RefCell::new(Env::new())), 64);
+        match eval.expand_macros(program) {
+            Ok(_) => panic!("The quoted expression is nested deeper than 64"),
+            Err(err) => test_object(Ok(Object::Err(err)), "An error happen: Expression is nested deeper than 64"),
+        }
+
+        // Nor can a pattern nested deeper than the evaluator allows
+        let input = format!(
+            "let mut v = 1; for (_ in [{}0]) {{ v = [v]; }} let {}a{} = v;",
+            "0, ".repeat(1100), "[".repeat(1100), "]".repeat(1100)
+        );
+        let lexer   = Lexer::new(&input);
+        let parser  = Parser::new_with_limit(lexer.tokenize(), usize::MAX);
+        let program = parser.parse().unwrap_or_else(|err| panic!("{}", err));
+        let mut eval = Eval::new_with_limit(Rc::new(RefCell::new(Env::new())), 1024);
+        test_object(Ok(eval.eval(program)), "An error happen: Evaluation is nested deeper than 1024");
+
+        // A chain longer than the parser allows still can't overflow the evaluator
+        let input = format!("let a = [1]; a{};", "[0]".repeat(200000));
+        let lexer   = Lexer::new(&input);
+        let parser  = Parser::new_with_limit(lexer.tokenize(), usize::MAX);
+        let program = parser.parse().unwrap_or_else(|err| panic!("{}", err));
+        let mut eval = Eval::new(Rc::new(RefCell::new(Env::new())));
+        test_object(Ok(eval.eval(program)), "An error happen: Evaluation is nested deeper than 16384");
+    });
+}
+
+#[test]
+fn test_configurable_recursion_limit() {
+    let input = "
+        let f = fn(n) { if (n < 1) { 0 } else { f(n - 1) } };
+        f(10);";
+    let lexer   = Lexer::new(input);
+    let parser  = Parser::new(lexer.tokenize());
+    let program = parser.parse().unwrap_or_else(|err| panic!("{}", err));
+    let mut eval = Eval::new_with_limit(Rc::new(RefCell::new(Env::new())), 5);
+    test_error(Ok(eval.eval(program)));
+}
+
+#[test]
+fn test_nesting_limit() {
+    let input = format!("{}1{};", "(".repeat(100000), ")".repeat(100000));
+    let lexer  = Lexer::new(&input);
+    let parser = Parser::new(lexer.tokenize());
+    assert!(parser.parse().is_err(), "Deeply nested input should be rejected");
+
+    let input = format!("{}1;", "!".repeat(100000));
+    let lexer  = Lexer::new(&input);
+    let parser = Parser::new(lexer.tokenize());
+    assert!(parser.parse().is_err(), "Deeply nested input should be rejected");
+
+    let input = format!("let {}a{} = 1;", "[".repeat(100000), "]".repeat(100000));
+    let lexer  = Lexer::new(&input);
+    let parser = Parser::new(lexer.tokenize());
+    assert!(parser.parse().is_err(), "Deeply nested pattern should be rejected");
+
+    for link in ["()", "[0]", ".x", "?.()", "++"] {
+        let input = format!("f{};", link.repeat(100000));
+        let lexer  = Lexer::new(&input);
+        let parser = Parser::new(lexer.tokenize());
+        match parser.parse() {
+            Ok(_) => panic!("A chain of {} should be rejected", link),
+            Err(err) => assert_eq!(err.to_string(), "Expression is nested deeper than 128"),
+        }
+    }
+
+    let input = format!("{}1{};", "(".repeat(5), ")".repeat(5));
+    let lexer  = Lexer::new(&input);
+    let parser = Parser::new_with_limit(lexer.tokenize(), 3);
+    assert!(parser.parse().is_err(), "Nesting deeper than the limit should be rejected");
+}
+
+#[test]
+fn test_null() {
+    let tests = vec![
+        ("null;", "null"),
+        ("!null;", "true"),
+        ("null == null;", "true"),
+        ("1 != null;", "true"),
+        ("null ?? 5;", "5"),
+        ("3 ?? 5;", "3"),
+        ("let f = fn() { 1 / 0 }; 3 ?? f();", "3"),
+        ("let a = null; a?.[0];", "null"),
+        ("let a = null; a?.[0][1];", "null"),
+        ("let f = null; f?.(1 / 0);", "null"),
+        ("let f = fn(x) { x * 2 }; f?.(4);", "8"),
+        ("let h = {\"name\": \"monkey\"}; h[\"age\"] ?? 0;", "0"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    test_error(eval_input("null[0];"));
+    test_error(eval_input("let f = null; f(1);"));
+}
+
+#[test]
+fn test_collections() {
+    let tests = vec![
+        ("[1, 2 + 3][1];", "5"),
+        ("[1, 2][2];", "null"),
+        ("{\"a\": 1, true: 2, 3: [4]};", "{a: 1, true: 2, 3: [4]}"),
+        ("{\"a\": 1}[\"a\"];", "1"),
+        ("let fs = [fn(x) { x + 1 }]; fs[0](1);", "2"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    test_error(eval_input("{[1]: 2};"));
+    test_error(eval_input("{1: 2}[[1]];"));
+}
+
+#[test]
+fn test_interpolated_string() {
+    let tests = vec![
+        ("let name = \"monkey\"; let age = 3; \"Hello, ${name}! You are ${age + 1}\";", "Hello, monkey! You are 4"),
+        ("\"${[1, 2]} and ${null} and ${true}\";", "[1, 2] and null and true"),
+        ("\"outer ${\"inner ${1 + 1}\"}\";", "outer inner 2"),
+        ("\"cost: $5\";", "cost: $5"),
+        ("let x = 1; \"$${x} is ${x}\";", "${x} is 1"),
+        ("\"$$${x}\";", "$${x}"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    test_error(eval_input("\"${1 / 0}\";"));
+    test_error(eval_input("\"${}\";"));
+}
+
+#[test]
+fn test_match_expression() {
+    let classify = "
+        let classify = fn(x) {
+            match (x) {
+                0 => \"zero\",
+                1 | 2 => \"small\",
+                3..10 => \"medium\",
+                -9..=-1 => \"negative\",
+                \"hi\" => \"greeting\",
+                null => \"nothing\",
+                true | false => \"boolean\",
+                n if n > 100 => \"big ${n}\",
+            }
+        };";
+    let tests = vec![
+        ("classify(0);", "zero"),
+        ("classify(2);", "small"),
+        ("classify(9);", "medium"),
+        ("classify(-9);", "negative"),
+        ("classify(\"hi\");", "greeting"),
+        ("classify(null);", "nothing"),
+        ("classify(101);", "big 101"),
+        ("classify(false);", "boolean"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", classify, input)), expect);
+    }
+
+    test_error(eval_input(&format!("{} classify(10);", classify)));
+    test_error(eval_input(&format!("{} classify(50);", classify)));
+    test_error(eval_input("match (1) { n if n / 0 => 1, _ => 2 };"));
+
+    let input = "let n = 1; match (2) { n => n + 1 }; n;";
+    test_object(eval_input(input), "1");
+}
+
+#[test]
+fn test_destructuring() {
+    let tests = vec![
+        ("let [a, b, ...rest] = [1, 2, 3, 4]; [a, b, rest];", "[1, 2, [3, 4]]"),
+        ("let [a, ...rest] = [1]; rest;", "[]"),
+        ("let [_, [x, y]] = [1, [2, 3]]; x + y;", "5"),
+        ("let {name, age} = {\"name\": \"monkey\", \"age\": 3}; \"${name} ${age}\";", "monkey 3"),
+        ("let {a: [x], ...rest} = {\"a\": [1], \"b\": 2, 3: 4}; [x, rest];", "[1, {b: 2, 3: 4}]"),
+        ("let f = fn({name, age}) { age + 1 }; f({\"age\": 3, \"name\": \"x\"});", "4"),
+        ("let f = fn(x, [y, ...ys]) { x + y + ys[0] }; f(1, [2, 3]);", "6"),
+        ("match ([1, 2]) { [] => 0, [x] => 1, [x, y] => x + y };", "3"),
+        ("match ({\"kind\": \"circle\", \"r\": 2}) { {kind: \"square\"} => 1, {kind: \"circle\", r} => r };", "2"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    let tests = vec![
+        ("let [a, b] = [1];", "Expected exactly 2 elements to destructure with [a, b], got [1]"),
+        ("let [a, ...b] = [];", "Expected at least 1 element to destructure with [a, ...b], got []"),
+        ("let [a] = 5;", "Expected an array to destructure with [a], got 5"),
+        ("let {name} = [1];", "Expected a hash to destructure with {name}, got [1]"),
+        ("let {name, age} = {\"name\": 1};", "Key age not found to destructure with {name, age}"),
+        ("let f = fn([x]) { x }; f(1);", "Expected an array to destructure with [x], got 1"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), &format!("An error happen: {}", expect));
+    }
+}
+
+#[test]
+fn test_function_parameters() {
+    let f = "let f = fn(x, y = x * 2, ...rest) { [x, y, rest] };";
+    let tests = vec![
+        ("f(1);", "[1, 2, []]"),
+        ("f(1, 5);", "[1, 5, []]"),
+        ("f(1, 5, 6, 7);", "[1, 5, [6, 7]]"),
+        ("f(y: 3, x: 1);", "[1, 3, []]"),
+        ("f(...[1, 2, 3]);", "[1, 2, [3]]"),
+        ("f(...{\"x\": 4});", "[4, 8, []]"),
+        ("f(0, ...[1, 2], ...[3]);", "[0, 1, [2, 3]]"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", f, input)), expect);
+    }
+
+    let tests = vec![
+        ("f();", "Missing argument for parameter x of fn(x, y = (x * 2), ...rest)"),
+        ("f(1, x: 2);", "Multiple values for parameter x of fn(x, y = (x * 2), ...rest)"),
+        ("f(z: 2);", "Unknown keyword argument z to fn(x, y = (x * 2), ...rest)"),
+        ("f(...5);", "You can't spread 5 into arguments"),
+        ("let g = fn(a, b = 1) { a }; g(1, 2, 3);", "Wrong number of arguments to fn(a, b = 1): expect 1 to 2, got 3"),
+        ("let g = fn(a) { a }; g(1, 2);", "Wrong number of arguments to fn(a): expect 1, got 2"),
+        ("puts(x: 1);", "Buildin Function doesn't take keyword arguments"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", f, input)), &format!("An error happen: {}", expect));
+    }
+}
+
+#[test]
+fn test_function_declaration() {
+    let tests = vec![
+        ("let y = double(4); fn double(x) { x * 2 } y;", "8"),
+        ("
+            fn is_even(n) { if (n < 1) { true } else { is_odd(n - 1) } }
+            fn is_odd(n) { if (n < 1) { false } else { is_even(n - 1) } }
+            is_even(10);", "true"),
+        ("
+            let check = fn(n) {
+                fn even(n) { if (n < 1) { true } else { odd(n - 1) } }
+                fn odd(n) { if (n < 1) { false } else { even(n - 1) } }
+                odd(n)
+            };
+            check(7);", "true"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    test_object(
+        eval_input("fn add(a, b) { a + b } add(1, 2, 3);"),
+        "An error happen: Wrong number of arguments to fn add(a, b): expect 2, got 3",
+    );
+    test_error(eval_input("let f = fn() { fn inner() { 1 } inner() }; f(); inner();"));
+}
+
+#[test]
+fn test_pipeline_and_composition() {
+    let fns = "
+        let double = fn(x) { x * 2 };
+        let inc = fn(x) { x + 1 };
+        let sub = fn(a, b) { a - b };";
+    let tests = vec![
+        ("3 |> double |> inc;", "7"),
+        ("10 |> sub(3);", "7"),
+        ("10 |> sub(3, _);", "-7"),
+        ("3 |> fn(x) { x * x };", "9"),
+        ("let f = double >> inc; f(3);", "7"),
+        ("let f = double << inc; f(3);", "8"),
+        ("let f = sub >> double; f(5, 2);", "6"),
+        ("3 |> double >> inc >> double;", "14"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", fns, input)), expect);
+    }
+
+    test_error(eval_input(&format!("{} double >> 1;", fns)));
+    test_error(eval_input(&format!("{} double(_);", fns)));
+    test_error(eval_input(&format!("{} 3 |> 4;", fns)));
+}
+
+#[test]
+fn test_method_call() {
+    let tests = vec![
+        ("\"abc\".len();", "3"),
+        ("\"日本語\".len();", "3"),
+        ("[1, 2, 3].push(4);", "[1, 2, 3, 4]"),
+        ("[1, 2].push(3).len();", "3"),
+        ("{\"a\": 1}.len();", "1"),
+        ("let greet = fn(name, greeting = \"Hello\") { \"${greeting}, ${name}\" }; \"monkey\".greet();", "Hello, monkey"),
+        ("fn sub(a, b) { a - b } 10.sub(3);", "7"),
+        ("let user = null; user?.len();", "null"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    test_object(eval_input("1.nothing();"), "An error happen: Method nothing not found for 1");
+    test_error(eval_input("let x = 1; 2.x();"));
+    test_error(eval_input("5.len();"));
+}
+
+#[test]
+fn test_assignment() {
+    let tests = vec![
+        ("let mut x = 1; x = x + 1; x;", "2"),
+        ("let mut x = 1; let f = fn() { x = x + 1 }; f(); f(); x;", "3"),
+        ("let mut a = 1; let mut b = 2; a = b = 5; a + b;", "10"),
+        ("let mut xs = [1, 2]; xs[1] = 5; xs;", "[1, 5]"),
+        ("let xs = [[1], [2]]; let mut ys = xs; ys[0][0] = 9; [xs, ys];", "[[[1], [2]], [[9], [2]]]"),
+        ("let mut h = {\"a\": 1}; h[\"b\"] = 2; h;", "{a: 1, b: 2}"),
+        ("let mut n = 0; let i = fn() { n = n + 1; 0 }; let mut a = [[1]]; a[i()][i()] = 2; [a, n];", "[[[2]], 2]"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    let errors = vec![
+        ("y = 1;", "Identifier not found: y"),
+        ("let mut xs = [1]; xs[1] = 2;", "Index out of range: 1 for length 1"),
+        ("let mut h = {}; h[[1]] = 2;", "Unusable as hash key: [1]"),
+        ("let mut h = {}; h[\"a\"][\"b\"] = 1;", "Index operator not supported: null[b]"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_struct() {
+    let decl = "struct Point { x, y }";
+    let tests = vec![
+        ("Point { x: 1, y: 2 };", "Point { x: 1, y: 2 }"),
+        ("Point { y: 2, x: 1 };", "Point { x: 1, y: 2 }"),
+        ("let p = Point { x: 1, y: 2 }; p.x + p.y;", "3"),
+        ("let mut p = Point { x: 1, y: 2 }; p.x = 10; p;", "Point { x: 10, y: 2 }"),
+        ("let p = Point { x: 1, y: 2 }; let mut q = p; q.x = 5; p.x;", "1"),
+        ("let mut l = Point { x: Point { x: 0, y: 0 }, y: 0 }; l.x.y = 3; l;", "Point { x: Point { x: 0, y: 3 }, y: 0 }"),
+        ("let mut ps = [Point { x: 1, y: 2 }]; ps[0].y = 7; ps;", "[Point { x: 1, y: 7 }]"),
+        ("Point { x: 1, y: 2 } == Point { x: 1, y: 2 };", "true"),
+        ("Point { x: 1, y: 2 } != Point { x: 1, y: 3 };", "true"),
+        ("let f = fn() { struct Point { x, y, z } Point { x: 1, y: 2, z: 3 } }; Point { x: 1, y: 2 } == f();", "false"),
+        ("let f = fn() { struct Point { x, w } Point { x: 1, w: 2 } }; Point { x: 1, y: 2 } == f();", "false"),
+        ("let p = null; p?.x;", "null"),
+        ("let mut h = {\"a\": 1}; h.b = 2; h.a + h.b;", "3"),
+        ("let mut n = 0; let i = fn() { n = n + 1; 0 }; let mut ps = [Point { x: 1, y: 2 }]; ps[i()].y = 2; n;", "1"),
+        ("[1, [2]] == [1, [2]];", "true"),
+        ("{\"a\": 1} == {\"a\": 2};", "false"),
+        ("1 == true;", "false"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", decl, input)), expect);
+    }
+
+    let errors = vec![
+        ("Point { x: 1, z: 2 };", "Struct Point has no field z"),
+        ("Point { x: 1 };", "Missing field y of struct Point"),
+        ("Point { x: 1, x: 2, y: 3 };", "Field x of struct Point is given twice"),
+        ("let p = Point { x: 1, y: 2 }; p.z;", "Struct Point has no field z"),
+        ("let mut p = Point { x: 1, y: 2 }; p.z = 1;", "Struct Point has no field z"),
+        ("y = 1;", "Identifier not found: y"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(&format!("{} {}", decl, input)), &format!("An error happen: {}", msg));
+    }
+    test_error(eval_input("let p = 1; p.x;"));
+}
+
+#[test]
+fn test_enum() {
+    let decl = "
+        enum State { Loading, Ready(data), Failed(code, reason) }
+        let describe = fn(s) {
+            match (s) {
+                State::Loading => \"loading\",
+                State::Ready(data) => \"ready: ${data}\",
+                State::Failed(code, reason) if code > 499 => \"server: ${reason}\",
+                State::Failed(_, reason) => \"failed: ${reason}\",
+            }
+        };";
+    let tests = vec![
+        ("State::Loading;", "State::Loading"),
+        ("State::Ready([1, 2]);", "State::Ready([1, 2])"),
+        ("State::Ready;", "fn State::Ready(data)"),
+        ("describe(State::Loading);", "loading"),
+        ("describe(State::Ready(42));", "ready: 42"),
+        ("describe(State::Failed(500, \"down\"));", "server: down"),
+        ("describe(State::Failed(404, \"missing\"));", "failed: missing"),
+        ("[1, 2] |> State::Ready |> describe;", "ready: [1, 2]"),
+        ("let State::Ready(x) = State::Ready(7); x;", "7"),
+        ("State::Ready(1) == State::Ready(1);", "true"),
+        ("State::Ready(1) == State::Ready(2);", "false"),
+        ("let f = fn() { enum State { Ready(data, more) } State::Ready(1, 2) }; State::Ready(1) == f();", "false"),
+        ("State::Loading == State::Loading;", "true"),
+        ("State::Loading != State::Ready(1);", "true"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", decl, input)), expect);
+    }
+
+    let errors = vec![
+        ("State::Done;", "Enum State has no variant or associated function Done"),
+        ("State::Ready(1, 2);", "Wrong number of arguments to fn State::Ready(data): expect 1, got 2"),
+        ("let State::Ready(x) = State::Loading;", "Expected State::Ready(x), got State::Loading"),
+        ("let n = 1; n::Loading;", "n is not a type: 1"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(&format!("{} {}", decl, input)), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_impl() {
+    let decl = "
+        struct Point { x, y }
+        impl Point {
+            fn origin() { Point { x: 0, y: 0 } }
+            fn new(x, y) { Point { x: x, y: y } }
+            fn norm(self) { self.x * self.x + self.y * self.y }
+            fn add(self, other) { Point::new(self.x + other.x, self.y + other.y) }
+        }
+        enum Shape { Square(side), Rect(w, h) }
+        impl Shape {
+            fn area(self) {
+                match (self) { Shape::Square(s) => s * s, Shape::Rect(w, h) => w * h }
+            }
+        }
+        impl Int {
+            fn double(self) { self * 2 }
+        }";
+    let tests = vec![
+        ("Point::origin();", "Point { x: 0, y: 0 }"),
+        ("Point::new(3, 4).norm();", "25"),
+        ("let p = Point::new(1, 2); p.add(Point::new(10, 20));", "Point { x: 11, y: 22 }"),
+        ("Point::norm(Point::new(1, 1));", "2"),
+        ("Shape::Rect(2, 3).area();", "6"),
+        ("Shape::Square(4).area();", "16"),
+        ("21.double();", "42"),
+        ("[Point::new(2, 0)].push(Point::new(0, 3))[1] |> Point::norm;", "9"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", decl, input)), expect);
+    }
+
+    let errors = vec![
+        ("Point::new(1, 2).size();", "Method size not found for Point { x: 1, y: 2 }"),
+        // Methods are used by operators only if they have the reserved names of hooks
+        ("Point::new(1, 2) + Point::new(3, 4);", "You can't use Point { x: 1, y: 2 } or Point { x: 3, y: 4 } as operand"),
+        ("Point::origin().origin();", "Point::origin is an associated function, not a method"),
+        ("Point::nothing();", "Struct Point has no associated function nothing"),
+        ("Int::nothing;", "Int has no associated function nothing"),
+        ("impl Piont { fn f(self) { 1 } }", "Unknown type for impl: Piont"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(&format!("{} {}", decl, input)), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_operator_hooks() {
+    let decl = "
+        struct Vec2 { x, y }
+        impl Vec2 {
+            fn op_add(self, other) { Vec2 { x: self.x + other.x, y: self.y + other.y } }
+            fn op_sub(self, other) { Vec2 { x: self.x - other.x, y: self.y - other.y } }
+            fn op_mul(self, k) { Vec2 { x: self.x * k, y: self.y * k } }
+            fn op_index(self, i) { match (i) { 0 => self.x, 1 => self.y } }
+            fn op_to_string(self) { \"(${self.x}, ${self.y})\" }
+        }
+        struct Money { cents }
+        impl Money {
+            fn op_eq(self, other) { self.cents / 100 == other.cents / 100 }
+            fn op_lt(self, other) { self.cents < other.cents }
+            fn op_to_string(self) { 1 }
+        }
+        let a = Vec2 { x: 1, y: 2 };
+        let b = Vec2 { x: 10, y: 20 };";
+    let tests = vec![
+        ("a + b;", "Vec2 { x: 11, y: 22 }"),
+        ("b - a;", "Vec2 { x: 9, y: 18 }"),
+        ("a * 3;", "Vec2 { x: 3, y: 6 }"),
+        ("(a + b)[1];", "22"),
+        ("\"a = ${a}\";", "a = (1, 2)"),
+        ("a == Vec2 { x: 1, y: 2 };", "true"),
+        ("Money { cents: 150 } == Money { cents: 199 };", "true"),
+        ("Money { cents: 150 } != Money { cents: 250 };", "true"),
+        ("Money { cents: 150 } < Money { cents: 250 };", "true"),
+        ("Money { cents: 150 } > Money { cents: 250 };", "false"),
+        // Methods with the names of the request aren't hooks
+        ("struct N { n } impl N { fn add(self, o) { self.n + o } } N { n: 1 }.add(2);", "3"),
+        ("struct N { n } impl N { fn to_string(self) { \"n\" } } \"${N { n: 1 }}\";", "N { n: 1 }"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", decl, input)), expect);
+    }
+
+    let errors = vec![
+        ("a < b;", "You can't use Vec2 { x: 1, y: 2 } or Vec2 { x: 10, y: 20 } as operand"),
+        // Only the left operand is looked up
+        ("3 * a;", "Vec2::op_mul is only used for the left operand: 3 * Vec2 { x: 1, y: 2 }"),
+        ("1 > Money { cents: 1 };", "Money::op_lt is only used for the left operand: 1 > Money { cents: 1 }"),
+        ("a[2];", "No match arm matched: 2"),
+        ("puts(Money { cents: 1 });", "Money::op_to_string must return a string, got 1"),
+        ("\"${Money { cents: 1 }}\";", "Money::op_to_string must return a string, got 1"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(&format!("{} {}", decl, input)), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_exceptions() {
+    let decl = "
+        struct NotFound { key }
+        impl NotFound {
+            fn op_to_string(self) { \"no such key: ${self.key}\" }
+        }
+        fn find(h, key) { h[key] ?? throw NotFound { key: key } }
+        fn lookup(h, key) { find(h, key) }";
+    let tests = vec![
+        ("try { throw \"boom\" } catch (e) { e.message };", "boom"),
+        ("try { throw \"boom\" } catch (e) { e.kind };", "Error"),
+        ("try { throw \"boom\" } catch (e) { e };", "Error: boom"),
+        ("try { x } catch (e) { e };", "RuntimeError: Identifier not found: x"),
+        ("try { 1 / 0 } catch (e) { e.kind };", "RuntimeError"),
+        ("try { lookup({}, \"a\") } catch (e) { e };", "NotFound: no such key: a"),
+        ("try { lookup({}, \"a\") } catch (e) { e.trace };", "[find, lookup]"),
+        ("try { lookup({}, \"a\") } catch (e) { e.value.key };", "a"),
+        ("try { lookup({\"a\": 1}, \"a\") } catch (e) { 0 };", "1"),
+        ("try { try { throw \"a\" } catch (e) { throw e } } catch (e) { e.message };", "a"),
+        ("let mut log = []; try { 1 } finally { log = log.push(\"done\") }; log;", "[done]"),
+        ("let f = fn() { try { return 1; } finally { 2 } }; f();", "1"),
+        ("let f = fn() { try { return 1; } finally { return 2; } }; f();", "2"),
+        ("try { try { x } finally { 1 } } catch (e) { e.message };", "Identifier not found: x"),
+        ("try { fn(){ y }() } catch (e) { e.trace };", "[<anonymous>]"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", decl, input)), expect);
+    }
+
+    test_object(eval_input("throw \"boom\";"), "An error happen: boom");
+    test_object(eval_input("try { 1 } catch (e) { e.nothing };"), "1");
+    test_object(
+        eval_input("try { x } catch (e) { e.nothing };"),
+        "An error happen: Error has no field nothing"
+    );
+    test_object(eval_input("try { x } catch (e) { throw e.message + \"!\" };"), "An error happen: Identifier not found: x!");
+}
+
+#[test]
+fn test_result() {
+    let decl = "
+        fn parse_digit(s) {
+            match (s) {
+                \"0\" => Ok(0), \"1\" => Ok(1), \"2\" => Ok(2),
+                _ => Err(\"not a digit: ${s}\"),
+            }
+        }
+        fn sum(a, b) { Ok(parse_digit(a)? + parse_digit(b)?) }
+        fn describe(r) {
+            match (r) { Result::Ok(v) => \"ok ${v}\", Result::Err(e) => \"err ${e}\" }
+        }";
+    let tests = vec![
+        ("Ok(1);", "Result::Ok(1)"),
+        ("sum(\"1\", \"2\");", "Result::Ok(3)"),
+        ("sum(\"1\", \"x\");", "Result::Err(not a digit: x)"),
+        ("sum(\"y\", \"1\");", "Result::Err(not a digit: y)"),
+        ("describe(sum(\"2\", \"2\"));", "ok 4"),
+        ("describe(sum(\"2\", \"z\"));", "err not a digit: z"),
+        ("let f = fn() { let x = parse_digit(\"q\")?; x + 1 }; f();", "Result::Err(not a digit: q)"),
+        ("let f = fn() { return parse_digit(\"q\")?; }; f();", "Result::Err(not a digit: q)"),
+        ("let f = fn() { [parse_digit(\"1\")?, parse_digit(\"q\")?] }; f();", "Result::Err(not a digit: q)"),
+        ("Ok(1) == Result::Ok(1);", "true"),
+        ("(read_file(\"/nonexistent/file\") |> describe |> len) > 0;", "true"),
+        ("match (read_file(\"/nonexistent/file\")) { Result::Ok(_) => 1, Result::Err(_) => 2 };", "2"),
+        ("let f = fn() { Ok(len(read_file(\"Cargo.toml\")?) > 0) }; f();", "Result::Ok(true)"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", decl, input)), expect);
+    }
+
+    let errors = vec![
+        ("1?;", "Invalid uses of postfix operator: ? can't applied to 1"),
+        // An enum shadowing Result isn't the builtin one
+        (
+            "let f = fn() { enum Result { Ok() } Result::Ok()? }; f();",
+            "Invalid uses of postfix operator: ? can't applied to Result::Ok()"
+        ),
+        (
+            "let f = fn() { enum Result { Ok(a, b) } Result::Ok(1, 2)? }; f();",
+            "Invalid uses of postfix operator: ? can't applied to Result::Ok(1, 2)"
+        ),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_defer() {
+    let decl = "
+        let mut log = [];
+        fn note(x) { log = log.push(x) }";
+    let tests = vec![
+        ("fn f() { defer note(1); defer note(2); note(0); } f(); log;", "[0, 2, 1]"),
+        ("fn f() { defer note(\"closed\"); return 1; note(\"unreachable\"); } [f(), log];", "[1, [closed]]"),
+        ("fn f() { defer note(\"closed\"); x; } try { f() } catch (e) { [e.message, log] };", "[Identifier not found: x, [closed]]"),
+        ("fn f() { defer note(\"closed\"); Err(1)?; } [f(), log];", "[Result::Err(1), [closed]]"),
+        ("if (true) { defer note(\"inner\"); note(\"body\"); }; note(\"after\"); log;", "[body, inner, after]"),
+        ("let f = fn(x) { defer note(x); x * 2 }; [f(1), f(2), log];", "[2, 4, [1, 2]]"),
+        ("fn f() { let mut x = 1; defer note(x); x = 2; } f(); log;", "[2]"),
+        ("defer note(\"end\"); note(\"start\"); log;", "[start]"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&format!("{} {}", decl, input)), expect);
+    }
+
+    test_object(
+        eval_input(&format!("{} fn f() {{ defer y; 1 }} f();", decl)),
+        "An error happen: Identifier not found: y"
+    );
+    test_object(
+        eval_input(&format!("{} fn f() {{ defer y; x }} f();", decl)),
+        "An error happen: Identifier not found: x"
+    );
+}
+
+#[test]
+fn test_mutability() {
+    let tests = vec![
+        ("let mut x = 1; x = x + 1; x;", "2"),
+        ("let mut x = 1; let mut x = 5; x;", "5"),
+        ("const LIMIT = 2 * 50; LIMIT;", "100"),
+        ("let mut p = {\"a\": [1]}; p[\"a\"][0] = 2; p;", "{a: [2]}"),
+        ("let f = fn(x) { x = x * 2; x }; f(4);", "8"),
+        ("let x = 1; let f = fn() { let x = 2; x }; f() + x;", "3"),
+        ("let f = fn() { let puts = 1; puts }; f();", "1"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    // Bindings unknown at parse time are checked at runtime
+    let errors = vec![
+        ("fn f() { x = 2 } let x = 1; f();", "Cannot assign to immutable binding x"),
+        ("fn f() { x.a = 2 } let x = {}; f();", "Cannot assign to immutable binding x"),
+        ("fn f() { LIMIT = 2 } const LIMIT = 1; f();", "Cannot assign to immutable binding LIMIT"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+
+    // Builtins and declared functions and types are immutable as well. The declarations are
+    // rejected by the parser where it knows them.
+    let runtime = |msg: &str| Ok(format!("An error happen: {}", msg));
+    let parse = |msg: &str| Err(msg.to_string());
+    let tests = vec![
+        ("puts = 5;", runtime("Cannot assign to immutable binding puts")),
+        ("let puts = 5;", runtime("Cannot redefine immutable binding puts")),
+        ("fn f() { 1 } f = 2;", parse("Cannot assign to immutable binding f")),
+        ("fn f() { 1 } let f = 2;", parse("Cannot redefine immutable binding f")),
+        ("fn g() { f = 2 } fn f() { 1 } g();", runtime("Cannot assign to immutable binding f")),
+        ("struct P { x } P = 3;", parse("Cannot assign to immutable binding P")),
+        ("fn g() { P = 3 } struct P { x } g();", runtime("Cannot assign to immutable binding P")),
+        ("enum E { A } E = 4;", parse("Cannot assign to immutable binding E")),
+    ];
+    for (input, expect) in tests {
+        assert_eq!(eval_input(input).map(|obj| obj.inspect()), expect, "{}", input);
+    }
+}
+
+#[test]
+fn test_blk_scope() {
+    let tests = vec![
+        ("let x = 1; if (true) { let x = 2; }; x;", "1"),
+        ("let x = 1; { let x = 2; x * 10 } + x;", "21"),
+        ("let mut x = 1; { x = 5; }; x;", "5"),
+        ("let y = { let a = 2; let b = 3; a * b }; y;", "6"),
+        ("{ let a = 1; }; a;", "An error happen: Identifier not found: a"),
+        ("let f = fn() { let n = 0; { defer puts(n); n } }; f();", "0"),
+        ("{\"k\": 1}[\"k\"];", "1"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+}
+
+#[test]
+fn test_for_statement() {
+    let tests = vec![
+        ("let mut sum = 0; for (x in [1, 2, 3]) { sum = sum + x; }; sum;", "6"),
+        ("let mut s = \"\"; for (c in \"abc\") { s = c + s; } s;", "cba"),
+        ("let mut n = 0; for (k in {\"a\": 1, \"b\": 2}) { n = n + 1; } n;", "2"),
+        ("let mut sum = 0; for ([a, b] in [[1, 2], [3, 4]]) { sum = sum + a * b; } sum;", "14"),
+        ("fn f() { for (x in [1, 2, 3]) { if (x == 2) { return x * 10; } else { x } } 0 } f();", "20"),
+        ("for (x in []) { x }", "null"),
+        ("for (x in 1) { x }", "An error happen: 1 is not iterable"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+}
+
+#[test]
+fn test_generator() {
+    let tests = vec![
+        (
+            "fn g() { yield 1; yield 2; } let it = g(); [it.next(), it.next(), it.next()];",
+            "[{value: 1, done: false}, {value: 2, done: false}, {value: null, done: true}]"
+        ),
+        // The end is told apart from a yielded null
+        ("fn g() { yield null; } let it = g(); [it.next().done, it.next().done];", "[false, true]"),
+        (
+            "fn range(n) { let mut i = 0; for (x in [0, 0, 0, 0, 0]) { if (i < n) { yield i; i = i + 1; } else {} } }
+            let mut sum = 0; for (x in range(4)) { sum = sum + x; } sum;",
+            "6"
+        ),
+        // Suspended state is kept between calls, and copies share it
+        (
+            "let mut log = []; fn g() { log = push(log, \"start\"); yield 1; log = push(log, \"end\"); }
+            let it = g(); let copy = it; let before = log; it.next(); let during = log; copy.next();
+            [len(before), len(during), len(log)];",
+            "[0, 1, 2]"
+        ),
+        // An infinite stream is consumed lazily
+        (
+            "fn nat(n) { yield n; for (x in nat(n + 1)) { yield x; } }
+            let it = nat(1); it.next(); it.next(); it.next().value;",
+            "3"
+        ),
+        (
+            "let g = fn(xs) { for (x in xs) { yield x * 2; } }; let mut ret = []; for (x in g([1, 2])) { ret = push(ret, x); } ret;",
+            "[2, 4]"
+        ),
+        // The returned value is discarded
+        ("fn g() { yield 1; return 5; yield 2; } let it = g(); it.next(); it.next();", "{value: null, done: true}"),
+        (
+            "let mut log = []; fn g() { defer log = push(log, 0); for (x in [1, 2]) { defer log = push(log, x); yield x; } }
+            let it = g(); it.next(); let before = log; it.next(); it.next(); [before, log];",
+            "[[], [1, 2, 0]]"
+        ),
+        ("fn g() { { yield 1; }; yield 2; } let it = g(); it.next(); it.next().value;", "2"),
+        ("fn g() { yield 1; } let it = g(); it == it;", "true"),
+        // 'next' is a method of generators, which a variable of the same name doesn't shadow
+        ("let next = fn(node) { node.next }; fn g() { yield 1; } g().next().value;", "1"),
+        ("fn g() { yield 1; } Generator::next(g()).value;", "1"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    let errors = vec![
+        ("fn g() { yield 1; throw \"boom\"; } let it = g(); it.next(); it.next();", "boom"),
+        ("fn g() { yield 1; 1 + true; } for (x in g()) { x }", "You can't use 1 or true as operand"),
+        ("fn g() { yield it.next(); } let it = g(); it.next();", "Generator fn g() is already running"),
+        ("fn g() { yield 1; } g().next(1);", "Generator::next takes no arguments"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+
+    // 'yield' where a generator can't be suspended is rejected before running
+    let input = "fn g() { try { yield 1; } finally {} } g().next();";
+    let msg = "yield can only be used in blocks, if and for statements of a generator";
+    assert_eq!(eval_input(input).map(|obj| obj.inspect()), Err(msg.to_string()));
+}
+
+#[test]
+fn test_tasks() {
+    let tests = vec![
+        // The main program runs the tasks while it waits
+        ("let ch = channel(); spawn(fn(n) { ch.send(n * 2); }, 21); ch.recv();", "42"),
+        (
+            "let ch = channel(); let out = channel();
+            fn worker(inbox, outbox) { for (i in [1, 2, 3]) { let x = inbox.recv(); outbox.send(x * i); } }
+            spawn(worker, ch, out); let mut ret = [];
+            for (x in [10, 20, 30]) { ch.send(x); ret = push(ret, out.recv()); }
+            ret;",
+            "[10, 40, 90]"
+        ),
+        // Tasks are switched when they wait, and an unbuffered send waits for the receiver
+        (
+            "let ch = channel(); let done = channel(); let mut log = [];
+            spawn(fn() { log = push(log, \"a1\"); ch.send(1); log = push(log, \"a2\"); done.send(0); });
+            spawn(fn() { log = push(log, \"b1\"); let x = ch.recv(); log = push(log, \"b2\"); });
+            done.recv(); log;",
+            "[a1, b1, b2, a2]"
+        ),
+        (
+            "let ch = channel(2); ch.send(1); ch.send(2); [ch.recv(), ch.recv()];",
+            "[1, 2]"
+        ),
+        (
+            "let a = channel(); let b = channel();
+            spawn(fn() { b.send(\"from b\"); });
+            let [i, v] = select(a, b); [i, v];",
+            "[1, from b]"
+        ),
+        // Tasks can yield to each other
+        (
+            "let mut log = []; let done = channel(3);
+            fn count(name) { for (i in [1, 2]) { log = push(log, name + \"${i}\"); yield null; } done.send(name); }
+            spawn(count, \"a\"); spawn(count, \"b\"); done.recv(); done.recv(); log;",
+            "[a1, b1, a2, b2]"
+        ),
+        // Operations which don't have to wait complete anywhere in a task
+        (
+            "let ch = channel(1); let out = channel(1); fn get(c) { c.recv() }
+            spawn(fn() { ch.send(1); out.send(get(ch) + 1); }); out.recv();",
+            "2"
+        ),
+        // Tasks run to the end after the main program
+        ("let mut n = 0; spawn(fn() { n = 1; }); n;", "0"),
+        ("let ch = channel(); ch == ch;", "true"),
+        // 'send' and 'recv' are methods of channels, which variables of the same names don't shadow
+        ("let send = 3; let recv = 4; let ch = channel(1); ch.send(1); ch.recv();", "1"),
+        ("let ch = channel(1); Channel::send(ch, 2); Channel::recv(ch);", "2"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    let errors = vec![
+        ("let ch = channel(); ch.recv();", "Deadlock: main waits to recv from channel 1"),
+        (
+            "let a = channel(); let b = channel();
+            spawn(fn() { let x = a.recv(); b.send(x); }); spawn(fn() { let y = b.recv(); a.send(y); });",
+            "Deadlock: task 1 fn() waits to recv from channel 1, task 2 fn() waits to recv from channel 2"
+        ),
+        (
+            "let ch = channel(); spawn(fn() { puts(ch.recv()); }); spawn(fn() { ch.send(1); });",
+            "Task 1 can't wait to recv from channel 1 here, only as a statement of its body"
+        ),
+        (
+            "let ch = channel(); spawn(fn() { let x = ch.recv() + 1; }); spawn(fn() { ch.send(1); });",
+            "Task 1 can't wait to recv from channel 1 here, only as a statement of its body"
+        ),
+        (
+            "let ch = channel(); fn get(c) { c.recv() } spawn(fn() { let x = get(ch); }); spawn(fn() { ch.send(1); });",
+            "Task 1 can't wait to recv from channel 1 here, only as a statement of its body"
+        ),
+        ("spawn(fn() { 1 + true; });", "You can't use 1 or true as operand"),
+        ("spawn(1);", "spawn expects a function, got 1"),
+        ("channel(-1);", "channel expects a non-negative capacity"),
+        ("select(1);", "1 is not a channel"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+
+    // Tasks left by a failed program don't run with the next one, as in the REPL
+    let tests = vec![
+        ("spawn(fn() { 2 + true; }); 1 + true;", "An error happen: You can't use 1 or true as operand"),
+        ("spawn(fn() { 1 + true; }); spawn(fn() { 2 + true; });", "An error happen: You can't use 1 or true as operand"),
+        ("1;", "1"),
+    ];
+    let mut eval = Eval::new(Rc::new(RefCell::new(Env::new())));
+    for (input, expect) in tests {
+        let program = Parser::new(Lexer::new(input).tokenize()).parse().unwrap_or_else(|err| panic!("{}", err));
+        test_object(Ok(eval.eval(program)), expect);
+    }
+}
+
+#[test]
+fn test_macros() {
+    let tests = vec![
+        ("quote(1 + 2);", "QUOTE((1 + 2))"),
+        ("quote(foo(x));", "QUOTE(foo(x))"),
+        ("quote(1 + unquote(2 + 2));", "QUOTE((1 + 4))"),
+        ("let q = quote(a * b); quote(unquote(q) + c);", "QUOTE(((a * b) + c))"),
+        ("quote(unquote([1, true, \"s\"]));", "QUOTE([1, true, \"s\"])"),
+        ("quote(x) == quote(x);", "true"),
+        (
+            "let unless = macro(cond, then, other) { quote(if (!(unquote(cond))) { unquote(then) } else { unquote(other) }) };
+            unless(10 > 5, \"not greater\", \"greater\");",
+            "greater"
+        ),
+        // Arguments are given unevaluated
+        (
+            "let mut n = 0; let never = macro(x) { quote(null) }; never(n = 1); n;",
+            "0"
+        ),
+        (
+            "let twice = macro(x) { quote({ unquote(x); unquote(x) }) }; let mut n = 0; twice(n = n + 1); n;",
+            "2"
+        ),
+        // Macros can use other macros in their expansions
+        (
+            "let inc = macro(x) { quote(unquote(x) + 1) }; let inc2 = macro(x) { quote(inc(inc(unquote(x)))) }; inc2(1);",
+            "3"
+        ),
+        // Bindings in the expansion don't capture the variables of the caller
+        (
+            "let add10 = macro(v) { quote({ let tmp = 10; unquote(v) + tmp }) }; let tmp = 1; add10(tmp);",
+            "11"
+        ),
+        (
+            "let with_x = macro(body) { quote({ let x = 100; unquote(body) }) }; let x = 1; with_x(x + 1);",
+            "2"
+        ),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    let errors = vec![
+        ("unquote(1);", "unquote used outside of quote"),
+        ("quote(1, 2);", "quote expects one expression"),
+        ("quote(unquote(puts));", "Cannot unquote Buildin Function"),
+        ("let m = macro(x) { x }; m(1, 2);", "Wrong number of arguments to macro m: expect 1, got 2"),
+        ("let m = macro(x) { 1 }; m(1);", "Macro m must return a quoted expression, got 1"),
+        ("let f = fn() { macro() { quote(1) } }; f();", "macro can only be defined by a top-level let"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_tuples() {
+    let tests = vec![
+        ("(1, \"a\", true);", "(1, a, true)"),
+        ("(1,);", "(1,)"),
+        ("();", "()"),
+        ("(1 + 2);", "3"),
+        ("let t = (1, (2, 3)); [t.0, t.1.1];", "[1, 3]"),
+        ("fn divmod(a, b) { (a / b, a - a / b * b) } let (q, r) = divmod(17, 5); [q, r];", "[3, 2]"),
+        ("let mut a = 1; let mut b = 2; a, b = b, a; [a, b];", "[2, 1]"),
+        ("let mut a = 1; let mut b = 2; (a, b) = (b, a + b); [a, b];", "[2, 3]"),
+        ("let mut xs = [1, 2]; xs[0], xs[1] = xs[1], xs[0]; xs;", "[2, 1]"),
+        ("let mut a = 0; let mut b = 0; a, b = (3, 4); a + b;", "7"),
+        ("let ((a, b), c) = ((1, 2), 3); a + b + c;", "6"),
+        ("match ((1, 2)) { (2, _) => 1, (1, y) => y, _ => 0 };", "2"),
+        ("(1, [2]) == (1, [2]);", "true"),
+        ("(1, 2) == [1, 2];", "false"),
+        ("len((1, 2, 3));", "3"),
+        ("let mut n = 0; for ((a, b) in [(1, 2), (3, 4)]) { n = n + a * b; } n;", "14"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    let errors = vec![
+        ("(1, 2).2;", "Tuple (1, 2) has no field 2"),
+        ("let (a, b) = (1, 2, 3);", "Expected a tuple of 2 elements to destructure with (a, b), got (1, 2, 3)"),
+        ("let mut a = 0; let mut b = 0; a, b = 1, 2, 3;", "Expected a tuple of 2 values to assign to (a, b), got (1, 2, 3)"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_sets() {
+    let tests = vec![
+        ("#{1, 2, 2, \"a\", true, 1};", "#{1, 2, a, true}"),
+        ("#{};", "#{}"),
+        ("len(#{1, 1, 2});", "2"),
+        ("[2 in #{1, 2}, 3 in #{1, 2}, [1] in #{1}];", "[true, false, false]"),
+        ("[2 in [1, 2], 2 in (2,), \"k\" in {\"k\": 1}, \"bc\" in \"abc\"];", "[true, true, true, true]"),
+        ("#{1, 2} | #{2, 3};", "#{1, 2, 3}"),
+        ("#{3, 2, 1} & #{1, 2, 4};", "#{2, 1}"),
+        ("#{1, 2, 3} - #{2};", "#{1, 3}"),
+        ("[#{1} <= #{1, 2}, #{1, 2} <= #{1, 2}, #{1, 2} < #{1, 2}, #{1, 2} >= #{2}, #{3} > #{}];", "[true, true, false, true, true]"),
+        ("#{1, 2} == #{2, 1};", "true"),
+        ("#{1, 2} == #{1, 2, 3};", "false"),
+        ("set([3, 1, 3, 2]);", "#{3, 1, 2}"),
+        ("[union(#{1}, #{2}), intersection(#{1, 2}, #{2}), difference(#{1, 2}, #{2})];", "[#{1, 2}, #{2}, #{1}]"),
+        ("[is_subset(#{1}, #{1, 2}), is_subset(#{3}, #{1, 2})];", "[true, false]"),
+        ("push(#{1}, 1) |> push(_, 2);", "#{1, 2}"),
+        ("let mut n = 0; for (x in #{1, 2, 2, 3}) { n = n + x; } n;", "6"),
+        ("[1 <= 1, 2 >= 3];", "[true, false]"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    let errors = vec![
+        ("#{[1]};", "Unusable as set element: [1]"),
+        ("set([1, {}]);", "Unusable as set element: {}"),
+        ("union(#{1}, [2]);", "union expects two sets"),
+        ("1 in 2;", "Can't look for 1 in 2"),
+        ("#{1} * #{2};", "Invalid uses of infix operator: Asterisk can't applied to #{1} and #{2}"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_slicing() {
+    let tests = vec![
+        ("[1, 2, 3, 4, 5][1:4];", "[2, 3, 4]"),
+        ("let xs = [1, 2, 3, 4, 5]; [xs[:-1], xs[::2], xs[::-1], xs[3:], xs[-2:]];", "[[1, 2, 3, 4], [1, 3, 5], [5, 4, 3, 2, 1], [4, 5], [4, 5]]"),
+        ("let xs = [1, 2, 3, 4, 5]; [xs[-100:100], xs[4:1], xs[3:0:-1], xs[:]];", "[[1, 2, 3, 4, 5], [], [4, 3, 2], [1, 2, 3, 4, 5]]"),
+        ("\"héllo wörld\"[1:4];", "éll"),
+        ("let s = \"héllo\"; s[::-1] + s[:-1];", "olléhhéll"),
+        ("(1, 2, 3)[1:];", "(2, 3)"),
+        ("(1, 2, 3)[:1];", "(1,)"),
+        ("let xs = [1, 2, 3]; let mut ys = xs[:]; ys[0] = 9; [xs, ys];", "[[1, 2, 3], [9, 2, 3]]"),
+        ("let i = 1; [1, 2, 3][i:null];", "[2, 3]"),
+        ("let xs = [1, 2, 3, 4, 5]; let i = 1; let n = 2; [xs[i::(n)], xs[i:i + n], xs[::-n]];", "[[2, 4], [2, 3], [5, 3, 1]]"),
+        // 'a::b' in an index is a path rather than a slice
+        ("impl Int { fn one() { 1 } } [1, 2, 3][Int::one()];", "2"),
+        (
+            "enum Axis { X, Y } struct V { x, y }
+            impl V { fn op_index(self, axis) { match (axis) { Axis::X => self.x, Axis::Y => self.y } } }
+            let v = V { x: 1, y: 2 }; v[Axis::Y];",
+            "2"
+        ),
+        ("null?.[1:];", "null"),
+        ("let xs = [1, 2, 3]; [xs[-1], xs[-3], xs[-4]];", "[3, 1, null]"),
+        ("[\"héllo\"[1], \"héllo\"[-1], \"héllo\"[5]];", "[é, o, null]"),
+        ("(1, 2, 3)[-1];", "3"),
+        ("let mut xs = [1, 2, 3]; xs[-1] = 9; xs;", "[1, 2, 9]"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(input), expect);
+    }
+
+    let errors = vec![
+        ("[1, 2][::0];", "Slice step cannot be zero"),
+        ("[1, 2][\"a\":];", "Slice bounds must be integers, got a"),
+        ("{\"a\": 1}[1:];", "Slicing not supported: {a: 1}"),
+        ("let mut xs = [1]; xs[-2] = 0;", "Index out of range: -2 for length 1"),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(input), &format!("An error happen: {}", msg));
+    }
+}
+
+#[test]
+fn test_modules() {
+    let dir = write_files("test_modules", &[
+        ("app.mk", "import \"lib/math.mk\" as math; export let answer = math.add(40, 2); export fn twice(x) { math.double(x) }"),
+        (
+            "lib/math.mk",
+            "export fn double(x) { add(x, x) } export fn add(a, b) { a + b } export const PI = 3;
+            fn hidden() { 0 } export struct Point { x, y }"
+        ),
+        ("cycle/a.mk", "import \"b.mk\" as b; export let a = 1;"),
+        ("cycle/b.mk", "import \"a.mk\" as a; export let b = 2;"),
+        ("broken.mk", "export let x = 1 + true;"),
+    ]);
+    let dir = dir.display();
+
+    let tests = vec![
+        (format!("import \"{}/lib/math.mk\" as math; math.add(1, 2);", dir), "3"),
+        (format!("import \"{}/lib/math.mk\" as math; math.PI;", dir), "3"),
+        (format!("import \"{}/app.mk\" as app; [app.answer, app.twice(5)];", dir), "[42, 10]"),
+        (format!("import \"{}/lib/math.mk\" as m; let Point = m.Point; Point {{ x: 1, y: 2 }}.x;", dir), "1"),
+        // Each module is evaluated once and shared by all imports
+        (format!("import \"{0}/lib/math.mk\" as a; import \"{0}/lib/../lib/math.mk\" as b; a == b;", dir), "true"),
+    ];
+    for (input, expect) in tests {
+        test_object(eval_input(&input), expect);
+    }
+    let input = format!("import \"{}/lib/math.mk\" as math; math;", dir);
+    test_object(eval_input(&input), &format!("<module {}/lib/math.mk>", dir));
+
+    let errors = vec![
+        (format!("import \"{}/lib/math.mk\" as math; math.hidden();", dir), format!("Module {}/lib/math.mk has no export hidden", dir)),
+        (format!("import \"{}/cycle/a.mk\" as a;", dir), format!("Circular import: {}/cycle/a.mk -> b.mk -> a.mk", dir)),
+        (format!("import \"{}/broken.mk\" as b;", dir), "You can't use 1 or true as operand".to_string()),
+        (
+            format!("import \"{}/missing.mk\" as m;", dir),
+            format!("Can't import {}/missing.mk: No such file or directory (os error 2)", dir)
+        ),
+    ];
+    for (input, msg) in errors {
+        test_object(eval_input(&input), &format!("An error happen: {}", msg));
+    }
+}
+
+// Write the files into a fresh directory for the test, and give the directory
+fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rmonkey-{}-{}", name, std::process::id()));
+    for (path, content) in files.iter() {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap_or_else(|err| panic!("{}", err));
+        }
+        fs::write(path, content).unwrap_or_else(|err| panic!("{}", err));
+    }
+    fs::canonicalize(dir).unwrap_or_else(|err| panic!("{}", err))
+}
+
+// Feed random token soup and random bytes through the whole pipeline. Any panic fails the test.
+#[test]
+fn test_no_panic_on_random_input() {
+    let fragments = [
+        "let", "x", "f", "=", "fn", "(", ")", "{", "}", "if", "else", "return", ";", ",",
+        "0", "1", "-1", "9223372036854775807", "true", "false", "\"s\"",
+        "+", "-", "*", "/", "!", "<", ">", "==", "!=", "++", "--",
+        "null", "??", "?.", "[", "]", ":", "\"a${", "}b\"",
+        "match", "=>", "_", "|", "..", "..=", "...", "|>", ">>", "<<", ".", "len",
+        "struct", "Point", "enum", "::", "impl", "self",
+        "throw", "try", "catch", "finally", "?", "Ok", "Err", "defer", "mut", "const",
+        "for", "in", "yield", ".next()", "spawn", "channel()", ".send", ".recv()", "select",
+        "macro", "quote", "unquote", "import", "export", "as",
+        "#{", "&", "<=", ">=", "[1:", ":-1]", "[::",
+    ];
+
+    run_with_stack(move || {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        for _ in 0..2000 {
+            let len = rng.next() % 40;
+            let input: Vec<&str> = (0..len)
+                .map(|_| fragments[rng.next() % fragments.len()])
+                .collect();
+            let _ = eval_input(&input.join(" "));
+
+            let len = rng.next() % 40;
+            let bytes: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+            let _ = eval_input(&String::from_utf8_lossy(&bytes));
+        }
+    });
+}
+
+// Inputs far longer than the random ones, each of which once overflowed the stack
+#[test]
+fn test_no_panic_on_long_input() {
+    run_with_stack(|| {
+        let nested = "Expression is nested deeper than 128";
+        let tests = vec![
+            (format!("let f = fn() {{ f }}; f{};", "()".repeat(200000)), Err(nested.to_string())),
+            (format!("let a = [0]; a{};", "[0]".repeat(200000)), Err(nested.to_string())),
+            (format!("let a = {}0{};", "[".repeat(100000), "]".repeat(100000)), Err(nested.to_string())),
+            (format!("let {}a{} = 1;", "[".repeat(100000), "]".repeat(100000)), Err(nested.to_string())),
+        ];
+        for (input, expect) in tests {
+            assert_eq!(eval_input(&input).map(|obj| obj.inspect()), expect);
+        }
+
+        let input = format!("1{};", " + 1".repeat(2000));
+        test_object(eval_input(&input), "2001");
+
+        let input = format!("1{};", " + 1".repeat(200000));
+        test_object(eval_input(&input), "An error happen: Evaluation is nested deeper than 16384");
+    });
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 as usize
+    }
+}
+
+// The interpreter expects to run with STACK_SIZE bytes of stack, like the repl does.
+fn run_with_stack<F: FnOnce() + Send + 'static>(f: F) {
+    let handle = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(f)
+        .unwrap_or_else(|err| panic!("{}", err));
+    if let Err(err) = handle.join() {
+        panic::resume_unwind(err);
+    }
+}
+
+fn test_object(obj: Result<Object, String>, expect: &str) {
+    match obj {
+        Ok(obj) if obj.inspect() == expect => (),
+        Ok(obj) => panic!("The object has different value: expect {}, got {}", expect, obj.inspect()),
+        Err(err) => panic!("{}", err),
+    }
+}
+
+fn test_error(obj: Result<Object, String>) {
+    match obj {
+        Ok(Object::Err(_)) | Err(_) => (),
+        Ok(obj) => panic!("Expect an error, got {}", obj.inspect()),
+    }
+}
+
+fn eval_input(input: &str) -> Result<Object, String> {
+    let lexer   = Lexer::new(input);
+    let parser  = Parser::new(lexer.tokenize());
+    let program = parser.parse().map_err(|err| err.to_string())?;
+    let mut eval = Eval::new(Rc::new(RefCell::new(Env::new())));
+    match eval.expand_macros(program) {
+        Ok(program) => Ok(eval.eval(program)),
+        Err(err) => Ok(Object::Err(err)),
+    }
+}
@@ -1,16 +1,18 @@
 mod test;
 
-use std::{cell::Cell, collections::HashMap};
+use std::{cell::{Cell, RefCell}, collections::HashMap};
 use crate::token::{Token, TokenKind};
 use once_cell::sync::Lazy;
 
 pub struct Lexer<'a> {
     input: Cell<&'a str>,
+    // Depth of '{' for each interpolation '${ ... }' which is being read
+    interp: RefCell<Vec<usize>>,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &str) -> Lexer<'_> {
-        Lexer { input: Cell::new(input) }
+        Lexer { input: Cell::new(input), interp: RefCell::new(Vec::new()) }
     }
 
     pub fn tokenize(&self) -> Vec<Token<'_>> {
@@ -49,13 +51,36 @@ impl <'a> Lexer<'a> {
                 ("if",     Token::new(TokenKind::If,       "")),
                 ("else",   Token::new(TokenKind::Else,     "")),
                 ("return", Token::new(TokenKind::Return,   "")),
+                ("null",   Token::new(TokenKind::Null,     "")),
+                ("match",  Token::new(TokenKind::Match,    "")),
+                ("struct", Token::new(TokenKind::Struct,   "")),
+                ("enum",   Token::new(TokenKind::Enum,     "")),
+                ("impl",   Token::new(TokenKind::Impl,     "")),
+                ("throw",  Token::new(TokenKind::Throw,    "")),
+                ("try",    Token::new(TokenKind::Try,      "")),
+                ("catch",  Token::new(TokenKind::Catch,    "")),
+                ("finally", Token::new(TokenKind::Finally, "")),
+                ("defer",  Token::new(TokenKind::Defer,    "")),
+                ("mut",    Token::new(TokenKind::Mut,      "")),
+                ("const",  Token::new(TokenKind::Const,    "")),
+                ("for",    Token::new(TokenKind::For,      "")),
+                ("in",     Token::new(TokenKind::In,       "")),
+                ("yield",  Token::new(TokenKind::Yield,    "")),
+                ("macro",  Token::new(TokenKind::Macro,    "")),
+                ("import", Token::new(TokenKind::Import,   "")),
+                ("export", Token::new(TokenKind::Export,   "")),
+                ("as",     Token::new(TokenKind::As,       "")),
             ])
         });
 
-        if self.input.get().chars().next()?.is_ascii_alphabetic() {
+        let first = self.input.get().chars().next()?;
+        if first.is_ascii_alphabetic() || first == '_' {
             let body = self.trim_start_with(|c: char| {
                 c.is_ascii_alphanumeric() || c == '_'
             });
+            if body == "_" {
+                return Some(Token::new(TokenKind::Underscore, ""));
+            }
             match KEYWORD.get(body.to_lowercase().as_str()) {
                 Some(token) => Some(*token),
                 None        => Some(Token::new(TokenKind::Ident, body)),
@@ -65,7 +90,7 @@ impl <'a> Lexer<'a> {
         }
     }
 
-    fn integer(&self) -> Option<Token> {
+    fn integer(&self) -> Option<Token<'_>> {
         if self.input.get().chars().next()?.is_ascii_digit() {
             let body = self.trim_start_with(|c: char| c.is_ascii_digit());
             Some(Token::new(TokenKind::Int, body))
@@ -74,15 +99,38 @@ impl <'a> Lexer<'a> {
         }
     }
 
-    fn one_or_more(&self) -> Option<Token> {
+    fn one_or_more(&self) -> Option<Token<'_>> {
         let mut chars = self.input.get().chars();
         let ret = match chars.next()? {
             '=' => {
-                if self.input.get().chars().nth(1)? == '=' {
-                    chars.next();
-                    Some(Token::new(TokenKind::Eq,     ""))
-                } else {
-                    Some(Token::new(TokenKind::Assign, ""))
+                match self.input.get().chars().nth(1)? {
+                    '=' => {
+                        chars.next();
+                        Some(Token::new(TokenKind::Eq,       ""))
+                    }
+                    '>' => {
+                        chars.next();
+                        Some(Token::new(TokenKind::FatArrow, ""))
+                    }
+                    _ => Some(Token::new(TokenKind::Assign, "")),
+                }
+            }
+            '.' => {
+                if self.input.get().chars().nth(1) != Some('.') {
+                    self.input.set(chars.as_str());
+                    return Some(Token::new(TokenKind::Dot, ""));
+                }
+                chars.next();
+                match self.input.get().chars().nth(2) {
+                    Some('=') => {
+                        chars.next();
+                        Some(Token::new(TokenKind::DotDotEq, ""))
+                    }
+                    Some('.') => {
+                        chars.next();
+                        Some(Token::new(TokenKind::Ellipsis, ""))
+                    }
+                    _ => Some(Token::new(TokenKind::DotDot, "")),
                 }
             }
             '!' => {
@@ -109,34 +157,151 @@ impl <'a> Lexer<'a> {
                     Some(Token::new(TokenKind::Minus,  ""))
                 }
             }
+            '?' => {
+                match self.input.get().chars().nth(1) {
+                    Some('?') => {
+                        chars.next();
+                        Some(Token::new(TokenKind::Coalesce, ""))
+                    }
+                    Some('.') => {
+                        chars.next();
+                        Some(Token::new(TokenKind::OptChain, ""))
+                    }
+                    _ => Some(Token::new(TokenKind::Question, "")),
+                }
+            }
             '*' => Some(Token::new(TokenKind::Asterisk,      "")),
             '/' => Some(Token::new(TokenKind::Slash,         "")),
-            '<' => Some(Token::new(TokenKind::LT,            "")),
-            '>' => Some(Token::new(TokenKind::GT,            "")),
+            '<' => {
+                match self.input.get().chars().nth(1)? {
+                    '<' => {
+                        chars.next();
+                        Some(Token::new(TokenKind::ComposeLeft,  ""))
+                    }
+                    '=' => {
+                        chars.next();
+                        Some(Token::new(TokenKind::LtEq,         ""))
+                    }
+                    _ => Some(Token::new(TokenKind::LT,          "")),
+                }
+            }
+            '>' => {
+                match self.input.get().chars().nth(1)? {
+                    '>' => {
+                        chars.next();
+                        Some(Token::new(TokenKind::ComposeRight, ""))
+                    }
+                    '=' => {
+                        chars.next();
+                        Some(Token::new(TokenKind::GtEq,         ""))
+                    }
+                    _ => Some(Token::new(TokenKind::GT,          "")),
+                }
+            }
+            '&' => Some(Token::new(TokenKind::Ampersand,     "")),
+            '#' if self.input.get().chars().nth(1) == Some('{') => {
+                chars.next();
+                if let Some(depth) = self.interp.borrow_mut().last_mut() {
+                    *depth += 1;
+                }
+                Some(Token::new(TokenKind::SetStart, ""))
+            }
             ',' => Some(Token::new(TokenKind::Comma,         "")),
             ';' => Some(Token::new(TokenKind::Semicolon,     "")),
             '(' => Some(Token::new(TokenKind::LParenthesis,  "")),
             ')' => Some(Token::new(TokenKind::RParenthesis,  "")),
-            '{' => Some(Token::new(TokenKind::LCurlyBracket, "")),
-            '}' => Some(Token::new(TokenKind::RCurlyBracket, "")),
-            '"' => return self.read_string(),
+            '{' => {
+                if let Some(depth) = self.interp.borrow_mut().last_mut() {
+                    *depth += 1;
+                }
+                Some(Token::new(TokenKind::LCurlyBracket, ""))
+            }
+            '}' => {
+                let mut interp = self.interp.borrow_mut();
+                match interp.last_mut() {
+                    Some(0) => {
+                        // This closes '${', so the rest is a part of string
+                        interp.pop();
+                        drop(interp);
+                        self.skip_char();
+                        return self.read_string(true);
+                    }
+                    Some(depth) => *depth -= 1,
+                    None => (),
+                }
+                Some(Token::new(TokenKind::RCurlyBracket, ""))
+            }
+            '[' => Some(Token::new(TokenKind::LSquareBracket, "")),
+            ']' => Some(Token::new(TokenKind::RSquareBracket, "")),
+            ':' => {
+                if self.input.get().chars().nth(1) == Some(':') {
+                    chars.next();
+                    Some(Token::new(TokenKind::PathSep, ""))
+                } else {
+                    Some(Token::new(TokenKind::Colon,   ""))
+                }
+            }
+            '|' => {
+                if self.input.get().chars().nth(1)? == '>' {
+                    chars.next();
+                    Some(Token::new(TokenKind::Pipeline, ""))
+                } else {
+                    Some(Token::new(TokenKind::Pipe,     ""))
+                }
+            }
+            '"' => {
+                self.skip_char();
+                return self.read_string(false);
+            }
             _   => return None,
         };
         self.input.set(chars.as_str());
         ret
     }
 
-    fn read_string(&self) -> Option<Token> {
-        // Skip '"'
-        self.skip_char();
-
-        // Read string
-        let ret = self.trim_start_with(|c: char| c != '"');
+    // Read a string until '"' or '${'. 'continued' is true if this follows '${ ... }'.
+    fn read_string(&self, continued: bool) -> Option<Token<'_>> {
+        let input = self.input.get();
+        let mut end = input.len();
+        let mut interp = false;
+        let mut chars = input.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == '"' {
+                end = i;
+                break;
+            }
+            // '$${' is a literal '${', which is left in the token and unescaped by the parser
+            if input[i..].starts_with("$${") {
+                chars.nth(1);
+                continue;
+            }
+            if input[i..].starts_with("${") {
+                end = i;
+                interp = true;
+                break;
+            }
+        }
 
-        // Skip '"'
-        self.skip_char();
+        let (ret, other) = input.split_at(end);
+        self.input.set(other);
 
-        Some(Token::new(TokenKind::Str, ret))
+        if interp {
+            // Skip '${'
+            self.skip_char();
+            self.skip_char();
+            self.interp.borrow_mut().push(0);
+            match continued {
+                true  => Some(Token::new(TokenKind::InterpMid,   ret)),
+                false => Some(Token::new(TokenKind::InterpStart, ret)),
+            }
+        } else {
+            // Skip '"'
+            self.skip_char();
+            match continued {
+                true  => Some(Token::new(TokenKind::InterpEnd, ret)),
+                false => Some(Token::new(TokenKind::Str,       ret)),
+            }
+        }
     }
 
     // Take a closure and get trimmed string from begin of self.input
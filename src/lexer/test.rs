@@ -126,3 +126,195 @@ fn test_lexer() {
         }
     }
 }
+
+#[test]
+fn test_null_and_collections() {
+    let input = "null ?? [1]?.[0]; {\"a\": f?.()};";
+
+    let tests = vec![
+        Token::new(TokenKind::Null,           ""),
+        Token::new(TokenKind::Coalesce,       ""),
+        Token::new(TokenKind::LSquareBracket, ""),
+        Token::new(TokenKind::Int,           "1"),
+        Token::new(TokenKind::RSquareBracket, ""),
+        Token::new(TokenKind::OptChain,       ""),
+        Token::new(TokenKind::LSquareBracket, ""),
+        Token::new(TokenKind::Int,           "0"),
+        Token::new(TokenKind::RSquareBracket, ""),
+        Token::new(TokenKind::Semicolon,      ""),
+        Token::new(TokenKind::LCurlyBracket,  ""),
+        Token::new(TokenKind::Str,           "a"),
+        Token::new(TokenKind::Colon,          ""),
+        Token::new(TokenKind::Ident,         "f"),
+        Token::new(TokenKind::OptChain,       ""),
+        Token::new(TokenKind::LParenthesis,   ""),
+        Token::new(TokenKind::RParenthesis,   ""),
+        Token::new(TokenKind::RCurlyBracket,  ""),
+        Token::new(TokenKind::Semicolon,      ""),
+        Token::new(TokenKind::Eof,            ""),
+    ];
+
+    let lexer  = Lexer::new(input);
+    let tokens = lexer.tokenize();
+    assert_eq!(tokens, tests);
+}
+
+#[test]
+fn test_interpolated_string() {
+    let input = "\"Hello, ${name}! You are ${ {\"a\": age}[\"a\"] + 1 }\"; \"${\"${x}\"}\"";
+
+    let tests = vec![
+        Token::new(TokenKind::InterpStart, "Hello, "),
+        Token::new(TokenKind::Ident,          "name"),
+        Token::new(TokenKind::InterpMid,  "! You are "),
+        Token::new(TokenKind::LCurlyBracket,      ""),
+        Token::new(TokenKind::Str,               "a"),
+        Token::new(TokenKind::Colon,              ""),
+        Token::new(TokenKind::Ident,           "age"),
+        Token::new(TokenKind::RCurlyBracket,      ""),
+        Token::new(TokenKind::LSquareBracket,     ""),
+        Token::new(TokenKind::Str,               "a"),
+        Token::new(TokenKind::RSquareBracket,     ""),
+        Token::new(TokenKind::Plus,               ""),
+        Token::new(TokenKind::Int,               "1"),
+        Token::new(TokenKind::InterpEnd,          ""),
+        Token::new(TokenKind::Semicolon,          ""),
+        Token::new(TokenKind::InterpStart,        ""),
+        Token::new(TokenKind::InterpStart,        ""),
+        Token::new(TokenKind::Ident,             "x"),
+        Token::new(TokenKind::InterpEnd,          ""),
+        Token::new(TokenKind::InterpEnd,          ""),
+        Token::new(TokenKind::Eof,                ""),
+    ];
+
+    let lexer  = Lexer::new(input);
+    let tokens = lexer.tokenize();
+    assert_eq!(tokens, tests);
+}
+
+#[test]
+fn test_escaped_interpolation() {
+    let input = "\"$${x} costs $5\"; \"$${${y}}\"; \"$$${z}\"";
+
+    let tests = vec![
+        Token::new(TokenKind::Str, "$${x} costs $5"),
+        Token::new(TokenKind::Semicolon,        ""),
+        Token::new(TokenKind::InterpStart,   "$${"),
+        Token::new(TokenKind::Ident,           "y"),
+        Token::new(TokenKind::InterpEnd,       "}"),
+        Token::new(TokenKind::Semicolon,        ""),
+        Token::new(TokenKind::Str,      "$$${z}"),
+        Token::new(TokenKind::Eof,              ""),
+    ];
+
+    let lexer  = Lexer::new(input);
+    let tokens = lexer.tokenize();
+    assert_eq!(tokens, tests);
+}
+
+#[test]
+fn test_pipeline_and_composition() {
+    let input = "x |> f(_) | g >> h << i < j > k";
+
+    let tests = vec![
+        Token::new(TokenKind::Ident,     "x"),
+        Token::new(TokenKind::Pipeline,   ""),
+        Token::new(TokenKind::Ident,     "f"),
+        Token::new(TokenKind::LParenthesis, ""),
+        Token::new(TokenKind::Underscore, ""),
+        Token::new(TokenKind::RParenthesis, ""),
+        Token::new(TokenKind::Pipe,       ""),
+        Token::new(TokenKind::Ident,     "g"),
+        Token::new(TokenKind::ComposeRight, ""),
+        Token::new(TokenKind::Ident,     "h"),
+        Token::new(TokenKind::ComposeLeft, ""),
+        Token::new(TokenKind::Ident,     "i"),
+        Token::new(TokenKind::LT,         ""),
+        Token::new(TokenKind::Ident,     "j"),
+        Token::new(TokenKind::GT,         ""),
+        Token::new(TokenKind::Ident,     "k"),
+        Token::new(TokenKind::Eof,        ""),
+    ];
+
+    let lexer  = Lexer::new(input);
+    let tokens = lexer.tokenize();
+    assert_eq!(tokens, tests);
+}
+
+#[test]
+fn test_enum_and_path() {
+    let input = "enum E { A(x) } E::A {a: 1}";
+
+    let tests = vec![
+        Token::new(TokenKind::Enum,       ""),
+        Token::new(TokenKind::Ident,     "E"),
+        Token::new(TokenKind::LCurlyBracket, ""),
+        Token::new(TokenKind::Ident,     "A"),
+        Token::new(TokenKind::LParenthesis, ""),
+        Token::new(TokenKind::Ident,     "x"),
+        Token::new(TokenKind::RParenthesis, ""),
+        Token::new(TokenKind::RCurlyBracket, ""),
+        Token::new(TokenKind::Ident,     "E"),
+        Token::new(TokenKind::PathSep,    ""),
+        Token::new(TokenKind::Ident,     "A"),
+        Token::new(TokenKind::LCurlyBracket, ""),
+        Token::new(TokenKind::Ident,     "a"),
+        Token::new(TokenKind::Colon,      ""),
+        Token::new(TokenKind::Int,       "1"),
+        Token::new(TokenKind::RCurlyBracket, ""),
+        Token::new(TokenKind::Eof,        ""),
+    ];
+
+    let lexer  = Lexer::new(input);
+    let tokens = lexer.tokenize();
+    assert_eq!(tokens, tests);
+}
+
+#[test]
+fn test_for_and_yield() {
+    let input = "for (x in xs) { yield x; } index";
+
+    let tests = vec![
+        Token::new(TokenKind::For,        ""),
+        Token::new(TokenKind::LParenthesis, ""),
+        Token::new(TokenKind::Ident,     "x"),
+        Token::new(TokenKind::In,         ""),
+        Token::new(TokenKind::Ident,     "xs"),
+        Token::new(TokenKind::RParenthesis, ""),
+        Token::new(TokenKind::LCurlyBracket, ""),
+        Token::new(TokenKind::Yield,      ""),
+        Token::new(TokenKind::Ident,     "x"),
+        Token::new(TokenKind::Semicolon,  ""),
+        Token::new(TokenKind::RCurlyBracket, ""),
+        Token::new(TokenKind::Ident,     "index"),
+        Token::new(TokenKind::Eof,        ""),
+    ];
+
+    let lexer  = Lexer::new(input);
+    let tokens = lexer.tokenize();
+    assert_eq!(tokens, tests);
+}
+
+#[test]
+fn test_set_operators() {
+    let input = "#{1} & s | t <= u >= v";
+
+    let tests = vec![
+        Token::new(TokenKind::SetStart,  ""),
+        Token::new(TokenKind::Int,      "1"),
+        Token::new(TokenKind::RCurlyBracket, ""),
+        Token::new(TokenKind::Ampersand, ""),
+        Token::new(TokenKind::Ident,    "s"),
+        Token::new(TokenKind::Pipe,      ""),
+        Token::new(TokenKind::Ident,    "t"),
+        Token::new(TokenKind::LtEq,      ""),
+        Token::new(TokenKind::Ident,    "u"),
+        Token::new(TokenKind::GtEq,      ""),
+        Token::new(TokenKind::Ident,    "v"),
+        Token::new(TokenKind::Eof,       ""),
+    ];
+
+    let lexer  = Lexer::new(input);
+    let tokens = lexer.tokenize();
+    assert_eq!(tokens, tests);
+}
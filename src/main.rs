@@ -5,9 +5,9 @@ mod parser;
 mod repl;
 mod eval;
 
-use std::io::{stdout, Write, stdin};
+use std::{io::{stdout, Write, stdin}, thread};
 
-use crate::repl::{Repl, ReplExecKind, ReplInputKind};
+use crate::{repl::{Repl, ReplExecKind, ReplInputKind, MAX_READ_ERRORS}, eval::STACK_SIZE};
 
 fn main() {
     let cin = stdin();
@@ -16,12 +16,24 @@ fn main() {
     println!("Which type of input do you want?");
     println!("  1. One line input");
     println!("  2. Input while ctrl-d");
+    let mut read_errors = 0;
     let kind_in = loop {
         print!("num of type: ");
-        stdout().flush().unwrap();
+        let _ = stdout().flush();
 
         let mut input = String::new();
-        cin.read_line(&mut input).unwrap();
+        match cin.read_line(&mut input) {
+            Ok(0) => return,
+            Ok(_) => read_errors = 0,
+            Err(e) => {
+                println!("Invalid input: {}", e);
+                read_errors += 1;
+                if read_errors >= MAX_READ_ERRORS {
+                    return;
+                }
+                continue;
+            }
+        }
         match input.trim().parse::<usize>() {
             Ok(value) => match value {
                 1 => break ReplInputKind::OneLine,
@@ -39,10 +51,21 @@ fn main() {
     println!("  4. Display evaluated value");
     let kind_exe = loop {
         print!("num of type: ");
-        stdout().flush().unwrap();
+        let _ = stdout().flush();
 
         let mut input = String::new();
-        cin.read_line(&mut input).unwrap();
+        match cin.read_line(&mut input) {
+            Ok(0) => return,
+            Ok(_) => read_errors = 0,
+            Err(e) => {
+                println!("Invalid input: {}", e);
+                read_errors += 1;
+                if read_errors >= MAX_READ_ERRORS {
+                    return;
+                }
+                continue;
+            }
+        }
         match input.trim().parse::<usize>() {
             Ok(value) => match value {
                 1 => break ReplExecKind::Token,
@@ -55,5 +78,13 @@ fn main() {
         }
     };
 
-    Repl::new(cin).start(kind_exe, kind_in);
+    let repl = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || Repl::new(cin).start(kind_exe, kind_in));
+    match repl {
+        Ok(handle) => {
+            let _ = handle.join();
+        }
+        Err(e) => eprintln!("Failed to start interpreter: {}", e),
+    }
 }
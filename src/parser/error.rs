@@ -11,4 +11,40 @@ pub enum ParseError {
 
     #[error("No such expression that start with {0:?}")]
     NoSuchExpressionStartWith(TokenKind),
+
+    #[error("Invalid target of assignment: {0}")]
+    InvalidAssignTarget(String),
+
+    #[error("Cannot assign to immutable binding {0}")]
+    ImmutableAssigned(String),
+
+    #[error("Cannot redefine immutable binding {0}")]
+    ImmutableRedefined(String),
+
+    #[error("Refutable pattern can only be used in match: {0}")]
+    RefutablePattern(String),
+
+    #[error("Not a constant expression: {0}")]
+    NotConstant(String),
+
+    #[error("yield used outside of function")]
+    YieldOutsideFunction,
+
+    #[error("yield can only be used in blocks, if and for statements of a generator")]
+    MisplacedYield,
+
+    #[error("{0} can only be used at the top level")]
+    NotTopLevel(String),
+
+    #[error("Only let, const, fn, struct and enum can be exported, found {0:?}")]
+    InvalidExport(TokenKind),
+
+    #[error("Expression is nested deeper than {0}")]
+    NestingTooDeep(usize),
+}
+
+#[derive(Debug, Error)]
+pub enum ParseWarning {
+    #[error("Unreachable match arm: {0}")]
+    UnreachableArm(String),
 }
@@ -9,29 +9,75 @@ use crate::{
     ast::{
         Program, Statement, LetStatement, Identifier, Expression, RetStatement, ExpStatement,
         Integer, PrefixExpression, InfixExpression, Boolean, IfExpression, BlkStatement,
-        FunctionExpression, CallExpression, StringLiteral, PostfixExpression
+        FunctionExpression, CallExpression, StringLiteral, PostfixExpression, NullLiteral,
+        ArrayLiteral, HashLiteral, IndexExpression, InterpolatedString, StringPart, Pattern,
+        MatchArm, MatchExpression, Node, Parameter, Argument, FuncStatement, Placeholder,
+        MethodCallExpression, StructStatement, StructLiteral, FieldExpression, AssignExpression,
+        EnumStatement, EnumVariant, PathExpression, ImplStatement, ThrowExpression, TryExpression,
+        DeferStatement, ConstStatement, ForStatement, YieldStatement, MacroLiteral, ImportStatement,
+        ExportStatement, TupleLiteral, SetLiteral, SliceExpression
     },
 };
-use self::{error::ParseError, order::PriorityOrder};
-use std::{cell::Cell, error::Error};
+use self::{error::{ParseError, ParseWarning}, order::PriorityOrder};
+use std::{cell::{Cell, RefCell}, collections::HashMap, error::Error};
+
+// Default maximum nesting of expressions. Parsing is recursive, so this keeps inputs such as
+// '((((...))))' from overflowing the host stack.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+// Bindings known while parsing, so that invalid assignments are reported before evaluation.
+// Names not found here, such as ones defined by an earlier REPL input, are checked at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binding {
+    Mutable,
+    Immutable,
+    Const,
+}
 
 pub struct Parser<'a> {
     token: Vec<Token<'a>>,
     curr:  Cell<usize>,
+    depth: Cell<usize>,
+    max_depth: usize,
+    warnings: RefCell<Vec<ParseWarning>>,
+    scopes: RefCell<Vec<HashMap<String, Binding>>>,
+    // Whether each function being parsed contains 'yield', which makes it a generator
+    yields: RefCell<Vec<bool>>,
+    // Whether 'yield' can be used here, which is in the body of a function and the blocks, 'if'
+    // and 'for' statements in it, since a generator is suspended only between them. The number
+    // of 'yield' parsed so far tells if a statement contains one.
+    yield_here: Cell<bool>,
+    yield_count: Cell<usize>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(token: Vec<Token>) -> Parser {
+        Parser::new_with_limit(token, DEFAULT_MAX_DEPTH)
+    }
+
+    pub fn new_with_limit(token: Vec<Token>, max_depth: usize) -> Parser {
         Parser {
             token,
             curr: Cell::new(0),
+            depth: Cell::new(0),
+            max_depth,
+            warnings: RefCell::new(Vec::new()),
+            scopes: RefCell::new(vec![HashMap::new()]),
+            yields: RefCell::new(Vec::new()),
+            yield_here: Cell::new(false),
+            yield_count: Cell::new(0),
         }
     }
 
+    // Warnings found while parsing, such as unreachable match arms
+    pub fn warnings(&self) -> Vec<String> {
+        self.warnings.borrow().iter().map(|warning| warning.to_string()).collect()
+    }
+
     pub fn parse(&self) -> Result<Program, Box<dyn Error>> {
         let mut ret = Program::new();
         while !self.curr_token_is(TokenKind::Eof)? {
-            ret.statements.push(self.statement()?);
+            ret.statements.push(self.top_statement()?);
             self.next_token();
         }
         Ok(ret)
@@ -39,32 +85,184 @@ impl<'a> Parser<'a> {
 }
 
 impl<'a> Parser<'a> {
+    // Statements of a program, where modules can be imported and bindings exported
+    fn top_statement(&self) -> Result<Statement, Box<dyn Error>> {
+        match self.curr_token()?.kind {
+            TokenKind::Import => Ok(Statement::Import(self.import_statement()?)),
+            TokenKind::Export => Ok(Statement::Export(self.export_statement()?)),
+            _                 => self.statement(),
+        }
+    }
+
     fn statement(&self) -> Result<Statement, Box<dyn Error>> {
         match self.curr_token()?.kind {
+            TokenKind::Import => Err(ParseError::NotTopLevel("import".to_string()))?,
+            TokenKind::Export => Err(ParseError::NotTopLevel("export".to_string()))?,
             TokenKind::Let    => Ok(Statement::Let(self.let_statement()?)),
             TokenKind::Return => Ok(Statement::Ret(self.ret_statement()?)),
+            TokenKind::Function if self.peek_token_is(TokenKind::Ident)? => {
+                // Methods of 'impl' are parsed alike, but don't bind their names
+                let stmt = self.func_statement()?;
+                self.define(&stmt.name.name, Binding::Immutable)?;
+                Ok(Statement::Func(stmt))
+            }
+            TokenKind::Struct => Ok(Statement::Struct(self.struct_statement()?)),
+            TokenKind::Enum => Ok(Statement::Enum(self.enum_statement()?)),
+            TokenKind::Impl => Ok(Statement::Impl(self.impl_statement()?)),
+            TokenKind::Defer => Ok(Statement::Defer(self.defer_statement()?)),
+            TokenKind::Const => Ok(Statement::Const(self.const_statement()?)),
+            TokenKind::For => Ok(Statement::For(self.for_statement()?)),
+            TokenKind::Yield => Ok(Statement::Yield(self.yield_statement()?)),
             _                 => self.exp_statement(),
         }
     }
 
+    // 'fn' identifier '(' parameters ')' '{' statements '}'
+    fn func_statement(&self) -> Result<FuncStatement, Box<dyn Error>> {
+        self.next_token();
+        let name = self.identifier()?;
+        let func = self.func_expression()?;
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        Ok(FuncStatement::new(name, func))
+    }
+
     fn let_statement(&self) -> Result<LetStatement, Box<dyn Error>> {
-        if !self.expect_peek(TokenKind::Ident)? {
-            let err = ParseError::InvalidTokenFound(vec![TokenKind::Ident], self.curr_token()?.kind);
-            Err(err)?;
+        let mutable = self.expect_peek(TokenKind::Mut)?;
+        self.next_token();
+        let pattern = self.binding_pattern()?;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::Assign)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::Assign], kind))?
+        }
+
+        self.next_token();
+        let left_exp = self.expression(PriorityOrder::Lowest)?;
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        let binding = if mutable { Binding::Mutable } else { Binding::Immutable };
+        for ident in pattern.bindings() {
+            self.define(&ident.name, binding)?;
         }
 
-        let name = self.curr_token()?.literal.to_string();
+        Ok(LetStatement::new(pattern, left_exp, mutable))
+    }
+
+    // 'const' identifier '=' expression ';'
+    fn const_statement(&self) -> Result<ConstStatement, Box<dyn Error>> {
+        self.next_token();
+        let name = self.identifier()?;
 
+        let kind = self.peek_token()?.kind;
         if !self.expect_peek(TokenKind::Assign)? {
-            let err = ParseError::InvalidTokenFound(vec![TokenKind::Ident], self.curr_token()?.kind);
-            Err(err)?;
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::Assign], kind))?
         }
 
         self.next_token();
-        let left_exp = self.expression(PriorityOrder::Lowest)?;
+        let value = self.expression(PriorityOrder::Lowest)?;
+        if !self.is_constant(&value) {
+            Err(ParseError::NotConstant(value.string()))?
+        }
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        self.define(&name.name, Binding::Const)?;
+        Ok(ConstStatement::new(name, value))
+    }
+
+    // 'struct' identifier '{' identifier, ... '}'
+    fn struct_statement(&self) -> Result<StructStatement, Box<dyn Error>> {
+        self.next_token();
+        let name = self.identifier()?;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LCurlyBracket)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+        }
+
+        let mut fields = Vec::new();
+        while !self.expect_peek(TokenKind::RCurlyBracket)? {
+            self.next_token();
+            fields.push(self.identifier()?);
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RCurlyBracket)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RCurlyBracket], kind))?
+            }
+        }
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        self.define(&name.name, Binding::Immutable)?;
+        Ok(StructStatement::new(name, fields))
+    }
+
+    // 'enum' identifier '{' identifier ('(' identifier, ... ')')?, ... '}'
+    fn enum_statement(&self) -> Result<EnumStatement, Box<dyn Error>> {
+        self.next_token();
+        let name = self.identifier()?;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LCurlyBracket)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+        }
+
+        let mut variants = Vec::new();
+        while !self.expect_peek(TokenKind::RCurlyBracket)? {
+            self.next_token();
+            let variant = self.identifier()?;
+
+            let mut fields = None;
+            if self.expect_peek(TokenKind::LParenthesis)? {
+                let mut idents = Vec::new();
+                while !self.expect_peek(TokenKind::RParenthesis)? {
+                    self.next_token();
+                    idents.push(self.identifier()?);
+
+                    let kind = self.peek_token()?.kind;
+                    if !self.peek_token_is(TokenKind::RParenthesis)? && !self.expect_peek(TokenKind::Comma)? {
+                        Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RParenthesis], kind))?
+                    }
+                }
+                fields = Some(idents);
+            }
+            variants.push(EnumVariant::new(variant, fields));
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RCurlyBracket)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RCurlyBracket], kind))?
+            }
+        }
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        self.define(&name.name, Binding::Immutable)?;
+        Ok(EnumStatement::new(name, variants))
+    }
+
+    // 'impl' identifier '{' ('fn' identifier '(' parameters ')' '{' statements '}')* '}'
+    fn impl_statement(&self) -> Result<ImplStatement, Box<dyn Error>> {
+        self.next_token();
+        let ty = self.identifier()?;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LCurlyBracket)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+        }
+
+        let mut funcs = Vec::new();
+        while !self.expect_peek(TokenKind::RCurlyBracket)? {
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::Function)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Function, TokenKind::RCurlyBracket], kind))?
+            }
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::Ident)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Ident], kind))?
+            }
+            funcs.push(self.func_statement()?);
+        }
         self.expect_peek(TokenKind::Semicolon)?;
 
-        Ok(LetStatement::new(Identifier::new(name), left_exp))
+        Ok(ImplStatement::new(ty, funcs))
     }
 
     fn ret_statement(&self) -> Result<RetStatement, Box<dyn Error>> {
@@ -75,35 +273,173 @@ impl<'a> Parser<'a> {
         Ok(RetStatement::new(exp))
     }
 
-    fn exp_statement(&self) -> Result<Statement, Box<dyn Error>> {
+    fn defer_statement(&self) -> Result<DeferStatement, Box<dyn Error>> {
+        self.next_token();
+        let exp = self.expression(PriorityOrder::Lowest)?;
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        Ok(DeferStatement::new(exp))
+    }
+
+    // 'for' '(' pattern 'in' expression ')' '{' statements '}'
+    fn for_statement(&self) -> Result<ForStatement, Box<dyn Error>> {
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LParenthesis)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LParenthesis], kind))?
+        }
+        self.next_token();
+        let pattern = self.binding_pattern()?;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::In)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::In], kind))?
+        }
+        self.next_token();
+        let iterable = self.expression(PriorityOrder::Lowest)?;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::RParenthesis)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::RParenthesis], kind))?
+        }
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LCurlyBracket)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+        }
+
+        // Bindings of the pattern are mutable like the ones of match arms
+        let scope = pattern.bindings().into_iter().map(|ident| (ident.name.clone(), Binding::Mutable));
+        self.scopes.borrow_mut().push(scope.collect());
+        let body = self.blk_statement();
+        self.scopes.borrow_mut().pop();
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        Ok(ForStatement::new(pattern, iterable, body?))
+    }
+
+    fn yield_statement(&self) -> Result<YieldStatement, Box<dyn Error>> {
+        match self.yields.borrow_mut().last_mut() {
+            Some(yields) => *yields = true,
+            None => Err(ParseError::YieldOutsideFunction)?,
+        }
+        if !self.yield_here.get() {
+            Err(ParseError::MisplacedYield)?
+        }
+        self.yield_count.set(self.yield_count.get() + 1);
+        self.next_token();
         let exp = self.expression(PriorityOrder::Lowest)?;
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        Ok(YieldStatement::new(exp))
+    }
+
+    // 'import' string 'as' identifier ';'
+    fn import_statement(&self) -> Result<ImportStatement, Box<dyn Error>> {
+        self.next_token();
+        let path = self.string_literal()?.str;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::As)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::As], kind))?
+        }
+        self.next_token();
+        let name = self.identifier()?;
+        self.expect_peek(TokenKind::Semicolon)?;
+
+        self.define(&name.name, Binding::Immutable)?;
+        Ok(ImportStatement::new(path, name))
+    }
+
+    // 'export' followed by a statement defining names
+    fn export_statement(&self) -> Result<ExportStatement, Box<dyn Error>> {
+        self.next_token();
+        match self.curr_token()?.kind {
+            TokenKind::Let | TokenKind::Const | TokenKind::Struct | TokenKind::Enum => {}
+            TokenKind::Function if self.peek_token_is(TokenKind::Ident)? => {}
+            kind => Err(ParseError::InvalidExport(kind))?,
+        }
+        Ok(ExportStatement::new(self.statement()?))
+    }
+
+    fn exp_statement(&self) -> Result<Statement, Box<dyn Error>> {
+        // 'if' and a block as a statement can contain 'yield', unless operators follow them
+        let yield_count = self.yield_count.get();
+        let mut exp = match self.curr_token()?.kind {
+            TokenKind::If | TokenKind::LCurlyBracket => self.bounded_expression(PriorityOrder::Lowest)?,
+            _ => self.expression(PriorityOrder::Lowest)?,
+        };
+        if self.peek_token_is(TokenKind::Comma)? {
+            exp = Expression::Assign(Box::new(self.multiple_assign(exp)?));
+        }
+        if !matches!(exp, Expression::If(_) | Expression::Blk(_)) && self.yield_count.get() != yield_count {
+            Err(ParseError::MisplacedYield)?
+        }
 
         self.expect_peek(TokenKind::Semicolon)?;
-        Ok(Statement::Exp(ExpStatement::new(exp)))
+        Ok(Parser::exp_to_statement(exp))
+    }
+
+    // A standalone block expression is a block statement
+    fn exp_to_statement(exp: Expression) -> Statement {
+        match exp {
+            Expression::Blk(blk) => Statement::Blk(blk),
+            exp => Statement::Exp(ExpStatement::new(exp)),
+        }
     }
 
     fn blk_statement(&self) -> Result<BlkStatement, Box<dyn Error>> {
         self.next_token();
+        self.blk_rest(Vec::new())
+    }
 
-        let mut ret = Vec::new();
+    // Statements from the current token to '}', following the already parsed ones
+    fn blk_rest(&self, mut ret: Vec<Statement>) -> Result<BlkStatement, Box<dyn Error>> {
+        self.scopes.borrow_mut().push(HashMap::new());
         while !self.curr_token_is(TokenKind::RCurlyBracket)? {
             let stmt = self.statement()?;
             ret.push(stmt);
             self.next_token();
         }
+        self.scopes.borrow_mut().pop();
 
         Ok(BlkStatement::new(ret))
     }
 
+    // 'yield' can't be used within expressions, such as a match arm or a block as a value
     fn expression(&self, order: PriorityOrder) -> Result<Expression, Box<dyn Error>> {
-        let mut left = match self.curr_token()?.kind {
+        let yield_here = self.yield_here.replace(false);
+        let ret = self.bounded_expression(order);
+        self.yield_here.set(yield_here);
+        ret
+    }
+
+    fn bounded_expression(&self, order: PriorityOrder) -> Result<Expression, Box<dyn Error>> {
+        if self.depth.get() >= self.max_depth {
+            Err(ParseError::NestingTooDeep(self.max_depth))?
+        }
+
+        self.depth.set(self.depth.get() + 1);
+        let ret = self.nested_expression(order);
+        self.depth.set(self.depth.get() - 1);
+        ret
+    }
+
+    fn nested_expression(&self, order: PriorityOrder) -> Result<Expression, Box<dyn Error>> {
+        let left = match self.curr_token()?.kind {
+            TokenKind::Ident if self.is_struct_literal()? => Expression::Struct(self.struct_literal()?),
+            TokenKind::Ident if self.is_path()? => Expression::Path(self.path()?),
             TokenKind::Ident => Expression::Ident(self.identifier()?),
             TokenKind::Int   => Expression::Int(self.integer()?),
             TokenKind::Str   => Expression::Str(self.string_literal()?),
+            TokenKind::InterpStart => Expression::Interp(self.interpolated_string()?),
             TokenKind::True
             | TokenKind::False => Expression::Bool(self.boolean()?),
+            TokenKind::Null    => Expression::Null(NullLiteral::new()),
+            TokenKind::Underscore => Expression::Placeholder(Placeholder::new()),
 
-            TokenKind::LParenthesis => self.group()?,
+            TokenKind::LParenthesis   => self.group()?,
+            TokenKind::LSquareBracket => Expression::Array(self.array_literal()?),
+            TokenKind::SetStart       => Expression::Set(self.set_literal()?),
+            TokenKind::LCurlyBracket  => self.hash_or_blk()?,
 
             TokenKind::Bang 
             | TokenKind::Minus
@@ -112,12 +448,34 @@ impl<'a> Parser<'a> {
 
             TokenKind::If       => Expression::If(self.if_expression()?),
             TokenKind::Function => Expression::Func(self.func_expression()?),
+            TokenKind::Macro    => Expression::Macro(self.macro_literal()?),
+            TokenKind::Match    => Expression::Match(self.match_expression()?),
+            TokenKind::Throw    => Expression::Throw(self.throw_expression()?),
+            TokenKind::Try      => Expression::Try(self.try_expression()?),
 
             kind => Err(ParseError::NoSuchExpressionStartWith(kind))?,
         };
 
+        // Postfix links nest the expression as deep as parentheses do, so they're counted in the
+        // depth until the whole expression is parsed.
+        let depth = self.depth.get();
+        let ret = self.operators(left, order);
+        self.depth.set(depth);
+        ret
+    }
+
+    // Operators following the left hand side which bind tighter than the order
+    fn operators(&self, mut left: Expression, order: PriorityOrder) -> Result<Expression, Box<dyn Error>> {
         while !self.peek_token_is(TokenKind::Semicolon)? && order < self.peek_order()? {
-            match self.peek_token()?.kind {
+            let kind = self.peek_token()?.kind;
+            if Parser::is_link(kind) {
+                if self.depth.get() >= self.max_depth {
+                    Err(ParseError::NestingTooDeep(self.max_depth))?
+                }
+                self.depth.set(self.depth.get() + 1);
+            }
+
+            match kind {
                 TokenKind::Plus
                 | TokenKind::Minus
                 | TokenKind::Asterisk
@@ -125,15 +483,40 @@ impl<'a> Parser<'a> {
                 | TokenKind::Eq
                 | TokenKind::NotEq
                 | TokenKind::LT
-                | TokenKind::GT => {
+                | TokenKind::GT
+                | TokenKind::LtEq
+                | TokenKind::GtEq
+                | TokenKind::In
+                | TokenKind::Pipe
+                | TokenKind::Ampersand
+                | TokenKind::Coalesce
+                | TokenKind::Pipeline
+                | TokenKind::ComposeRight
+                | TokenKind::ComposeLeft => {
                     self.next_token();
                     left = Expression::Infix(Box::new(self.infix(left)?));
                 }
+                TokenKind::Assign => {
+                    self.next_token();
+                    left = Expression::Assign(Box::new(self.assign(left)?));
+                }
                 TokenKind::LParenthesis => {
                     self.next_token();
-                    left = Expression::Call(self.call_expression(left)?);
+                    left = Expression::Call(self.call_expression(left, false)?);
                 }
-                TokenKind::Inc | TokenKind::Dec => {
+                TokenKind::LSquareBracket => {
+                    self.next_token();
+                    left = self.index_expression(left, false)?;
+                }
+                TokenKind::OptChain => {
+                    self.next_token();
+                    left = self.optional_chain(left)?;
+                }
+                TokenKind::Dot => {
+                    self.next_token();
+                    left = self.member(left, false)?;
+                }
+                TokenKind::Inc | TokenKind::Dec | TokenKind::Question => {
                     self.next_token();
                     left = Expression::Postfix(Box::new(self.postfix(left)?));
                 }
@@ -173,7 +556,38 @@ impl<'a> Parser<'a> {
             _ => Err(ParseError::InvalidTokenFound(vec![TokenKind::Str], token.kind))?,
         };
 
-        Ok(StringLiteral::new(value.to_string()))
+        Ok(StringLiteral::new(Parser::unescape(value)))
+    }
+
+    // InterpStart expression (InterpMid expression)* InterpEnd
+    fn interpolated_string(&self) -> Result<InterpolatedString, Box<dyn Error>> {
+        let mut parts = vec![StringPart::Text(Parser::unescape(self.curr_token()?.literal()))];
+
+        loop {
+            self.next_token();
+            parts.push(StringPart::Exp(self.expression(PriorityOrder::Lowest)?));
+
+            self.next_token();
+            let token = self.curr_token()?;
+            match token.kind {
+                TokenKind::InterpMid => {
+                    parts.push(StringPart::Text(Parser::unescape(token.literal())));
+                }
+                TokenKind::InterpEnd => {
+                    parts.push(StringPart::Text(Parser::unescape(token.literal())));
+                    break;
+                }
+                kind => {
+                    Err(
+                        ParseError::InvalidTokenFound(
+                            vec![TokenKind::InterpMid, TokenKind::InterpEnd], kind
+                        )
+                    )?
+                }
+            }
+        }
+
+        Ok(InterpolatedString::new(parts))
     }
 
     fn boolean(&self) -> Result<Boolean, Box<dyn Error>> {
@@ -209,6 +623,74 @@ impl<'a> Parser<'a> {
         Ok(InfixExpression::new(operator, left, right))
     }
 
+    // The variable which holds the updated value, such as 'a' of 'a.b[0] = 1'
+    fn assigned_variable(target: &Expression) -> Option<&Identifier> {
+        match target {
+            Expression::Ident(ident) => Some(ident),
+            Expression::Field(field) => Parser::assigned_variable(&field.lhs_exp),
+            Expression::Index(index) => Parser::assigned_variable(&index.lhs_exp),
+            _ => None,
+        }
+    }
+
+    // target '=' expression, which is right associative
+    fn assign(&self, target: Expression) -> Result<AssignExpression, Box<dyn Error>> {
+        self.check_assign_target(&target)?;
+
+        self.next_token();
+        let rhs_exp = self.expression(PriorityOrder::Lowest)?;
+
+        Ok(AssignExpression::new(target, rhs_exp))
+    }
+
+    // Targets are variables, fields and indexes, or tuples of them
+    fn check_assign_target(&self, target: &Expression) -> Result<(), ParseError> {
+        match target {
+            Expression::Ident(_) => (),
+            Expression::Field(field) if !field.optional => (),
+            Expression::Index(index) if !index.optional => (),
+            Expression::Tuple(tuple) => {
+                return tuple.elements.iter().try_for_each(|elem| self.check_assign_target(elem));
+            }
+            _ => return Err(ParseError::InvalidAssignTarget(target.string())),
+        }
+        if let Some(ident) = Parser::assigned_variable(target) {
+            if matches!(self.resolve(&ident.name), Some(Binding::Immutable | Binding::Const)) {
+                return Err(ParseError::ImmutableAssigned(ident.name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    // target, ... '=' expression, ... assigns the elements of a tuple to the targets. All values are
+    // evaluated before assigning, so 'a, b = b, a' swaps them.
+    fn multiple_assign(&self, first: Expression) -> Result<AssignExpression, Box<dyn Error>> {
+        let mut targets = vec![first];
+        while self.expect_peek(TokenKind::Comma)? {
+            self.next_token();
+            targets.push(self.expression(PriorityOrder::Assign)?);
+        }
+        let target = Expression::Tuple(TupleLiteral::new(targets));
+        self.check_assign_target(&target)?;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::Assign)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::Assign], kind))?
+        }
+        self.next_token();
+        let mut values = vec![self.expression(PriorityOrder::Lowest)?];
+        while self.expect_peek(TokenKind::Comma)? {
+            self.next_token();
+            values.push(self.expression(PriorityOrder::Lowest)?);
+        }
+        let rhs_exp = match values.len() {
+            1 => values.remove(0),
+            _ => Expression::Tuple(TupleLiteral::new(values)),
+        };
+
+        Ok(AssignExpression::new(target, rhs_exp))
+    }
+
     // expression 'op'
     fn postfix(&self, left: Expression) -> Result<PostfixExpression, Box<dyn Error>> {
         let operator = self.curr_token()?.kind;
@@ -216,18 +698,35 @@ impl<'a> Parser<'a> {
         Ok(PostfixExpression::new(operator, left))
     }
 
-    // '(' expression ')''
+    // '(' expression ')' groups the expression, and '(' expression ',' ... ')' or '()' is a tuple
     fn group(&self) -> Result<Expression, Box<dyn Error>> {
+        if self.expect_peek(TokenKind::RParenthesis)? {
+            return Ok(Expression::Tuple(TupleLiteral::new(Vec::new())));
+        }
         self.next_token();
 
         let exp = self.expression(PriorityOrder::Lowest)?;
+        if !self.peek_token_is(TokenKind::Comma)? {
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::RParenthesis)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::RParenthesis], kind))?
+            }
+            return Ok(exp);
+        }
 
+        let mut elements = vec![exp];
+        while self.expect_peek(TokenKind::Comma)? {
+            if self.peek_token_is(TokenKind::RParenthesis)? {
+                break;
+            }
+            self.next_token();
+            elements.push(self.expression(PriorityOrder::Lowest)?);
+        }
         let kind = self.peek_token()?.kind;
-        if self.expect_peek(TokenKind::RParenthesis)? {
-            Ok(exp)
-        } else {
-            Err(ParseError::InvalidTokenFound(vec![TokenKind::RParenthesis], kind))?
+        if !self.expect_peek(TokenKind::RParenthesis)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RParenthesis], kind))?
         }
+        Ok(Expression::Tuple(TupleLiteral::new(elements)))
     }
 
     fn if_expression(&self) -> Result<IfExpression, Box<dyn Error>> {
@@ -264,58 +763,710 @@ impl<'a> Parser<'a> {
         Ok(IfExpression::new(cond, cons, alt))
     }
 
-    fn func_expression(&self) -> Result<FunctionExpression, Box<dyn Error>> {
-        let kind = self.peek_token()?.kind;
-        if !self.expect_peek(TokenKind::LParenthesis)? {
-            Err(ParseError::InvalidTokenFound(vec![TokenKind::LParenthesis], kind))?
-        }
-
-        let params = self.func_paramators()?;
+    // 'throw' expression
+    fn throw_expression(&self) -> Result<ThrowExpression, Box<dyn Error>> {
+        self.next_token();
+        Ok(ThrowExpression::new(self.expression(PriorityOrder::Lowest)?))
+    }
 
+    // 'try' '{' statements '}' ('catch' '(' identifier ')' '{' statements '}')? ('finally' '{' statements '}')?
+    fn try_expression(&self) -> Result<TryExpression, Box<dyn Error>> {
         let kind = self.peek_token()?.kind;
         if !self.expect_peek(TokenKind::LCurlyBracket)? {
             Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
         }
-
         let body = self.blk_statement()?;
 
-        Ok(FunctionExpression::new(params, body))
-    }
-
-    fn func_paramators(&self) -> Result<Vec<Identifier>, Box<dyn Error>> {
-        let mut ret = Vec::new();
+        let catch = if self.expect_peek(TokenKind::Catch)? {
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::LParenthesis)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::LParenthesis], kind))?
+            }
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::Ident)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Ident], kind))?
+            }
+            let ident = self.identifier()?;
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::RParenthesis)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::RParenthesis], kind))?
+            }
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::LCurlyBracket)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+            }
+            self.scopes.borrow_mut().push(HashMap::from([(ident.name.clone(), Binding::Mutable)]));
+            let blk = self.blk_statement();
+            self.scopes.borrow_mut().pop();
+            Some((ident, blk?))
+        } else {
+            None
+        };
 
-        if self.peek_token_is(TokenKind::RParenthesis)? {
-            self.next_token();
-            return Ok(ret);
+        let finally = if self.expect_peek(TokenKind::Finally)? {
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::LCurlyBracket)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+            }
+            Some(self.blk_statement()?)
         } else {
-            self.next_token();
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            let kind = self.peek_token()?.kind;
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::Catch, TokenKind::Finally], kind))?
         }
 
-        ret.push(self.identifier()?);
-        while self.peek_token_is(TokenKind::Comma)? {
-            self.next_token();
-            self.next_token();
-            ret.push(self.identifier()?);
+        Ok(TryExpression::new(body, catch, finally))
+    }
+
+    // 'match' '(' expression ')' '{' arm, ... '}'
+    fn match_expression(&self) -> Result<MatchExpression, Box<dyn Error>> {
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LParenthesis)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LParenthesis], kind))?
         }
 
+        self.next_token();
+        let subject = self.expression(PriorityOrder::Lowest)?;
+
         let kind = self.peek_token()?.kind;
         if !self.expect_peek(TokenKind::RParenthesis)? {
             Err(ParseError::InvalidTokenFound(vec![TokenKind::RParenthesis], kind))?
+        }
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LCurlyBracket)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+        }
+
+        let mut arms: Vec<MatchArm> = Vec::new();
+        while !self.expect_peek(TokenKind::RCurlyBracket)? {
+            self.next_token();
+            let arm = self.match_arm()?;
+
+            let covered = arms
+                .iter()
+                .filter(|prev| prev.guard.is_none())
+                .any(|prev| prev.pattern.covers(&arm.pattern));
+            if covered {
+                self.warnings.borrow_mut().push(ParseWarning::UnreachableArm(arm.pattern.string()));
+            }
+            arms.push(arm);
+
+            self.expect_peek(TokenKind::Comma)?;
+        }
+
+        Ok(MatchExpression::new(subject, arms))
+    }
+
+    // pattern ('if' expression)? '=>' (expression | block)
+    fn match_arm(&self) -> Result<MatchArm, Box<dyn Error>> {
+        let pattern = self.pattern()?;
+
+        let scope = pattern.bindings().into_iter().map(|ident| (ident.name.clone(), Binding::Mutable));
+        self.scopes.borrow_mut().push(scope.collect());
+        let ret = self.match_arm_body(pattern);
+        self.scopes.borrow_mut().pop();
+        ret
+    }
+
+    // ('if' expression)? '=>' (expression | '{' statements '}')
+    fn match_arm_body(&self, pattern: Pattern) -> Result<MatchArm, Box<dyn Error>> {
+        let guard = if self.expect_peek(TokenKind::If)? {
+            self.next_token();
+            Some(self.expression(PriorityOrder::Lowest)?)
         } else {
-            Ok(ret)
+            None
+        };
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::FatArrow)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::FatArrow], kind))?
+        }
+
+        let body = if self.expect_peek(TokenKind::LCurlyBracket)? {
+            self.blk_statement()?
+        } else {
+            self.next_token();
+            let exp = self.expression(PriorityOrder::Lowest)?;
+            BlkStatement::new(vec![Statement::Exp(ExpStatement::new(exp))])
+        };
+
+        Ok(MatchArm::new(pattern, guard, body))
+    }
+
+    // Pattern of 'let', parameters and 'for', which can't compare the value with literals
+    fn binding_pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        let pattern = self.pattern()?;
+        if !pattern.is_destructuring() {
+            Err(ParseError::RefutablePattern(pattern.string()))?
         }
+        Ok(pattern)
     }
 
-    fn call_expression(&self, left: Expression) -> Result<CallExpression, Box<dyn Error>> {
+    // Patterns nest like expressions, so they share the depth
+    fn pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        if self.depth.get() >= self.max_depth {
+            Err(ParseError::NestingTooDeep(self.max_depth))?
+        }
+
+        self.depth.set(self.depth.get() + 1);
+        let ret = self.nested_pattern();
+        self.depth.set(self.depth.get() - 1);
+        ret
+    }
+
+    // pattern ('|' pattern)*
+    fn nested_pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        let first = self.single_pattern()?;
+        if !self.peek_token_is(TokenKind::Pipe)? {
+            return Ok(first);
+        }
+
+        let mut patterns = vec![first];
+        while self.expect_peek(TokenKind::Pipe)? {
+            self.next_token();
+            patterns.push(self.single_pattern()?);
+        }
+        Ok(Pattern::Or(patterns))
+    }
+
+    fn single_pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        match self.curr_token()?.kind {
+            TokenKind::Underscore => Ok(Pattern::Wildcard),
+            TokenKind::Ident if self.peek_token_is(TokenKind::PathSep)? => self.variant_pattern(),
+            TokenKind::Ident      => Ok(Pattern::Binding(self.identifier()?)),
+            TokenKind::Str        => Ok(Pattern::Literal(Expression::Str(self.string_literal()?))),
+            TokenKind::True
+            | TokenKind::False    => Ok(Pattern::Literal(Expression::Bool(self.boolean()?))),
+            TokenKind::Null       => Ok(Pattern::Literal(Expression::Null(NullLiteral::new()))),
+            TokenKind::LSquareBracket => self.array_pattern(),
+            TokenKind::LParenthesis   => self.tuple_pattern(),
+            TokenKind::LCurlyBracket  => self.hash_pattern(),
+            TokenKind::Int
+            | TokenKind::Minus    => {
+                let start = self.pattern_integer()?;
+                let inclusive = match self.peek_token()?.kind {
+                    TokenKind::DotDot   => false,
+                    TokenKind::DotDotEq => true,
+                    _ => return Ok(Pattern::Literal(Expression::Int(start))),
+                };
+                self.next_token();
+                self.next_token();
+                let end = self.pattern_integer()?;
+                Ok(Pattern::Range(start, end, inclusive))
+            }
+            kind => {
+                Err(
+                    ParseError::InvalidTokenFound(
+                        vec![
+                            TokenKind::Underscore, TokenKind::Ident, TokenKind::Int,
+                            TokenKind::Str, TokenKind::True, TokenKind::False, TokenKind::Null,
+                            TokenKind::LSquareBracket, TokenKind::LParenthesis, TokenKind::LCurlyBracket,
+                        ],
+                        kind
+                    )
+                )?
+            }
+        }
+    }
+
+    // path ('(' pattern, ... ')')?
+    fn variant_pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        let path = self.path()?;
+        if !self.expect_peek(TokenKind::LParenthesis)? {
+            return Ok(Pattern::Variant(path, None));
+        }
+
+        let mut patterns = Vec::new();
+        while !self.expect_peek(TokenKind::RParenthesis)? {
+            self.next_token();
+            patterns.push(self.pattern()?);
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RParenthesis)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RParenthesis], kind))?
+            }
+        }
+        Ok(Pattern::Variant(path, Some(patterns)))
+    }
+
+    // '[' pattern, ... (',' '...' rest)? ']'
+    fn array_pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        let mut patterns = Vec::new();
+        let mut rest = None;
+
+        while !self.expect_peek(TokenKind::RSquareBracket)? {
+            self.next_token();
+            if self.curr_token_is(TokenKind::Ellipsis)? {
+                rest = Some(Box::new(self.rest_pattern()?));
+                let kind = self.peek_token()?.kind;
+                if !self.expect_peek(TokenKind::RSquareBracket)? {
+                    Err(ParseError::InvalidTokenFound(vec![TokenKind::RSquareBracket], kind))?
+                }
+                break;
+            }
+            patterns.push(self.pattern()?);
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RSquareBracket)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RSquareBracket], kind))?
+            }
+        }
+
+        Ok(Pattern::Array(patterns, rest))
+    }
+
+    // '(' pattern, ... ')' where '(pattern)' only groups the pattern
+    fn tuple_pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        let mut patterns = Vec::new();
+        let mut trailing_comma = false;
+
+        while !self.expect_peek(TokenKind::RParenthesis)? {
+            self.next_token();
+            patterns.push(self.pattern()?);
+
+            let kind = self.peek_token()?.kind;
+            trailing_comma = self.expect_peek(TokenKind::Comma)?;
+            if !trailing_comma && !self.peek_token_is(TokenKind::RParenthesis)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RParenthesis], kind))?
+            }
+        }
+
+        match (patterns.len(), trailing_comma) {
+            (1, false) => Ok(patterns.remove(0)),
+            _ => Ok(Pattern::Tuple(patterns)),
+        }
+    }
+
+    // '{' key (':' pattern)?, ... (',' '...' rest)? '}'
+    fn hash_pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        let mut pairs = Vec::new();
+        let mut rest = None;
+
+        while !self.expect_peek(TokenKind::RCurlyBracket)? {
+            self.next_token();
+            if self.curr_token_is(TokenKind::Ellipsis)? {
+                rest = Some(Box::new(self.rest_pattern()?));
+                let kind = self.peek_token()?.kind;
+                if !self.expect_peek(TokenKind::RCurlyBracket)? {
+                    Err(ParseError::InvalidTokenFound(vec![TokenKind::RCurlyBracket], kind))?
+                }
+                break;
+            }
+
+            let token = self.curr_token()?;
+            let key = match token.kind {
+                TokenKind::Ident | TokenKind::Str => token.literal().to_string(),
+                kind => Err(ParseError::InvalidTokenFound(vec![TokenKind::Ident, TokenKind::Str], kind))?,
+            };
+            if self.expect_peek(TokenKind::Colon)? {
+                self.next_token();
+                pairs.push((key, self.pattern()?));
+            } else if token.kind == TokenKind::Ident {
+                pairs.push((key.clone(), Pattern::Binding(Identifier::new(key))));
+            } else {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Colon], self.peek_token()?.kind))?
+            }
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RCurlyBracket)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RCurlyBracket], kind))?
+            }
+        }
+
+        Ok(Pattern::Hash(pairs, rest))
+    }
+
+    // '...' identifier or '...' alone which ignores the rest
+    fn rest_pattern(&self) -> Result<Pattern, Box<dyn Error>> {
+        match self.peek_token()?.kind {
+            TokenKind::Ident => {
+                self.next_token();
+                Ok(Pattern::Binding(self.identifier()?))
+            }
+            TokenKind::Underscore => {
+                self.next_token();
+                Ok(Pattern::Wildcard)
+            }
+            _ => Ok(Pattern::Wildcard),
+        }
+    }
+
+    // Integer with optional '-'
+    fn pattern_integer(&self) -> Result<Integer, Box<dyn Error>> {
+        if self.curr_token_is(TokenKind::Minus)? {
+            self.next_token();
+            let int = self.integer()?;
+            Ok(Integer::new(-int.value))
+        } else {
+            self.integer()
+        }
+    }
+
+    fn func_expression(&self) -> Result<FunctionExpression, Box<dyn Error>> {
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LParenthesis)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LParenthesis], kind))?
+        }
+
+        let (params, rest) = self.func_paramators()?;
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LCurlyBracket)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+        }
+
+        // Parameters are mutable bindings in the scope of the body
+        let mut scope = HashMap::new();
+        for ident in params.iter().flat_map(|param| param.pattern.bindings()).chain(rest.as_ref()) {
+            scope.insert(ident.name.clone(), Binding::Mutable);
+        }
+        self.scopes.borrow_mut().push(scope);
+        self.yields.borrow_mut().push(false);
+        let yield_here = self.yield_here.replace(true);
+        let body = self.blk_statement();
+        self.yield_here.set(yield_here);
+        let generator = self.yields.borrow_mut().pop().unwrap_or_default();
+        self.scopes.borrow_mut().pop();
+
+        Ok(FunctionExpression::new(params, rest, body?, generator))
+    }
+
+    // 'macro' '(' identifier, ... ')' '{' statements '}'
+    fn macro_literal(&self) -> Result<MacroLiteral, Box<dyn Error>> {
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LParenthesis)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LParenthesis], kind))?
+        }
+
+        let mut params = Vec::new();
+        while !self.expect_peek(TokenKind::RParenthesis)? {
+            self.next_token();
+            params.push(self.identifier()?);
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RParenthesis)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RParenthesis], kind))?
+            }
+        }
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::LCurlyBracket)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::LCurlyBracket], kind))?
+        }
+
+        let scope = params.iter().map(|ident| (ident.name.clone(), Binding::Mutable));
+        self.scopes.borrow_mut().push(scope.collect());
+        let body = self.blk_statement();
+        self.scopes.borrow_mut().pop();
+
+        Ok(MacroLiteral::new(params, body?))
+    }
+
+    // (pattern ('=' expression)?), ... (',' '...' identifier)?
+    fn func_paramators(&self) -> Result<(Vec<Parameter>, Option<Identifier>), Box<dyn Error>> {
+        let mut ret = Vec::new();
+        let mut rest = None;
+
+        while !self.expect_peek(TokenKind::RParenthesis)? {
+            self.next_token();
+            if self.curr_token_is(TokenKind::Ellipsis)? {
+                self.next_token();
+                rest = Some(self.identifier()?);
+
+                let kind = self.peek_token()?.kind;
+                if !self.expect_peek(TokenKind::RParenthesis)? {
+                    Err(ParseError::InvalidTokenFound(vec![TokenKind::RParenthesis], kind))?
+                }
+                break;
+            }
+
+            let pattern = self.binding_pattern()?;
+            let default = if self.expect_peek(TokenKind::Assign)? {
+                self.next_token();
+                Some(self.expression(PriorityOrder::Lowest)?)
+            } else {
+                None
+            };
+            ret.push(Parameter::new(pattern, default));
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RParenthesis)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RParenthesis], kind))?
+            }
+        }
+
+        Ok((ret, rest))
+    }
+
+    fn call_expression(&self, left: Expression, optional: bool) -> Result<CallExpression, Box<dyn Error>> {
         let args = self.call_arguments()?;
-        Ok(CallExpression::new(left, args))
+        Ok(CallExpression::new(left, args, optional))
+    }
+
+    // (expression | identifier ':' expression | '...' expression), ... ')'
+    fn call_arguments(&self) -> Result<Vec<Argument>, Box<dyn Error>> {
+        let mut ret = Vec::new();
+
+        while !self.expect_peek(TokenKind::RParenthesis)? {
+            self.next_token();
+            let arg = match self.curr_token()?.kind {
+                TokenKind::Ellipsis => {
+                    self.next_token();
+                    Argument::Spread(self.expression(PriorityOrder::Lowest)?)
+                }
+                TokenKind::Ident if self.peek_token_is(TokenKind::Colon)? => {
+                    let name = self.identifier()?;
+                    self.next_token();
+                    self.next_token();
+                    Argument::Keyword(name, self.expression(PriorityOrder::Lowest)?)
+                }
+                _ => Argument::Positional(self.expression(PriorityOrder::Lowest)?),
+            };
+            ret.push(arg);
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RParenthesis)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RParenthesis], kind))?
+            }
+        }
+
+        Ok(ret)
+    }
+
+    // expression '[' expression ']', or expression '[' start? ':' end? (':' step?)? ']' for a slice
+    fn index_expression(&self, left: Expression, optional: bool) -> Result<Expression, Box<dyn Error>> {
+        let mut bounds = vec![self.slice_bound()?];
+        while bounds.len() < 3 {
+            match self.peek_token()?.kind {
+                TokenKind::Colon => {
+                    self.next_token();
+                    bounds.push(self.slice_bound()?);
+                }
+                // '::' of 'a[::2]' omits the end
+                TokenKind::PathSep if bounds.len() == 1 => {
+                    self.next_token();
+                    bounds.push(None);
+                    bounds.push(self.slice_bound()?);
+                }
+                _ => break,
+            }
+        }
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::RSquareBracket)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::RSquareBracket], kind))?
+        }
+
+        let mut bounds = bounds.into_iter();
+        let start = bounds.next().flatten();
+        if bounds.len() == 0 {
+            return match start {
+                Some(index) => Ok(Expression::Index(IndexExpression::new(left, index, optional))),
+                None => Err(ParseError::NoSuchExpressionStartWith(TokenKind::RSquareBracket))?,
+            };
+        }
+        let end = bounds.next().flatten();
+        let step = bounds.next().flatten();
+        Ok(Expression::Slice(SliceExpression::new(left, start, end, step, optional)))
+    }
+
+    // A bound of a slice, which is omitted if followed by ':' or ']'
+    fn slice_bound(&self) -> Result<Option<Expression>, Box<dyn Error>> {
+        match self.peek_token()?.kind {
+            TokenKind::Colon | TokenKind::PathSep | TokenKind::RSquareBracket => Ok(None),
+            _ => {
+                self.next_token();
+                Ok(Some(self.expression(PriorityOrder::Lowest)?))
+            }
+        }
+    }
+
+    // expression '.' identifier ('(' arguments ')')?
+    fn member(&self, left: Expression, optional: bool) -> Result<Expression, Box<dyn Error>> {
+        // 'tuple.0' is a field named by the index
+        if self.expect_peek(TokenKind::Int)? {
+            let name = Identifier::new(self.curr_token()?.literal.to_string());
+            return Ok(Expression::Field(FieldExpression::new(left, name, optional)));
+        }
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::Ident)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::Ident, TokenKind::Int], kind))?
+        }
+        let name = self.identifier()?;
+
+        if !self.expect_peek(TokenKind::LParenthesis)? {
+            return Ok(Expression::Field(FieldExpression::new(left, name, optional)));
+        }
+        let args = self.call_arguments()?;
+
+        Ok(Expression::MethodCall(MethodCallExpression::new(left, name, args, optional)))
+    }
+
+    // identifier '::' identifier. In an index, 'i::n' is a path as well, so a slice stepping by a
+    // variable is written as 'a[i::(n)]'. '::' followed by other expressions starts a slice.
+    fn is_path(&self) -> Result<bool, Box<dyn Error>> {
+        Ok(self.peek_token_is(TokenKind::PathSep)? && self.nth_token(2)?.kind == TokenKind::Ident)
+    }
+
+    // identifier '::' identifier
+    fn path(&self) -> Result<PathExpression, Box<dyn Error>> {
+        let ty = self.identifier()?;
+        self.next_token();
+
+        let kind = self.peek_token()?.kind;
+        if !self.expect_peek(TokenKind::Ident)? {
+            Err(ParseError::InvalidTokenFound(vec![TokenKind::Ident], kind))?
+        }
+        let name = self.identifier()?;
+
+        Ok(PathExpression::new(ty, name))
+    }
+
+    // identifier '{' '}' or identifier '{' identifier ':' starts a struct literal
+    fn is_struct_literal(&self) -> Result<bool, Box<dyn Error>> {
+        if !self.peek_token_is(TokenKind::LCurlyBracket)? {
+            return Ok(false);
+        }
+        match self.nth_token(2)?.kind {
+            TokenKind::RCurlyBracket => Ok(true),
+            TokenKind::Ident => Ok(self.nth_token(3)?.kind == TokenKind::Colon),
+            _ => Ok(false),
+        }
+    }
+
+    // identifier '{' identifier ':' expression, ... '}'
+    fn struct_literal(&self) -> Result<StructLiteral, Box<dyn Error>> {
+        let name = self.identifier()?;
+        self.next_token();
+
+        let mut fields = Vec::new();
+        while !self.expect_peek(TokenKind::RCurlyBracket)? {
+            self.next_token();
+            let field = self.identifier()?;
+
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::Colon)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Colon], kind))?
+            }
+
+            self.next_token();
+            fields.push((field, self.expression(PriorityOrder::Lowest)?));
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RCurlyBracket)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RCurlyBracket], kind))?
+            }
+        }
+
+        Ok(StructLiteral::new(name, fields))
+    }
+
+    // expression '?.' followed by '(' arguments ')', '[' expression ']' or identifier '(' arguments ')'
+    fn optional_chain(&self, left: Expression) -> Result<Expression, Box<dyn Error>> {
+        match self.peek_token()?.kind {
+            TokenKind::Ident => self.member(left, true),
+            TokenKind::LParenthesis => {
+                self.next_token();
+                Ok(Expression::Call(self.call_expression(left, true)?))
+            }
+            TokenKind::LSquareBracket => {
+                self.next_token();
+                self.index_expression(left, true)
+            }
+            kind => {
+                Err(
+                    ParseError::InvalidTokenFound(
+                        vec![TokenKind::LParenthesis, TokenKind::LSquareBracket, TokenKind::Ident], kind
+                    )
+                )?
+            }
+        }
     }
 
-    fn call_arguments(&self) -> Result<Vec<Expression>, Box<dyn Error>> {
+    // '[' expression, ... ']'
+    fn array_literal(&self) -> Result<ArrayLiteral, Box<dyn Error>> {
+        let elements = self.expression_list(TokenKind::RSquareBracket)?;
+        Ok(ArrayLiteral::new(elements))
+    }
+
+    // '#{' expression, ... '}'
+    fn set_literal(&self) -> Result<SetLiteral, Box<dyn Error>> {
+        let elements = self.expression_list(TokenKind::RCurlyBracket)?;
+        Ok(SetLiteral::new(elements))
+    }
+
+    // '{' expression ':' expression, ... '}'
+    // '{' starts a hash literal if its first expression is followed by ':', or a block otherwise.
+    // This is decided without backtracking, so nested braces are parsed in linear time.
+    fn hash_or_blk(&self) -> Result<Expression, Box<dyn Error>> {
+        if self.expect_peek(TokenKind::RCurlyBracket)? {
+            return Ok(Expression::Hash(HashLiteral::new(Vec::new())));
+        }
+
+        self.next_token();
+        let starts_statement = match self.curr_token()?.kind {
+            TokenKind::Let | TokenKind::Return | TokenKind::Defer | TokenKind::Const
+            | TokenKind::Struct | TokenKind::Enum | TokenKind::Impl | TokenKind::For
+            | TokenKind::Yield | TokenKind::Import | TokenKind::Export => true,
+            TokenKind::Function => self.peek_token_is(TokenKind::Ident)?,
+            _ => false,
+        };
+        if starts_statement {
+            return Ok(Expression::Blk(self.blk_rest(Vec::new())?));
+        }
+
+        let mut first = self.expression(PriorityOrder::Lowest)?;
+        if self.expect_peek(TokenKind::Colon)? {
+            return Ok(Expression::Hash(self.hash_literal(first)?));
+        }
+        if self.peek_token_is(TokenKind::Comma)? {
+            first = Expression::Assign(Box::new(self.multiple_assign(first)?));
+        }
+
+        self.expect_peek(TokenKind::Semicolon)?;
+        self.next_token();
+        Ok(Expression::Blk(self.blk_rest(vec![Parser::exp_to_statement(first)])?))
+    }
+
+    // Pairs of hash literal following the first key and ':'
+    fn hash_literal(&self, first_key: Expression) -> Result<HashLiteral, Box<dyn Error>> {
+        let mut pairs = Vec::new();
+        let mut key = first_key;
+
+        loop {
+            self.next_token();
+            let value = self.expression(PriorityOrder::Lowest)?;
+            pairs.push((key, value));
+
+            let kind = self.peek_token()?.kind;
+            if !self.peek_token_is(TokenKind::RCurlyBracket)? && !self.expect_peek(TokenKind::Comma)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Comma, TokenKind::RCurlyBracket], kind))?
+            }
+            if self.expect_peek(TokenKind::RCurlyBracket)? {
+                break;
+            }
+
+            self.next_token();
+            key = self.expression(PriorityOrder::Lowest)?;
+
+            let kind = self.peek_token()?.kind;
+            if !self.expect_peek(TokenKind::Colon)? {
+                Err(ParseError::InvalidTokenFound(vec![TokenKind::Colon], kind))?
+            }
+        }
+
+        Ok(HashLiteral::new(pairs))
+    }
+
+    // Comma separated expressions which is terminated by 'end'
+    fn expression_list(&self, end: TokenKind) -> Result<Vec<Expression>, Box<dyn Error>> {
         let mut ret = Vec::new();
 
-        if self.peek_token_is(TokenKind::RParenthesis)? {
+        if self.peek_token_is(end)? {
             self.next_token();
             return Ok(ret);
         } else {
@@ -330,8 +1481,8 @@ impl<'a> Parser<'a> {
         }
 
         let kind = self.peek_token()?.kind;
-        if !self.expect_peek(TokenKind::RParenthesis)? {
-            Err(ParseError::InvalidTokenFound(vec![TokenKind::RParenthesis], kind))?
+        if !self.expect_peek(end)? {
+            Err(ParseError::InvalidTokenFound(vec![end], kind))?
         } else {
             Ok(ret)
         }
@@ -340,20 +1491,73 @@ impl<'a> Parser<'a> {
 
 // Helper functions
 impl<'a> Parser<'a> {
-    fn curr_token(&self) -> Result<Token, ParseError> {
-        match self.token.get(self.curr.get() + 0) {
+    fn curr_token(&self) -> Result<Token<'_>, ParseError> {
+        match self.token.get(self.curr.get()) {
             Some(ret) => Ok(*ret),
             None => Err(ParseError::FailedToReadToken),
         }
     }
 
-    fn peek_token(&self) -> Result<Token, ParseError> {
+    fn peek_token(&self) -> Result<Token<'_>, ParseError> {
         match self.token.get(self.curr.get() + 1) {
             Some(ret) => Ok(*ret),
             None => Err(ParseError::FailedToReadToken),
         }
     }
 
+    // Define the binding in the innermost scope. An immutable binding can't be redefined in the
+    // same scope, but can be shadowed in inner scopes.
+    fn define(&self, name: &str, binding: Binding) -> Result<(), ParseError> {
+        let mut scopes = self.scopes.borrow_mut();
+        if let Some(scope) = scopes.last_mut() {
+            if matches!(scope.get(name), Some(Binding::Immutable | Binding::Const)) {
+                return Err(ParseError::ImmutableRedefined(name.to_string()));
+            }
+            scope.insert(name.to_string(), binding);
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, name: &str) -> Option<Binding> {
+        self.scopes.borrow().iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    // Literals, and operators and collections of them or other constants
+    fn is_constant(&self, exp: &Expression) -> bool {
+        match exp {
+            Expression::Int(_) | Expression::Bool(_) | Expression::Str(_) | Expression::Null(_) => true,
+            Expression::Ident(ident) => self.resolve(&ident.name) == Some(Binding::Const),
+            Expression::Interp(interp) => interp.parts.iter().all(|part| match part {
+                StringPart::Text(_) => true,
+                StringPart::Exp(exp) => self.is_constant(exp),
+            }),
+            Expression::Array(ArrayLiteral { elements })
+            | Expression::Tuple(TupleLiteral { elements })
+            | Expression::Set(SetLiteral { elements }) => {
+                elements.iter().all(|elem| self.is_constant(elem))
+            }
+            Expression::Hash(hash) => {
+                hash.pairs.iter().all(|(key, value)| self.is_constant(key) && self.is_constant(value))
+            }
+            Expression::Prefix(prefix) => {
+                matches!(prefix.operator, TokenKind::Bang | TokenKind::Minus) && self.is_constant(&prefix.rhs_exp)
+            }
+            Expression::Infix(infix) => {
+                !matches!(infix.operator, TokenKind::Pipeline | TokenKind::ComposeLeft | TokenKind::ComposeRight)
+                    && self.is_constant(&infix.lhs_exp)
+                    && self.is_constant(&infix.rhs_exp)
+            }
+            _ => false,
+        }
+    }
+
+    fn nth_token(&self, n: usize) -> Result<Token<'_>, ParseError> {
+        match self.token.get(self.curr.get() + n) {
+            Some(ret) => Ok(*ret),
+            None => Err(ParseError::FailedToReadToken),
+        }
+    }
+
     fn curr_token_is(&self, kind: TokenKind) -> Result<bool, ParseError> {
         match self.curr_token()?.kind {
             val if val == kind => Ok(true),
@@ -381,14 +1585,46 @@ impl<'a> Parser<'a> {
         self.curr.set(self.curr.get().wrapping_add(1));
     }
 
+    // Postfix operators chained to an expression, such as calls, indexes and fields
+    fn is_link(kind: TokenKind) -> bool {
+        matches!(
+            kind,
+            TokenKind::LParenthesis
+            | TokenKind::LSquareBracket
+            | TokenKind::OptChain
+            | TokenKind::Dot
+            | TokenKind::Inc
+            | TokenKind::Dec
+            | TokenKind::Question
+        )
+    }
+
+    // Text of a string token, in which '$${' is written for a literal '${'
+    fn unescape(text: &str) -> String {
+        text.replace("$${", "${")
+    }
+
     fn token_to_order(kind: TokenKind) -> PriorityOrder {
         match kind {
+            TokenKind::Assign                      => PriorityOrder::Assign,
+            TokenKind::Pipeline                    => PriorityOrder::Pipeline,
+            TokenKind::ComposeRight
+            | TokenKind::ComposeLeft               => PriorityOrder::Compose,
+            TokenKind::Coalesce                    => PriorityOrder::Coalesce,
             TokenKind::Eq       | TokenKind::NotEq => PriorityOrder::Equals,
-            TokenKind::LT       | TokenKind::GT    => PriorityOrder::LessGreater,
-            TokenKind::Plus     | TokenKind::Minus => PriorityOrder::Sum,
-            TokenKind::Asterisk | TokenKind::Slash => PriorityOrder::Product,
+            TokenKind::LT       | TokenKind::GT
+            | TokenKind::LtEq   | TokenKind::GtEq
+            | TokenKind::In                        => PriorityOrder::LessGreater,
+            TokenKind::Plus     | TokenKind::Minus
+            | TokenKind::Pipe                      => PriorityOrder::Sum,
+            TokenKind::Asterisk | TokenKind::Slash
+            | TokenKind::Ampersand                 => PriorityOrder::Product,
             TokenKind::LParenthesis                => PriorityOrder::Call,
+            TokenKind::LSquareBracket
+            | TokenKind::OptChain
+            | TokenKind::Dot                       => PriorityOrder::Index,
             TokenKind::Inc      | TokenKind::Dec   => PriorityOrder::Postfix,
+            TokenKind::Question                    => PriorityOrder::Postfix,
             _ => PriorityOrder::Lowest,
         }
     }
@@ -1,11 +1,16 @@
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub enum PriorityOrder {
     Lowest,
+    Assign,      // '='
+    Pipeline,    // '|>'
+    Compose,     // '>>' or '<<'
+    Coalesce,    // '??'
     Equals,      // '==' or '!='
-    LessGreater, // '>' or '<'
-    Sum,         // '+' or '-'
-    Product,     // '*' or '/'
+    LessGreater, // '>', '<', '>=', '<=' or 'in'
+    Sum,         // '+', '-' or '|'
+    Product,     // '*', '/' or '&'
     Prefix,      // '!' or '-'
     Call,        // add(...)
+    Index,       // array[...]
     Postfix,     // 'hoge++' or 'hoge--'
 }
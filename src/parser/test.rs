@@ -3,7 +3,7 @@
 use crate::{
     lexer::Lexer, 
     parser::Parser, 
-    ast::{Program, Statement, Node}
+    ast::{Program, Statement, Expression, Node}
 };
 
 #[test]
@@ -22,6 +22,41 @@ fn test_let_statements() {
     }
 }
 
+#[test]
+fn test_destructuring_let_statements() {
+    let input = "
+        let [a, b, ...rest] = xs;
+        let {name, age: [x, _], ...} = user;
+        let [[c], {\"first name\": n}] = y;";
+
+    let program = parse_input(input);
+    test_program_length(&program, 3);
+
+    let tests = vec![
+        "let [a, b, ...rest] = xs;",
+        "let {name, age: [x, _], ..._} = user;",
+        "let [[c], {first name: n}] = y;",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    let errors = vec![
+        ("let 1 = x;", "1"),
+        ("let [a, 0..9] = x;", "[a, 0..9]"),
+        ("let {kind: \"a\"} = x;", "{kind: \"a\"}"),
+        ("fn f(null) { 0 }", "null"),
+        ("for (a | 1 in xs) { a }", "a | 1"),
+    ];
+    for (input, pattern) in errors {
+        let lexer = Lexer::new(input);
+        match Parser::new(lexer.tokenize()).parse() {
+            Ok(program) => panic!("{} should fail to parse, got {}", input, program.string()),
+            Err(err) => assert_eq!(err.to_string(), format!("Refutable pattern can only be used in match: {}", pattern)),
+        }
+    }
+}
+
 #[test]
 fn test_ret_statements() {
     let input = "
@@ -173,6 +208,52 @@ fn test_function_statement() {
     test_exp_statement(stmt, "fn(a, b) { (a + b) }".to_string());
 }
 
+#[test]
+fn test_destructuring_function_statement() {
+    let input = "fn({name, age}, [x, ...xs]) { name }";
+
+    let program = parse_input(input);
+    test_program_length(&program, 1);
+
+    let stmt = &program.statements[0];
+    test_exp_statement(stmt, "fn({name, age}, [x, ...xs]) { name }".to_string());
+}
+
+#[test]
+fn test_function_parameters() {
+    let input = "
+        fn(x, y = x * 2, ...rest) { x };
+        fn() { 1 };
+        f(1, y: 2, ...xs);";
+
+    let program = parse_input(input);
+    test_program_length(&program, 3);
+
+    let tests = vec![
+        "fn(x, y = (x * 2), ...rest) { x }",
+        "fn() { 1 }",
+        "f(1, y: 2, ...xs)",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
+#[test]
+fn test_function_declaration() {
+    let input = "
+        fn add(a, b = 1) { a + b }
+        fn(a) { a };";
+
+    let program = parse_input(input);
+    test_program_length(&program, 2);
+
+    let tests = vec!["fn add(a, b = 1) { (a + b) }", "fn(a) { a }"];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
 #[test]
 fn test_call_expression() {
     let input = "add(10, a * (10 + b), c == d)";
@@ -195,6 +276,590 @@ fn test_str_expression() {
     test_exp_statement(stmt, "\"hello world\"".to_string());
 }
 
+#[test]
+fn test_collection_expression() {
+    let input = "
+        [1, 2 * 3];
+        {\"a\": 1, true: [2]};
+        a[1 + 1];
+        f(a)[0][1];";
+
+    let program = parse_input(input);
+    test_program_length(&program, 4);
+
+    let tests = vec![
+        "[1, (2 * 3)]",
+        "{\"a\": 1, true: [2]}",
+        "(a[(1 + 1)])",
+        "((f(a)[0])[1])",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
+#[test]
+fn test_null_expression() {
+    let input = "
+        null;
+        a ?? b == c;
+        a?.[0] ?? f?.(1);
+        !null;";
+
+    let program = parse_input(input);
+    test_program_length(&program, 4);
+
+    let tests = vec![
+        "null",
+        "(a ?? (b == c))",
+        "((a?.[0]) ?? f?.(1))",
+        "(!null)",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
+#[test]
+fn test_interpolated_string() {
+    let input = "\"Hello, ${name}! You are ${age + 1}\";";
+
+    let program = parse_input(input);
+    test_program_length(&program, 1);
+
+    let stmt = &program.statements[0];
+    test_exp_statement(stmt, "\"Hello, ${name}! You are ${(age + 1)}\"".to_string());
+}
+
+#[test]
+fn test_match_expression() {
+    let input = "
+        match (x) {
+            0 => \"zero\",
+            1 | 2 => \"small\",
+            -5..=-1 => \"negative\",
+            n if n < 100 => { n * 2 }
+            _ => null,
+        };";
+
+    let program = parse_input(input);
+    test_program_length(&program, 1);
+
+    let stmt = &program.statements[0];
+    test_exp_statement(
+        stmt,
+        "match ( x ) { 0 => { \"zero\" }, 1 | 2 => { \"small\" }, -5..=-1 => { \"negative\" }, \
+         n if (n < 100) => { (n * 2) }, _ => { null } }".to_string()
+    );
+}
+
+#[test]
+fn test_unreachable_match_arm() {
+    let input = "
+        match (x) {
+            1..10 => 1,
+            5 => 2,
+            n if n < 0 => 3,
+            -1 => 4,
+            2..=3 | 7 => 5,
+            \"a\" | \"b\" => 6,
+            \"b\" => 7,
+            n => 8,
+            _ => 9,
+        };";
+
+    let lexer  = Lexer::new(input);
+    let parser = Parser::new(lexer.tokenize());
+    parser.parse().unwrap_or_else(|err| panic!("{}", err));
+
+    let tests = vec![
+        "Unreachable match arm: 5",
+        "Unreachable match arm: 2..=3 | 7",
+        "Unreachable match arm: \"b\"",
+        "Unreachable match arm: _",
+    ];
+    assert_eq!(parser.warnings(), tests);
+}
+
+#[test]
+fn test_pipeline_expression() {
+    let input = "
+        input |> parse |> filter(_, pred) |> format;
+        a ?? b |> f >> g << h;
+        x |> f(1 + 2);";
+
+    let program = parse_input(input);
+    test_program_length(&program, 3);
+
+    let tests = vec![
+        "(((input |> parse) |> filter(_, pred)) |> format)",
+        "((a ?? b) |> ((f >> g) << h))",
+        "(x |> f((1 + 2)))",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
+#[test]
+fn test_method_call_expression() {
+    let input = "
+        \"abc\".len();
+        xs.push(4).len() + 1;
+        user?.greet(\"hi\", loud: true);
+        -a.abs();";
+
+    let program = parse_input(input);
+    test_program_length(&program, 4);
+
+    let tests = vec![
+        "\"abc\".len()",
+        "(xs.push(4).len() + 1)",
+        "user?.greet(\"hi\", loud: true)",
+        "(-a.abs())",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
+#[test]
+fn test_assignment() {
+    let input = "
+        a = b = c + 1;
+        a[0][1] = f(2);";
+
+    let program = parse_input(input);
+    test_program_length(&program, 2);
+
+    let tests = vec![
+        "(a = (b = (c + 1)))",
+        "(((a[0])[1]) = f(2))",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    for input in ["1 = 2;", "f() = 1;", "a + b = 1;", "a?.[0] = 1;"] {
+        let lexer  = Lexer::new(input);
+        let parser = Parser::new(lexer.tokenize());
+        assert!(parser.parse().is_err(), "{} should be rejected", input);
+    }
+}
+
+#[test]
+fn test_struct_and_assignment() {
+    let input = "
+        struct Point { x, y }
+        Point { x: 1 + 2, y: f(3) };
+        p.x + q?.y;
+        p.x = a = b + 1;
+        a[0].y = 2;
+        Empty {};";
+
+    let program = parse_input(input);
+    test_program_length(&program, 6);
+
+    let tests = vec![
+        "struct Point { x, y }",
+        "Point { x: (1 + 2), y: f(3) }",
+        "(p.x + q?.y)",
+        "(p.x = (a = (b + 1)))",
+        "((a[0]).y = 2)",
+        "Empty {  }",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    for input in ["1 = 2;", "f() = 1;", "a + b = 1;", "p?.x = 1;"] {
+        let lexer  = Lexer::new(input);
+        let parser = Parser::new(lexer.tokenize());
+        assert!(parser.parse().is_err(), "{} should be rejected", input);
+    }
+}
+
+#[test]
+fn test_enum_and_variant_pattern() {
+    let input = "
+        enum State { Loading, Ready(data), Failed(code, reason) }
+        State::Ready(1);
+        match (s) { State::Loading => 0, State::Failed(_, [r]) | State::Ready(r) => r };";
+
+    let program = parse_input(input);
+    test_program_length(&program, 3);
+
+    let tests = vec![
+        "enum State { Loading, Ready(data), Failed(code, reason) }",
+        "State::Ready(1)",
+        "match ( s ) { State::Loading => { 0 }, State::Failed(_, [r]) | State::Ready(r) => { r } }",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    let input = "match (s) { State::Ready(_) => 1, State::Ready(2) => 2, State::Loading => 3 };";
+    let lexer  = Lexer::new(input);
+    let parser = Parser::new(lexer.tokenize());
+    parser.parse().unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(parser.warnings(), vec!["Unreachable match arm: State::Ready(2)".to_string()]);
+}
+
+#[test]
+fn test_impl_statement() {
+    let input = "
+        impl Point {
+            fn origin() { Point { x: 0, y: 0 } }
+            fn norm(self) { self.x * self.x + self.y * self.y }
+        }
+        Point::origin().norm();";
+
+    let program = parse_input(input);
+    test_program_length(&program, 2);
+
+    let tests = vec![
+        "impl Point { fn origin() { Point { x: 0, y: 0 } } fn norm(self) { ((self.x * self.x) + (self.y * self.y)) } }",
+        "Point::origin().norm()",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
+#[test]
+fn test_try_expression() {
+    let input = "
+        try { f(); } catch (e) { e.message } finally { close(); };
+        try { 1 } finally { 2 };
+        let x = y ?? throw \"missing \" + name;";
+
+    let program = parse_input(input);
+    test_program_length(&program, 3);
+
+    let tests = vec![
+        "try { f() } catch ( e ) { e.message } finally { close() }",
+        "try { 1 } finally { 2 }",
+        "let x = (y ?? throw (\"missing \" + name));",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    let lexer  = Lexer::new("try { 1 };");
+    let parser = Parser::new(lexer.tokenize());
+    assert!(parser.parse().is_err());
+}
+
+#[test]
+fn test_question_operator() {
+    let input = "
+        f(x)? + g()?;
+        a?.b ?? c?;";
+
+    let program = parse_input(input);
+    test_program_length(&program, 2);
+
+    let tests = vec![
+        "((?f(x)) + (?g()))",
+        "(a?.b ?? (?c))",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
+#[test]
+fn test_defer_statement() {
+    let input = "
+        defer close(f);
+        fn g() { defer log(1 + 2); 3 }";
+
+    let program = parse_input(input);
+    test_program_length(&program, 2);
+
+    let tests = vec![
+        "defer close(f);",
+        "fn g() { defer log((1 + 2)); 3 }",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
+#[test]
+fn test_mutability() {
+    let input = "
+        let mut x = 1;
+        const LIMIT = 10 * 2;
+        const NAMES = [\"a\", \"b${LIMIT}\"];
+        x = LIMIT + 1;
+        let f = fn(x) { x = 2; let y = 1; fn() { let y = 2; y } };";
+
+    let program = parse_input(input);
+    test_program_length(&program, 5);
+
+    let tests = vec![
+        "let mut x = 1;",
+        "const LIMIT = (10 * 2);",
+        "const NAMES = [\"a\", \"b${LIMIT}\"];",
+        "(x = (LIMIT + 1))",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    let tests = vec![
+        ("let x = 1; x = 2;", "Cannot assign to immutable binding x"),
+        ("let p = {}; p.a[0] = 2;", "Cannot assign to immutable binding p"),
+        ("let x = 1; let f = fn() { x = 2 };", "Cannot assign to immutable binding x"),
+        ("const X = 1; X = 2;", "Cannot assign to immutable binding X"),
+        ("let x = 1; let x = 2;", "Cannot redefine immutable binding x"),
+        ("const X = 1; let mut X = 2;", "Cannot redefine immutable binding X"),
+        ("fn f() { 1 } f = 2;", "Cannot assign to immutable binding f"),
+        ("struct P { x } P = 3;", "Cannot assign to immutable binding P"),
+        ("enum E { A } let E = 4;", "Cannot redefine immutable binding E"),
+        ("let y = 1; const X = y + 1;", "Not a constant expression: (y + 1)"),
+        ("const X = f();", "Not a constant expression: f()"),
+    ];
+    for (input, msg) in tests {
+        let lexer  = Lexer::new(input);
+        let parser = Parser::new(lexer.tokenize());
+        match parser.parse() {
+            Ok(_) => panic!("{} should be rejected", input),
+            Err(err) => assert_eq!(err.to_string(), msg),
+        }
+    }
+}
+
+#[test]
+fn test_blk_expression() {
+    let input = "
+        { let x = 1; x + 1 }
+        {\"a\": 1, \"b\": 2};
+        let y = { f(1); 2 };
+        {};
+        { { 1 } }";
+
+    let program = parse_input(input);
+    test_program_length(&program, 5);
+
+    let tests = vec![
+        "{ let x = 1; (x + 1) }",
+        "{\"a\": 1, \"b\": 2}",
+        "let y = { f(1) 2 };",
+        "{}",
+        "{ { 1 } }",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+    assert!(matches!(program.statements[0], Statement::Blk(_)));
+    assert!(matches!(program.statements[4], Statement::Blk(_)));
+}
+
+#[test]
+fn test_generator() {
+    let input = "
+        for ([k, v] in pairs(h)) { puts(k); }
+        fn g(xs) { for (x in xs) { yield x * 2; } }
+        let f = fn() { 1 };";
+
+    let program = parse_input(input);
+    test_program_length(&program, 3);
+
+    let tests = vec![
+        "for ([k, v] in pairs(h)) { puts(k) }",
+        "fn g(xs) { for (x in xs) { yield (x * 2); } }",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    // Only functions containing 'yield' directly are generators
+    let generator = |stmt: &Statement| match stmt {
+        Statement::Func(stmt) => stmt.func.generator,
+        Statement::Let(stmt) => matches!(&stmt.rhs_exp, Expression::Func(func) if func.generator),
+        _ => false,
+    };
+    assert!(generator(&program.statements[1]));
+    assert!(!generator(&program.statements[2]));
+
+    let program = parse_input("fn outer() { let inner = fn() { yield 1; }; inner }");
+    assert!(!generator(&program.statements[0]));
+
+    // A generator is suspended only between statements of blocks, 'if' and 'for'
+    let program = parse_input("fn g(x) { { yield 1; } if (x) { for (i in x) { yield i; } } else { yield 2; }; }");
+    assert!(generator(&program.statements[0]));
+
+    let misplaced = "yield can only be used in blocks, if and for statements of a generator";
+    let tests = vec![
+        ("yield 1;", "yield used outside of function"),
+        ("fn g(x) { match (x) { _ => { yield 1; } } }", misplaced),
+        ("fn g() { try { yield 1; } finally {} }", misplaced),
+        ("fn g() { let x = { yield 1; }; }", misplaced),
+        ("fn g(x) { let y = if (x) { yield 1; } else {}; }", misplaced),
+        ("fn g(x) { if (x) { yield 1; } else {} + 1; }", misplaced),
+        ("fn g() { defer { yield 1; }; }", misplaced),
+    ];
+    for (input, msg) in tests {
+        let lexer  = Lexer::new(input);
+        let parser = Parser::new(lexer.tokenize());
+        match parser.parse() {
+            Ok(_) => panic!("{} should be rejected", input),
+            Err(err) => assert_eq!(err.to_string(), msg),
+        }
+    }
+}
+
+#[test]
+fn test_tuple() {
+    let input = "
+        (1, a + b);
+        (1,);
+        ();
+        (1);
+        t.0.1;
+        let (a, (b, c)) = f();
+        x, y = y, x;";
+
+    let program = parse_input(input);
+    test_program_length(&program, 7);
+
+    let tests = vec![
+        "(1, (a + b))",
+        "(1,)",
+        "()",
+        "1",
+        "t.0.1",
+        "let (a, (b, c)) = f();",
+        "((x, y) = (y, x))",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    let tests = vec![
+        ("let a = 1; let mut b = 2; a, b = b, a;", "Cannot assign to immutable binding a"),
+        ("let mut a = 1; a, 1 = 1, 2;", "Invalid target of assignment: 1"),
+        ("(1, 2", "Expected [Comma, RParenthesis], but found Eof"),
+    ];
+    for (input, msg) in tests {
+        let lexer  = Lexer::new(input);
+        let parser = Parser::new(lexer.tokenize());
+        match parser.parse() {
+            Ok(_) => panic!("{} should be rejected", input),
+            Err(err) => assert_eq!(err.to_string(), msg),
+        }
+    }
+}
+
+#[test]
+fn test_set() {
+    let tests = vec![
+        ("#{1, a + b};", "#{1, (a + b)}"),
+        ("#{};", "#{}"),
+        ("a | b & c - d;", "((a | (b & c)) - d)"),
+        ("x in s == y <= t;", "((x in s) == (y <= t))"),
+        ("a >= b + 1;", "(a >= (b + 1))"),
+    ];
+    for (input, expect) in tests {
+        let program = parse_input(input);
+        test_program_length(&program, 1);
+        test_exp_statement(&program.statements[0], expect.to_string());
+    }
+}
+
+#[test]
+fn test_slice() {
+    let tests = vec![
+        ("s[1:4];", "(s[1:4])"),
+        ("s[:-1];", "(s[:(-1)])"),
+        ("xs[::2];", "(xs[::2])"),
+        ("xs[i::-1];", "(xs[i::(-1)])"),
+        ("xs[a + 1:];", "(xs[(a + 1):])"),
+        ("xs?.[:n][0];", "((xs?.[:n])[0])"),
+        ("xs[i::(n + 1)];", "(xs[i::(n + 1)])"),
+        ("xs[i::n];", "(xs[i::n])"),
+        ("xs[Color::Red];", "(xs[Color::Red])"),
+        ("xs[Int::one()];", "(xs[Int::one()])"),
+        ("[Color::Red][0];", "([Color::Red][0])"),
+    ];
+    for (input, expect) in tests {
+        let program = parse_input(input);
+        test_program_length(&program, 1);
+        test_exp_statement(&program.statements[0], expect.to_string());
+    }
+
+    let errors = vec![
+        ("xs[];", "No such expression that start with RSquareBracket"),
+        ("xs[1:2:3:4];", "Expected [RSquareBracket], but found Colon"),
+        ("xs[1:2::3];", "Expected [RSquareBracket], but found PathSep"),
+    ];
+    for (input, msg) in errors {
+        let lexer = Lexer::new(input);
+        match Parser::new(lexer.tokenize()).parse() {
+            Ok(program) => panic!("{} should fail to parse, got {}", input, program.string()),
+            Err(err) => assert_eq!(err.to_string(), msg),
+        }
+    }
+}
+
+#[test]
+fn test_import_export() {
+    let input = "
+        import \"lib/math.mk\" as math;
+        export let [a, b] = [1, 2];
+        export fn add(x, y) { x + y }
+        export const PI = 3;";
+
+    let program = parse_input(input);
+    test_program_length(&program, 4);
+
+    let tests = vec![
+        "import \"lib/math.mk\" as math;",
+        "export let [a, b] = [1, 2];",
+        "export fn add(x, y) { (x + y) }",
+        "export const PI = 3;",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+
+    let tests = vec![
+        ("fn f() { import \"a.mk\" as a; }", "import can only be used at the top level"),
+        ("{ export let x = 1; }", "export can only be used at the top level"),
+        ("export 1;", "Only let, const, fn, struct and enum can be exported, found Int"),
+        ("import \"a.mk\" as a; let a = 1;", "Cannot redefine immutable binding a"),
+        ("import \"a.mk\";", "Expected [As], but found Semicolon"),
+    ];
+    for (input, msg) in tests {
+        let lexer  = Lexer::new(input);
+        let parser = Parser::new(lexer.tokenize());
+        match parser.parse() {
+            Ok(_) => panic!("{} should be rejected", input),
+            Err(err) => assert_eq!(err.to_string(), msg),
+        }
+    }
+}
+
+#[test]
+fn test_macro_literal() {
+    let input = "
+        let unless = macro(cond, body) { quote(if (!unquote(cond)) { unquote(body) }) };
+        let m = macro() { quote(1) };";
+
+    let program = parse_input(input);
+    test_program_length(&program, 2);
+
+    let tests = vec![
+        "let unless = macro(cond, body) { quote(if ( (!unquote(cond)) ) { unquote(body) }) };",
+        "let m = macro() { quote(1) };",
+    ];
+    for (stmt, test) in program.statements.iter().zip(&tests) {
+        test_exp_statement(stmt, test.to_string());
+    }
+}
+
 fn test_exp_statement(stmt: &Statement, exp: String) {
     if stmt.string() != exp {
         panic!("The statement has different expression: expect {}, got {}", exp, stmt.string());
@@ -6,6 +6,10 @@ use crate::{
     eval::{Eval, env::Env, object::ObjectTrait},
 };
 
+// Reading input is given up after failing this many times in a row, since a broken stdin keeps
+// failing instead of giving the end of input
+pub const MAX_READ_ERRORS: usize = 3;
+
 pub enum ReplExecKind {
     Token,
     Ast,
@@ -30,15 +34,29 @@ impl Repl {
     pub fn start(&mut self, kind_exec: ReplExecKind, kind_input: ReplInputKind) {
         let env = Env::new();
         let mut eval = Eval::new(Rc::new(RefCell::new(env)));
+        let mut read_errors = 0;
         loop {
             print!(">> ");
-            stdout().flush().unwrap();
+            // Failing to flush only delays the prompt, so there is nothing to recover from.
+            let _ = stdout().flush();
 
             let mut buf = String::new();
-            match kind_input {
-                ReplInputKind::OneLine    => self.cin.read_line(&mut buf).unwrap(),
-                ReplInputKind::WhileCtrlD => self.cin.read_to_string(&mut buf).unwrap(),
+            let read = match kind_input {
+                ReplInputKind::OneLine    => self.cin.read_line(&mut buf),
+                ReplInputKind::WhileCtrlD => self.cin.read_to_string(&mut buf),
             };
+            match read {
+                Ok(0) => break,
+                Ok(_) => read_errors = 0,
+                Err(err) => {
+                    eprintln!("Failed to read input: {}", err);
+                    read_errors += 1;
+                    if read_errors >= MAX_READ_ERRORS {
+                        break;
+                    }
+                    continue;
+                }
+            }
             let lexer  = Lexer::new(buf.as_str());
 
             match kind_exec {
@@ -75,7 +93,13 @@ impl Repl {
                     let parser = Parser::new(lexer.tokenize());
                     match parser.parse() {
                         Ok(prg) => {
-                            println!("{}", eval.eval(prg).inspect());
+                            for warning in parser.warnings() {
+                                eprintln!("Warning: {}", warning);
+                            }
+                            match eval.expand_macros(prg) {
+                                Ok(prg) => println!("{}", eval.eval(prg).inspect()),
+                                Err(err) => println!("{}", err.inspect()),
+                            }
                         }
                         Err(err) => {
                             eprintln!("{}", err);
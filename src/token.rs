@@ -15,7 +15,10 @@ impl<'a> Token<'a> {
 
             TokenKind::Ident
             | TokenKind::Int
-            | TokenKind::Str => self.literal,
+            | TokenKind::Str
+            | TokenKind::InterpStart
+            | TokenKind::InterpMid
+            | TokenKind::InterpEnd => self.literal,
 
             TokenKind::Assign        => "=",
             TokenKind::Plus          => "+",
@@ -31,11 +34,32 @@ impl<'a> Token<'a> {
             TokenKind::RParenthesis  => ")",
             TokenKind::LCurlyBracket => "{",
             TokenKind::RCurlyBracket => "}",
+            TokenKind::LSquareBracket => "[",
+            TokenKind::RSquareBracket => "]",
+            TokenKind::Colon         => ":",
+            TokenKind::Pipe          => "|",
+            TokenKind::Dot           => ".",
+            TokenKind::Underscore    => "_",
+            TokenKind::Question      => "?",
+            TokenKind::Ampersand     => "&",
 
             TokenKind::Eq    => "==",
             TokenKind::NotEq => "!=",
             TokenKind::Inc   => "++",
             TokenKind::Dec   => "--",
+            TokenKind::Coalesce => "??",
+            TokenKind::OptChain => "?.",
+            TokenKind::FatArrow => "=>",
+            TokenKind::DotDot   => "..",
+            TokenKind::DotDotEq => "..=",
+            TokenKind::Ellipsis => "...",
+            TokenKind::Pipeline => "|>",
+            TokenKind::ComposeRight => ">>",
+            TokenKind::ComposeLeft  => "<<",
+            TokenKind::PathSep  => "::",
+            TokenKind::LtEq     => "<=",
+            TokenKind::GtEq     => ">=",
+            TokenKind::SetStart => "#{",
 
             TokenKind::Function => "fn",
             TokenKind::Let      => "let",
@@ -44,6 +68,25 @@ impl<'a> Token<'a> {
             TokenKind::If       => "if",
             TokenKind::Else     => "else",
             TokenKind::Return   => "return",
+            TokenKind::Null     => "null",
+            TokenKind::Match    => "match",
+            TokenKind::Struct   => "struct",
+            TokenKind::Enum     => "enum",
+            TokenKind::Impl     => "impl",
+            TokenKind::Throw    => "throw",
+            TokenKind::Try      => "try",
+            TokenKind::Catch    => "catch",
+            TokenKind::Finally  => "finally",
+            TokenKind::Defer    => "defer",
+            TokenKind::Mut      => "mut",
+            TokenKind::Const    => "const",
+            TokenKind::For      => "for",
+            TokenKind::In       => "in",
+            TokenKind::Yield    => "yield",
+            TokenKind::Macro    => "macro",
+            TokenKind::Import   => "import",
+            TokenKind::Export   => "export",
+            TokenKind::As       => "as",
         }
     }
 }
@@ -57,6 +100,9 @@ pub enum TokenKind {
     Ident,
     Int,
     Str,
+    InterpStart,   // '"text${'
+    InterpMid,     // '}text${'
+    InterpEnd,     // '}text"'
 
     // One-character token
     Assign,        // '='
@@ -73,12 +119,33 @@ pub enum TokenKind {
     RParenthesis,  // ')'
     LCurlyBracket, // '{'
     RCurlyBracket, // '}'
+    LSquareBracket, // '['
+    RSquareBracket, // ']'
+    Colon,         // ':'
+    Pipe,          // '|'
+    Dot,           // '.'
+    Underscore,    // '_'
+    Question,      // '?'
+    Ampersand,     // '&'
 
     // Two or more character token
     Eq,            // '=='
     NotEq,         // '!='
     Inc,           // '++',
     Dec,           // '--'
+    Coalesce,      // '??'
+    OptChain,      // '?.'
+    FatArrow,      // '=>'
+    DotDot,        // '..'
+    DotDotEq,      // '..='
+    Ellipsis,      // '...'
+    Pipeline,      // '|>'
+    ComposeRight,  // '>>'
+    ComposeLeft,   // '<<'
+    PathSep,       // '::'
+    LtEq,          // '<='
+    GtEq,          // '>='
+    SetStart,      // '#{'
 
     // Keyword
     Function, // 'fn'
@@ -88,4 +155,23 @@ pub enum TokenKind {
     If,       // 'if'
     Else,     // 'else'
     Return,   // 'return'
+    Null,     // 'null'
+    Match,    // 'match'
+    Struct,   // 'struct'
+    Enum,     // 'enum'
+    Impl,     // 'impl'
+    Throw,    // 'throw'
+    Try,      // 'try'
+    Catch,    // 'catch'
+    Finally,  // 'finally'
+    Defer,    // 'defer'
+    Mut,      // 'mut'
+    Const,    // 'const'
+    For,      // 'for'
+    In,       // 'in'
+    Yield,    // 'yield'
+    Macro,    // 'macro'
+    Import,   // 'import'
+    Export,   // 'export'
+    As,       // 'as'
 }